@@ -0,0 +1,121 @@
+//! 对比读取图片元素字段的三种方式：
+//! 启发式解码为JSON、按schema解码为JSON、借用字节的 `SchemaView`
+//!
+//! cargo bench --bench protobuf
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::json;
+
+// 主程序是二进制crate，这里直接引入相关模块的源码
+#[path = "../src/protobuf/mod.rs"]
+#[allow(dead_code, unused_imports)]
+mod protobuf;
+#[path = "../src/elem/schemas.rs"]
+#[allow(dead_code)]
+mod schemas;
+
+// 供protobuf模块中的测试代码引用
+mod elem {
+    pub(crate) use crate::schemas;
+}
+
+use protobuf::view::SchemaView;
+use protobuf::Protobuf;
+
+/// 构造与实际图片消息结构相同的 bytes_pb_elem
+fn image_payload() -> Vec<u8> {
+    let value = json!({
+        "bodies": [{
+            "index": {
+                "info": {
+                    "size": 183_412,
+                    "md5": "8f14e45fceea167a5a36dedd4bea2543",
+                    "sha1": "1b6453892473a467d07372d45eb05abc2031647a",
+                    "file_name": "8F14E45FCEEA167A5A36DEDD4BEA2543.jpg",
+                    "type": {"type": 1, "pic_format": 1000},
+                    "width": 1080,
+                    "height": 1920,
+                },
+                "file_uuid": "EhQnVxYwJqFZr6xH2k0sW5cNqSgWjxiUmAsg_wooxqW7k_uhiQMyBHByb2Q",
+                "store_id": 1,
+                "upload_time": 1_700_000_000,
+                "ttl": 2_592_000,
+            },
+            "picture": {
+                "url_path": "/download?appid=1406&fileid=EhQnVxYwJqFZr6xH2k0sW5cNqSgWjxiUmAsg",
+                "ext": {
+                    "original_parameter": "&spec=0",
+                    "big_parameter": "&spec=720",
+                    "thumb_parameter": "&spec=198",
+                },
+                "domain": "multimedia.nt.qq.com.cn",
+            },
+            "file_exist": true,
+        }],
+        "ext_biz_info": {
+            "pic": {
+                "biz_type": 0,
+                "text_summary": "[图片]",
+                "c2c_reserve": {"rkey": "&rkey=CAQSKAB6JWENi5LMtWVWVxS2RAZOwgFk"},
+            },
+            "busi_type": 10,
+        },
+    });
+    Protobuf::serialize_with(&value, &schemas::MSG_INFO).unwrap()
+}
+
+fn bench_image(c: &mut Criterion) {
+    let payload = image_payload();
+    let mut group = c.benchmark_group("image_elem");
+
+    group.bench_function("json_value", |b| {
+        b.iter(|| {
+            let fields = Protobuf::deserialize(black_box(&payload)).unwrap();
+            let value = serde_json::to_value(fields).unwrap();
+            let index = &value["1"]["1"];
+            black_box((
+                index["1"]["1"].as_u64(),
+                index["1"]["2"].as_str().map(str::to_string),
+                index["2"].as_str().map(str::to_string),
+                value["2"]["1"]["11"]["30"].as_str().map(str::to_string),
+            ))
+        })
+    });
+
+    group.bench_function("schema_json", |b| {
+        b.iter(|| {
+            let value = Protobuf::deserialize_with(black_box(&payload), &schemas::MSG_INFO).unwrap();
+            let index = &value["bodies"][0]["index"];
+            black_box((
+                index["info"]["size"].as_u64(),
+                index["info"]["md5"].as_str().map(str::to_string),
+                index["file_uuid"].as_str().map(str::to_string),
+                value["ext_biz_info"]["pic"]["c2c_reserve"]["rkey"].as_str().map(str::to_string),
+            ))
+        })
+    });
+
+    group.bench_function("schema_view", |b| {
+        b.iter(|| {
+            let view = SchemaView::new(black_box(&payload), &schemas::MSG_INFO);
+            let index = view.messages("bodies").next().and_then(|b| b.message("index")).unwrap();
+            let info = index.message("info").unwrap();
+            let rkey = view
+                .message("ext_biz_info")
+                .and_then(|e| e.message("pic"))
+                .and_then(|p| p.message("c2c_reserve"))
+                .and_then(|r| r.string("rkey"));
+            black_box((
+                info.uint("size"),
+                info.string("md5").map(str::to_string),
+                index.string("file_uuid").map(str::to_string),
+                rkey.map(str::to_string),
+            ))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_image);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+#![no_main]
+
+//! 对Protobuf解码器做模糊测试：任意输入都不能panic或栈溢出
+//!
+//! cargo +nightly fuzz run protobuf_decode
+
+use libfuzzer_sys::fuzz_target;
+
+// 主程序是二进制crate，这里直接引入protobuf模块的源码
+#[path = "../../src/protobuf/mod.rs"]
+#[allow(dead_code)]
+mod protobuf;
+
+use protobuf::Protobuf;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(fields) = Protobuf::deserialize(data) {
+        // 解码结果必须能重新编码
+        let _ = Protobuf::serialize(&fields);
+    }
+    let _ = Protobuf::inspect(data);
+});
@@ -3,11 +3,44 @@ use reqwest::Client;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use crate::cookie::LoginInfo;
+use crate::helper::Helper;
+use crate::login::refresh;
+use crate::profile::Profile;
+use crate::protobuf::Protobuf;
+use crate::protobuf::schema::{Field, FieldKind, Schema};
 use base64::{Engine as _, engine::general_purpose};
 
 const API_URL: &str = "https://myqq.qq.com/qunng/http2rpc/gotrpc/v1/";
 
+/// 微信小程序内的User-Agent
+pub const USER_AGENT: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 18_6_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 MicroMessenger/8.0.64(0x1800402b) NetType/WIFI Language/zh_CN";
+
+/// 好友列表请求的 bytes_trans_param
+static FRIEND_TRANS_PARAM: Schema = Schema {
+    name: "FriendTransParam",
+    fields: &[Field::packed(1, "field_ids", FieldKind::Int)],
+};
+
+/// 好友列表请求的资料字段ID
+const FRIEND_FIELD_IDS: [u32; 5] = [101, 103, 20002, 63019, 63020];
+
+/// 登录态失效时的retcode
+///
+/// 取自QQ网页接口常见的未登录返回值（-100、-3000、100000），尚未在本接口的抓包中逐一确认。
+const AUTH_EXPIRED_RETCODES: [i32; 3] = [-100, -3000, 100000];
+
+/// 检查登录态请求的retcode：0为有效，[`AUTH_EXPIRED_RETCODES`] 为已失效（`Ok(false)`），其他为错误
+fn login_state(retcode: i32) -> Result<bool> {
+    match retcode {
+        0 => Ok(true),
+        code if AUTH_EXPIRED_RETCODES.contains(&code) => Ok(false),
+        code => Err(anyhow::anyhow!("检查登录态失败: retcode={}", code)),
+    }
+}
+
 /// API响应结构
 #[derive(Debug, Deserialize)]
 pub struct ApiResponse {
@@ -18,7 +51,11 @@ pub struct ApiResponse {
 /// API客户端
 pub struct Api {
     client: Client,
-    login_info: LoginInfo,
+    api_url: String,
+    refresh_url: String,
+    login_info: Mutex<LoginInfo>,
+    /// 登录态失效时刷新票据并写回的cookie.json
+    cookie_path: Option<PathBuf>,
 }
 
 impl Api {
@@ -30,10 +67,45 @@ impl Api {
 
         Ok(Api {
             client,
-            login_info,
+            api_url: API_URL.to_string(),
+            refresh_url: refresh::refresh_url(),
+            login_info: Mutex::new(login_info),
+            cookie_path: None,
         })
     }
 
+    /// 登录态失效时用 `cookie_path` 中的refresh_token刷新票据并重试
+    pub fn with_refresh(mut self, cookie_path: PathBuf) -> Self {
+        self.cookie_path = Some(cookie_path);
+        self
+    }
+
+    /// 使用其他接口地址，用于连接本地的模拟服务
+    #[cfg(test)]
+    pub(crate) fn with_urls(mut self, api_url: &str, refresh_url: &str) -> Self {
+        self.api_url = api_url.to_string();
+        self.refresh_url = refresh_url.to_string();
+        self
+    }
+
+    /// 刷新票据，更新本客户端使用的登录信息
+    async fn refresh_login(&self, cookie_path: &Path) -> Result<()> {
+        Helper::echo("登录态已失效，正在刷新票据...", "yellow");
+        let cookie = refresh::refresh_file(&self.client, &self.refresh_url, cookie_path).await?;
+        let login_info = cookie.to_login_info()?;
+        Helper::echo(
+            &format!(
+                "票据已刷新，有效期至 {}",
+                chrono::DateTime::from_timestamp(login_info.expire_at, 0)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default()
+            ),
+            "green",
+        );
+        *self.login_info.lock().unwrap() = login_info;
+        Ok(())
+    }
+
     /// 构建x-oidb头
     fn build_x_oidb(&self, cmd: &str) -> Result<String> {
         let oidb_map: HashMap<&str, &str> = [
@@ -58,19 +130,36 @@ impl Api {
         Ok(serde_json::to_string(&obj)?)
     }
 
-    /// 发送API请求
+    /// 发送API请求，登录态失效且可以刷新时刷新票据后重试一次
     async fn request(&self, cmd: &str, data: Value) -> Result<ApiResponse> {
+        let response = self.send(cmd, &data).await?;
+        match &self.cookie_path {
+            Some(path) if AUTH_EXPIRED_RETCODES.contains(&response.retcode) => {
+                self.refresh_login(path)
+                    .await
+                    .with_context(|| format!("登录态已失效 (retcode={})，刷新票据失败", response.retcode))?;
+                self.send(cmd, &data).await
+            }
+            _ => Ok(response),
+        }
+    }
+
+    async fn send(&self, cmd: &str, data: &Value) -> Result<ApiResponse> {
         let x_oidb = self.build_x_oidb(cmd)?;
-        let api_url = format!("{}{}?g_tk={}", API_URL, cmd, self.login_info.g_tk);
+        let (g_tk, cookie) = {
+            let login_info = self.login_info.lock().unwrap();
+            (login_info.g_tk, login_info.cookie.clone())
+        };
+        let api_url = format!("{}{}?g_tk={}", self.api_url, cmd, g_tk);
 
         let response = self
             .client
             .post(&api_url)
             .header("host", "myqq.qq.com")
             .header("x-oidb", x_oidb)
-            .header("cookie", &self.login_info.cookie)
-            .header("User-Agent", "Mozilla/5.0 (iPhone; CPU iPhone OS 18_6_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 MicroMessenger/8.0.64(0x1800402b) NetType/WIFI Language/zh_CN")
-            .json(&data)
+            .header("cookie", cookie)
+            .header("User-Agent", USER_AGENT)
+            .json(data)
             .send()
             .await?;
 
@@ -78,8 +167,40 @@ impl Api {
         Ok(api_response)
     }
 
+    /// 用一次只取一个好友的请求检查登录态，不刷新票据
+    ///
+    /// 返回 `Ok(false)` 表示登录态已失效，其他错误的retcode和网络错误返回Err。
+    pub async fn check_login(&self) -> Result<bool> {
+        let response = self
+            .send("trpc.relation.friendlist/GetFriendList", &Self::friend_list_request(1)?)
+            .await?;
+        login_state(response.retcode)
+    }
+
     /// 获取好友列表
     pub async fn get_friend_list(&self, num: u32) -> Result<Value> {
+        let post = Self::friend_list_request(num)?;
+        let response = self
+            .request("trpc.relation.friendlist/GetFriendList", post)
+            .await?;
+
+        if response.retcode == 0 {
+            Ok(response.data.unwrap_or(json!({})))
+        } else {
+            Err(anyhow::anyhow!(
+                "获取好友列表失败: retcode={}",
+                response.retcode
+            ))
+        }
+    }
+
+    /// 好友列表请求体
+    fn friend_list_request(num: u32) -> Result<Value> {
+        let trans_param = Protobuf::serialize_with(
+            &json!({ "field_ids": FRIEND_FIELD_IDS }),
+            &FRIEND_TRANS_PARAM,
+        )?;
+
         let post = json!({
             "bytes_req_paging_cookie": "",
             "uint32_paging_get_num": num,
@@ -90,23 +211,11 @@ impl Api {
             "rpt_msg_req_param": [
                 {
                     "uint32_busi_id": 1,
-                    "bytes_trans_param": "CgtlZ6KcAavsA6zsAw=="
+                    "bytes_trans_param": general_purpose::STANDARD.encode(trans_param)
                 }
             ]
         });
-
-        let response = self
-            .request("trpc.relation.friendlist/GetFriendList", post)
-            .await?;
-
-        if response.retcode == 0 {
-            Ok(response.data.unwrap_or(json!({})))
-        } else {
-            Err(anyhow::anyhow!(
-                "获取好友列表失败: retcode={}",
-                response.retcode
-            ))
-        }
+        Ok(post)
     }
 
     /// 获取离线消息
@@ -172,19 +281,19 @@ impl Api {
 
     /// 保存UID映射
     pub fn save_uid(data: &Value) -> Result<HashMap<String, String>> {
-        let mut uids: HashMap<String, String> = if std::path::Path::new("uids.json").exists() {
-            let content = std::fs::read_to_string("uids.json")?;
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
-            HashMap::new()
-        };
+        Self::save_uid_to(Profile::current(), data)
+    }
+
+    /// 保存UID映射到指定配置的 uids.json
+    pub fn save_uid_to(profile: &Profile, data: &Value) -> Result<HashMap<String, String>> {
+        let mut uids = profile.load_uids();
 
         // 处理群消息列表
         if let Some(group_msg_list) = data.get("group_msg_list").and_then(|v| v.as_array()) {
             for group_msg in group_msg_list {
                 if let Some(msgs) = group_msg.get("msg").and_then(|v| v.as_array()) {
                     for msg in msgs {
-                        Self::extract_uid(&msg, &mut uids);
+                        Self::extract_uid(msg, &mut uids);
                     }
                 }
             }
@@ -195,15 +304,14 @@ impl Api {
             for c2c_msg in c2c_msg_list {
                 if let Some(msgs) = c2c_msg.get("msgs").and_then(|v| v.as_array()) {
                     for msg in msgs {
-                        Self::extract_uid(&msg, &mut uids);
+                        Self::extract_uid(msg, &mut uids);
                     }
                 }
             }
         }
 
         // 保存到文件
-        let json_str = serde_json::to_string_pretty(&uids)?;
-        std::fs::write("uids.json", json_str)?;
+        profile.save_uids(&uids)?;
 
         Ok(uids)
     }
@@ -242,3 +350,100 @@ impl Api {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_friend_trans_param() {
+        let bytes = Protobuf::serialize_with(
+            &json!({ "field_ids": FRIEND_FIELD_IDS }),
+            &FRIEND_TRANS_PARAM,
+        )
+        .unwrap();
+        assert_eq!(general_purpose::STANDARD.encode(bytes), "CgtlZ6KcAavsA6zsAw==");
+    }
+
+    #[test]
+    fn test_login_state() {
+        assert!(login_state(0).unwrap());
+        for code in AUTH_EXPIRED_RETCODES {
+            assert!(!login_state(code).unwrap());
+        }
+        assert!(login_state(-1).is_err());
+        assert!(login_state(1).is_err());
+    }
+
+    /// 启动本地的模拟服务：好友列表接口在票据为 `old` 时返回登录态失效，刷新接口按顺序返回 `refresh`
+    async fn mock_server(refresh: Vec<(u16, &'static str)>) -> (String, Arc<Mutex<Vec<String>>>) {
+        use axum::http::{HeaderMap, StatusCode};
+        use axum::routing::post;
+
+        let cookies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = cookies.clone();
+        let refresh = Arc::new(Mutex::new(refresh.into_iter()));
+        let app = axum::Router::new()
+            .route(
+                "/trpc.relation.friendlist/GetFriendList",
+                post(move |headers: HeaderMap| async move {
+                    let cookie = headers["cookie"].to_str().unwrap().to_string();
+                    let retcode = if cookie.contains("p_skey=old;") { -100 } else { 0 };
+                    recorded.lock().unwrap().push(cookie);
+                    axum::Json(json!({ "retcode": retcode, "data": { "friends": [] } }))
+                }),
+            )
+            .route(
+                "/refresh",
+                post(move || async move {
+                    let (status, body) = refresh.lock().unwrap().next().unwrap_or((404, ""));
+                    (StatusCode::from_u16(status).unwrap(), body)
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, cookies)
+    }
+
+    #[tokio::test]
+    async fn test_request_refresh() {
+        const COOKIE: &str = r#"{"result":0,"msg":"","req_id":"","account":"10001","nickname":"","avatar_url":"",
+            "tickets":[{"name":"pskey","domain":"myqq.qq.com","ticket":"old","expire_at":4102444800,"refresh_token":"rt"}]}"#;
+        const REFRESHED: &str = r#"{"result":0,"msg":"","req_id":"","account":"10001","nickname":"","avatar_url":"",
+            "tickets":[{"name":"pskey","domain":"myqq.qq.com","ticket":"new","expire_at":4102444800}]}"#;
+        let dir = std::env::temp_dir().join(format!("qqhistory-api-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cookie.json");
+        std::fs::write(&path, COOKIE).unwrap();
+        let cookie = crate::cookie::Cookie::load_from_file(&path).unwrap();
+        let (url, cookies) = mock_server(vec![(200, REFRESHED), (500, "")]).await;
+        let refresh_url = format!("{}refresh", url);
+
+        // 没有cookie.json时不刷新，直接返回失效的retcode
+        let api = Api::new(cookie.to_login_info().unwrap()).unwrap().with_urls(&url, &refresh_url);
+        let err = api.get_friend_list(1).await.unwrap_err();
+        assert!(err.to_string().contains("retcode=-100"), "{}", err);
+
+        // 刷新后用新票据重试，并写回cookie.json
+        let api = Api::new(cookie.to_login_info().unwrap())
+            .unwrap()
+            .with_refresh(path.clone())
+            .with_urls(&url, &refresh_url);
+        assert_eq!(api.get_friend_list(1).await.unwrap(), json!({ "friends": [] }));
+        assert!(cookies.lock().unwrap()[2].contains("p_skey=new;"));
+        assert_eq!(crate::cookie::Cookie::load_from_file(&path).unwrap().tickets[0].ticket, "new");
+        assert_eq!(cookies.lock().unwrap().len(), 3);
+
+        // 刷新失败时返回错误，不再重试
+        std::fs::write(&path, COOKIE).unwrap();
+        let api = Api::new(cookie.to_login_info().unwrap())
+            .unwrap()
+            .with_refresh(path.clone())
+            .with_urls(&url, &refresh_url);
+        let err = api.get_friend_list(1).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("HTTP 500"), "{:#}", err);
+        assert_eq!(cookies.lock().unwrap().len(), 4);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
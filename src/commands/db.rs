@@ -0,0 +1,108 @@
+use anyhow::{bail, Result};
+use clap::{Args, Subcommand};
+use std::fs;
+use std::path::PathBuf;
+use crate::database::DB_KEY_ENV;
+use crate::profile::Profile;
+
+/// 提供新数据库密钥的环境变量，用于 `db rekey`
+#[cfg(feature = "sqlcipher")]
+pub const DB_NEW_KEY_ENV: &str = "QQHISTORY_DB_NEW_KEY";
+
+/// 数据库加密子命令，需要启用 sqlcipher 特性
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// 用SQLCipher加密数据库，密钥读取环境变量 QQHISTORY_DB_KEY 或在终端输入
+    Encrypt(DbArgs),
+    /// 解密数据库
+    Decrypt(DbArgs),
+    /// 更换数据库密钥，新密钥读取环境变量 QQHISTORY_DB_NEW_KEY 或在终端输入
+    Rekey(DbArgs),
+}
+
+/// 要转换的数据库
+#[derive(Args, Debug)]
+pub struct DbArgs {
+    /// 数据库文件；只有文件名时为当前账号配置 db 目录下的会话
+    #[arg(required_unless_present = "all")]
+    db: Vec<String>,
+    /// 当前账号配置 db 目录下的所有数据库
+    #[arg(long, conflicts_with = "db")]
+    all: bool,
+}
+
+impl DbArgs {
+    fn paths(&self) -> Result<Vec<PathBuf>> {
+        let profile = Profile::current();
+        let paths: Vec<PathBuf> = if self.all {
+            let mut paths: Vec<PathBuf> = fs::read_dir(profile.db_dir())?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "db"))
+                .collect();
+            paths.sort();
+            paths
+        } else {
+            self.db.iter().map(|db| profile.resolve_db(db)).collect()
+        };
+        if let Some(missing) = paths.iter().find(|p| !p.exists()) {
+            bail!("数据库文件不存在: {}", missing.display());
+        }
+        Ok(paths)
+    }
+}
+
+/// 执行db子命令
+#[cfg(not(feature = "sqlcipher"))]
+pub fn run(command: DbCommand) -> Result<()> {
+    let (DbCommand::Encrypt(args) | DbCommand::Decrypt(args) | DbCommand::Rekey(args)) = command;
+    args.paths()?;
+    bail!(
+        "未启用数据库加密，请使用 `cargo build --release --features sqlcipher` 编译；密钥通过 {} 或终端输入",
+        DB_KEY_ENV
+    )
+}
+
+/// 执行db子命令
+#[cfg(feature = "sqlcipher")]
+pub fn run(command: DbCommand) -> Result<()> {
+    use crate::database;
+    use crate::helper::Helper;
+    use crate::login::crypto::secret_from_env_or_prompt;
+
+    let (args, src_key, dst_key) = match command {
+        DbCommand::Encrypt(args) => {
+            let key = secret_from_env_or_prompt(DB_KEY_ENV, "数据库密钥", true)?;
+            (args, None, Some(key))
+        }
+        DbCommand::Decrypt(args) => {
+            let key = secret_from_env_or_prompt(DB_KEY_ENV, "数据库密钥", false)?;
+            (args, Some(key), None)
+        }
+        DbCommand::Rekey(args) => {
+            let old = secret_from_env_or_prompt(DB_KEY_ENV, "当前数据库密钥", false)?;
+            let new = secret_from_env_or_prompt(DB_NEW_KEY_ENV, "新的数据库密钥", true)?;
+            (args, Some(old), Some(new))
+        }
+    };
+
+    let mut failed = 0;
+    for path in args.paths()? {
+        // 加密只处理明文数据库，解密和换密钥只处理已加密的
+        if database::is_encrypted(&path)? != src_key.is_some() {
+            let state = if src_key.is_some() { "未加密" } else { "已加密" };
+            Helper::echo(&format!("{} {}，跳过", path.display(), state), "yellow");
+            continue;
+        }
+        match database::convert(&path, src_key.as_deref(), dst_key.as_deref()) {
+            Ok(()) => Helper::echo(&format!("已完成: {}", path.display()), "green"),
+            Err(e) => {
+                Helper::echo(&format!("{} 失败: {:#}", path.display(), e), "red");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} 个数据库转换失败", failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::cookie::Cookie;
+use crate::database::Database;
+use crate::export::html::HtmlExporter;
+use crate::export::onebot::OneBotExporter;
+use crate::export::table::TableExporter;
+use crate::export::telegram::{TelegramChat, TelegramExporter};
+use crate::export::text::{TextExporter, TextFormat, TextOptions};
+use crate::export::{ExportTimezone, SenderNames};
+use crate::helper::Helper;
+use crate::onebot::MessageFormat;
+use crate::profile::Profile;
+
+/// 导出聊天记录
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    /// 导出为可离线浏览的分页HTML
+    Html(HtmlArgs),
+    /// 导出为纯文本
+    Txt(TextArgs),
+    /// 导出为Markdown
+    Md(TextArgs),
+    /// 导出为JSON Lines，每行一条消息
+    Jsonl(JsonlArgs),
+    /// 导出为CSV，消息和元素分别写入两个文件
+    Csv(CsvArgs),
+    /// 导出为Telegram Desktop的 result.json 格式
+    Telegram(TelegramArgs),
+    /// 导出为OneBot消息段或CQ码，每行一条消息
+    Onebot(OneBotArgs),
+}
+
+/// 导出来源
+#[derive(Args, Debug)]
+pub struct SourceArgs {
+    /// 数据库文件，如 db/<uid>.db；只有文件名时为当前账号配置 db 目录下的会话
+    #[arg(long)]
+    db: String,
+    /// 发送者名称，格式为 UIN=名称，可重复
+    #[arg(long = "name", value_name = "UIN=NAME")]
+    names: Vec<String>,
+    /// 自己的QQ号，默认从未加密的 cookie.json 读取
+    #[arg(long)]
+    self_uin: Option<i64>,
+}
+
+impl SourceArgs {
+    pub(crate) fn open(&self) -> Result<(Database, SenderNames)> {
+        let profile = Profile::current();
+        let path = profile.resolve_db(&self.db);
+        if !path.exists() {
+            anyhow::bail!("数据库文件不存在: {}", path.display());
+        }
+        let db = Database::new(&path.to_string_lossy())?;
+
+        // 未加密的 cookie.json 中有自己的QQ号和昵称
+        let cookie = Cookie::load_unencrypted(profile.cookie_path());
+        let self_uin = self
+            .self_uin
+            .or_else(|| cookie.as_ref().and_then(|c| c.account.parse().ok()));
+        let self_name = cookie
+            .filter(|c| Some(c.account.as_str()) == self_uin.map(|u| u.to_string()).as_deref())
+            .map(|c| c.nickname);
+        // 配置文件中的名称在前，--name 可以覆盖
+        let mut overrides = profile.load_config()?.name_overrides();
+        overrides.extend(self.names.iter().cloned());
+        let names = SenderNames::new(self_uin, self_name, &overrides)?;
+        Ok((db, names))
+    }
+
+    /// 数据库所在目录下的 media，导入的本地媒体保存在其中
+    fn media_root(&self) -> PathBuf {
+        let path = Profile::current().resolve_db(&self.db);
+        path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .join("media")
+    }
+
+    /// 数据库文件名（不含扩展名）
+    fn stem(&self) -> String {
+        Path::new(&self.db)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("export")
+            .to_string()
+    }
+}
+
+/// HTML导出参数
+#[derive(Args, Debug)]
+pub struct HtmlArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    /// 输出目录，默认为账号配置下的 export/<数据库文件名>
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// 页面标题，默认为数据库文件名
+    #[arg(long)]
+    title: Option<String>,
+    /// 每页消息数
+    #[arg(long, default_value_t = 500)]
+    page_size: usize,
+    /// 不下载图片和视频封面
+    #[arg(long)]
+    no_media: bool,
+    /// 时区：local、utc 或 +08:00 形式的偏移
+    #[arg(long, default_value = "local")]
+    timezone: ExportTimezone,
+}
+
+/// 时间范围
+#[derive(Args, Debug)]
+pub struct RangeArgs {
+    /// 起始时间，YYYY-MM-DD 或 YYYY-MM-DD HH:MM:SS
+    #[arg(long)]
+    start: Option<String>,
+    /// 结束时间（含），只有日期时包含当天全部消息
+    #[arg(long)]
+    end: Option<String>,
+    /// 时区：local、utc 或 +08:00 形式的偏移
+    #[arg(long, default_value = "local")]
+    pub(crate) timezone: ExportTimezone,
+}
+
+impl RangeArgs {
+    /// 起止时间的Unix时间戳
+    pub(crate) fn bounds(&self) -> Result<(Option<i64>, Option<i64>)> {
+        let start = self.start.as_deref().map(|s| self.timezone.parse_time(s, false)).transpose()?;
+        let end = self.end.as_deref().map(|s| self.timezone.parse_time(s, true)).transpose()?;
+        Ok((start, end))
+    }
+}
+
+/// 文本导出参数
+#[derive(Args, Debug)]
+pub struct TextArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 输出文件，默认输出到标准输出
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// 在日期变化处插入分隔行
+    #[arg(long)]
+    day_separator: bool,
+}
+
+impl TextArgs {
+    fn export(self, format: TextFormat) -> Result<()> {
+        let (db, names) = self.source.open()?;
+        let (start, end) = self.range.bounds()?;
+        let options = TextOptions {
+            start,
+            end,
+            timezone: self.range.timezone,
+            day_separator: self.day_separator,
+        };
+        let exporter = TextExporter::new(&db, &names, format, options);
+        write_output(self.out.as_deref(), |out| exporter.export(out))
+    }
+}
+
+/// JSON Lines导出参数
+#[derive(Args, Debug)]
+pub struct JsonlArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 输出文件，默认输出到标准输出
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+/// CSV导出参数
+#[derive(Args, Debug)]
+pub struct CsvArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 输出目录，写入 messages.csv 和 elements.csv，默认为账号配置下的 export/<数据库文件名>
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+/// Telegram导出参数
+#[derive(Args, Debug)]
+pub struct TelegramArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 输出目录，默认为账号配置下的 export/<数据库文件名>
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// 会话名称，默认为对方的名称或群号
+    #[arg(long)]
+    chat_name: Option<String>,
+    /// 不下载图片和视频
+    #[arg(long)]
+    no_media: bool,
+}
+
+/// OneBot导出参数
+#[derive(Args, Debug)]
+pub struct OneBotArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 消息格式：v11、v12 或 cq
+    #[arg(long, default_value = "v11")]
+    format: MessageFormat,
+    /// 输出文件，默认输出到标准输出
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+/// 写入文件或标准输出，`export` 返回导出的条数
+fn write_output<F>(path: Option<&Path>, export: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<usize>,
+{
+    match path {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path).with_context(|| format!("无法创建 {}", path.display()))?);
+            let count = export(&mut out)?;
+            out.flush()?;
+            Helper::echo(&format!("导出完成，共 {} 条消息，保存到 {}", count, path.display()), "green");
+        }
+        None => {
+            let mut out = BufWriter::new(io::stdout().lock());
+            export(&mut out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// 执行export子命令
+pub async fn run(command: ExportCommand) -> Result<()> {
+    match command {
+        ExportCommand::Html(args) => {
+            let (db, names) = args.source.open()?;
+            let out = args
+                .out
+                .unwrap_or_else(|| Profile::current().export_dir().join(args.source.stem()));
+            let title = args.title.unwrap_or_else(|| args.source.stem());
+
+            Helper::echo(&format!("正在导出到 {}", out.display()), "cyan");
+            let exporter = HtmlExporter::new(&db, out.clone(), title, args.page_size, &names, !args.no_media)?
+                .with_local_media(args.source.media_root())
+                .with_timezone(args.timezone);
+            let pages = exporter.export().await.context("导出HTML失败")?;
+            Helper::echo(
+                &format!("导出完成，共 {} 页，打开 {} 查看", pages, out.join("index.html").display()),
+                "green",
+            );
+        }
+        ExportCommand::Txt(args) => args.export(TextFormat::Txt)?,
+        ExportCommand::Md(args) => args.export(TextFormat::Markdown)?,
+        ExportCommand::Jsonl(args) => {
+            let (db, names) = args.source.open()?;
+            let (start, end) = args.range.bounds()?;
+            let exporter = TableExporter::new(&db, &names, args.range.timezone, start, end);
+            write_output(args.out.as_deref(), |out| exporter.export_jsonl(out))?;
+        }
+        ExportCommand::Csv(args) => {
+            let (db, names) = args.source.open()?;
+            let (start, end) = args.range.bounds()?;
+            let out = args
+                .out
+                .unwrap_or_else(|| Profile::current().export_dir().join(args.source.stem()));
+            fs::create_dir_all(&out)?;
+
+            let exporter = TableExporter::new(&db, &names, args.range.timezone, start, end);
+            let count = exporter.export_csv(
+                BufWriter::new(File::create(out.join("messages.csv"))?),
+                BufWriter::new(File::create(out.join("elements.csv"))?),
+            )?;
+            Helper::echo(&format!("导出完成，共 {} 条消息，保存到 {}", count, out.display()), "green");
+        }
+        ExportCommand::Telegram(args) => {
+            let (db, names) = args.source.open()?;
+            let out = args
+                .out
+                .unwrap_or_else(|| Profile::current().export_dir().join(args.source.stem()));
+
+            // 群的数据库文件名为群号；私聊的对方取第一条消息中不是自己的一方
+            let stem = args.source.stem();
+            let group_code = Some(&stem)
+                .filter(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|s| s.parse::<i64>().ok());
+            let chat_id = match group_code {
+                Some(code) => code,
+                None => match db.get_messages_after(None, 1)?.first() {
+                    Some(stored) if names.is_self(&stored.message) => stored.message.routing_head.to_uin,
+                    Some(stored) => stored.message.routing_head.from_uin,
+                    None => 0,
+                },
+            };
+            let chat = TelegramChat {
+                name: args.chat_name.unwrap_or_else(|| names.name(chat_id)),
+                id: chat_id,
+                is_group: group_code.is_some(),
+            };
+
+            let exporter = TelegramExporter::new(
+                &db,
+                &names,
+                out.clone(),
+                chat,
+                args.range.timezone,
+                args.range.bounds()?,
+                !args.no_media,
+            )?
+            .with_local_media(args.source.media_root());
+            let count = exporter.export().await?;
+            Helper::echo(&format!("导出完成，共 {} 条消息，保存到 {}", count, out.join("result.json").display()), "green");
+        }
+        ExportCommand::Onebot(args) => {
+            let (db, names) = args.source.open()?;
+            let (start, end) = args.range.bounds()?;
+            let exporter = OneBotExporter::new(&db, &names, args.format, start, end);
+            write_output(args.out.as_deref(), |out| exporter.export(out))?;
+        }
+    }
+    Ok(())
+}
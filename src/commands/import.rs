@@ -0,0 +1,188 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use std::path::{Path, PathBuf};
+use crate::cookie::Cookie;
+use crate::database::Database;
+use crate::export::ExportTimezone;
+use crate::helper::Helper;
+use crate::import::ntqq::{NtChat, NtqqDatabase};
+use crate::import::{onebot, qq_pc, Importer};
+use crate::onebot::MessageFormat;
+use crate::profile::Profile;
+
+/// 导入其他来源的聊天记录
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    /// 导入QQ PC版“导出消息记录”生成的 .txt/.mht 文件
+    Pc(PcArgs),
+    /// 导入解密后的NTQQ本地数据库 nt_msg.db
+    Ntqq(NtqqArgs),
+    /// 导入OneBot消息的JSON Lines文件（消息段数组或CQ码）
+    Onebot(OneBotArgs),
+}
+
+/// QQ PC导出文件的导入参数
+#[derive(Args, Debug)]
+pub struct PcArgs {
+    /// .txt 或 .mht 文件，可指定多个
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// 目标数据库文件，如 db/<uid>.db；只有文件名时为当前账号配置 db 目录下的会话
+    #[arg(long)]
+    db: String,
+    /// 会话对方的QQ号
+    #[arg(long)]
+    peer_uin: i64,
+    /// 自己的QQ号，默认从未加密的 cookie.json 读取
+    #[arg(long)]
+    self_uin: Option<i64>,
+    /// 自己在导出文件中显示的名称，可重复，默认为未加密的 cookie.json 中的昵称
+    #[arg(long = "self-name")]
+    self_names: Vec<String>,
+    /// 其他发送者的名称，格式为 UIN=名称，可重复
+    #[arg(long = "name", value_name = "UIN=NAME")]
+    names: Vec<String>,
+    /// 导出文件中时间所在的时区：local、utc 或 +08:00 形式的偏移
+    #[arg(long, default_value = "local")]
+    timezone: ExportTimezone,
+}
+
+/// NTQQ数据库的导入参数
+#[derive(Args, Debug)]
+pub struct NtqqArgs {
+    /// 解密后的 nt_msg.db
+    file: PathBuf,
+    /// 目标数据库文件，如 db/<uid>.db；只有文件名时为当前账号配置 db 目录下的会话
+    #[arg(long)]
+    db: String,
+    /// 导入与该QQ号的私聊消息
+    #[arg(long, required_unless_present = "group", conflicts_with = "group")]
+    peer_uin: Option<i64>,
+    /// 导入该群的消息
+    #[arg(long)]
+    group: Option<i64>,
+    /// 自己的QQ号，默认从未加密的 cookie.json 读取
+    #[arg(long)]
+    self_uin: Option<i64>,
+}
+
+/// OneBot消息的导入参数
+#[derive(Args, Debug)]
+pub struct OneBotArgs {
+    /// 每行一条消息的JSON Lines文件
+    file: PathBuf,
+    /// 目标数据库文件，如 db/<uid>.db；只有文件名时为当前账号配置 db 目录下的会话
+    #[arg(long)]
+    db: String,
+    /// 会话对方的QQ号或群号
+    #[arg(long)]
+    peer_uin: i64,
+    /// 自己的QQ号，默认从未加密的 cookie.json 读取
+    #[arg(long)]
+    self_uin: Option<i64>,
+    /// 消息格式：v11、v12 或 cq，默认逐行判断
+    #[arg(long)]
+    format: Option<MessageFormat>,
+}
+
+/// 自己的QQ号，未指定时从未加密的 cookie.json 读取
+fn resolve_self_uin(self_uin: Option<i64>) -> Result<i64> {
+    match self_uin {
+        Some(uin) => Ok(uin),
+        None => Cookie::load_unencrypted(Profile::current().cookie_path())
+            .and_then(|c| c.account.parse().ok())
+            .context("无法确定自己的QQ号（cookie.json 不存在或已加密），请使用 --self-uin 指定"),
+    }
+}
+
+/// 打开目标数据库，必要时创建所在目录
+fn open_target(db: &Path) -> Result<Database> {
+    if let Some(parent) = db.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    Database::new(&db.to_string_lossy())
+}
+
+/// 执行import子命令
+pub fn run(command: ImportCommand) -> Result<()> {
+    match command {
+        ImportCommand::Pc(args) => {
+            let self_uin = resolve_self_uin(args.self_uin)?;
+            let mut self_names = args.self_names.clone();
+            if self_names.is_empty() {
+                let cookie = Cookie::load_unencrypted(Profile::current().cookie_path());
+                if let Some(cookie) = cookie.filter(|c| c.account == self_uin.to_string()) {
+                    self_names.push(cookie.nickname);
+                }
+            }
+            if self_names.is_empty() {
+                bail!("无法确定自己在导出文件中的名称，请使用 --self-name 指定");
+            }
+
+            // MHT内嵌图片保存到数据库所在目录下的 media/<数据库文件名>，与 serve 的媒体缓存一致
+            let db_path = Profile::current().resolve_db(&args.db);
+            let conversation = db_path.file_stem().and_then(|s| s.to_str()).unwrap_or("import").to_string();
+            let media_root = db_path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .join("media");
+            let db = open_target(&db_path)?;
+            let importer = Importer::new(&db, self_uin, args.peer_uin, &self_names, &args.names)?;
+
+            for file in &args.files {
+                Helper::echo(&format!("正在解析 {}", file.display()), "cyan");
+                let export = qq_pc::parse_file(file, args.timezone, &media_root, &conversation)?;
+                if let Some(peer) = &export.peer_name {
+                    Helper::echo(&format!("消息对象: {}", peer), "cyan");
+                }
+
+                let stats = importer.import(&export.messages)?;
+                Helper::echo(
+                    &format!(
+                        "{}: 解析 {} 条，{}，提取图片 {} 张",
+                        file.display(),
+                        export.messages.len(),
+                        stats,
+                        export.images
+                    ),
+                    "green",
+                );
+            }
+        }
+        ImportCommand::Ntqq(args) => {
+            let self_uin = resolve_self_uin(args.self_uin)?;
+            let (chat, peer_uin) = match (args.peer_uin, args.group) {
+                (Some(uin), _) => (NtChat::C2c(uin), uin),
+                (None, Some(group)) => (NtChat::Group(group), group),
+                (None, None) => bail!("请指定 --peer-uin 或 --group"),
+            };
+
+            let source = NtqqDatabase::open(&args.file)?;
+            let messages = source.read_messages(chat, self_uin)?;
+            Helper::echo(&format!("从 {} 读取到 {} 条消息", args.file.display(), messages.len()), "cyan");
+
+            let db = open_target(&Profile::current().resolve_db(&args.db))?;
+            let importer = Importer::new(&db, self_uin, peer_uin, &[], &[])?;
+            let total = messages.len();
+            let stats = importer.import_messages(messages)?;
+            Helper::echo(
+                &format!("读取 {} 条，{}", total, stats),
+                "green",
+            );
+        }
+        ImportCommand::Onebot(args) => {
+            let self_uin = resolve_self_uin(args.self_uin)?;
+            let messages = onebot::parse_file(&args.file, args.format)?;
+
+            let db = open_target(&Profile::current().resolve_db(&args.db))?;
+            let importer = Importer::new(&db, self_uin, args.peer_uin, &[], &[])?;
+            let stats = importer.import(&messages)?;
+            Helper::echo(
+                &format!("读取 {} 条，{}", messages.len(), stats),
+                "green",
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use crate::api::Api;
+use crate::cookie::Cookie;
+use crate::helper::Helper;
+use crate::login;
+use crate::login::capture::{self, CaptureFormat};
+use crate::login::crypto::{Envelope, KeyMethod, PASSPHRASE_ENV};
+use crate::profile::Profile;
+use crate::stats::{format_duration, print_table};
+
+/// 登录信息子命令
+#[derive(Subcommand, Debug)]
+pub enum LoginCommand {
+    /// 从抓包文件中导入 exchange 接口的响应，写入当前账号配置的 cookie.json
+    Import(ImportArgs),
+    /// 用 refresh_token 刷新当前账号配置的票据
+    Refresh,
+    /// 加密当前账号配置的 cookie.json
+    Encrypt(EncryptArgs),
+    /// 查看票据有效期并检查登录态，已失效时以非零状态退出
+    Status(StatusArgs),
+}
+
+/// 导入参数
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// HAR文件、mitmproxy流量文件或原始HTTP响应，- 表示标准输入
+    file: PathBuf,
+    /// 文件格式：har、mitmproxy、http，默认根据内容判断
+    #[arg(long)]
+    format: Option<CaptureFormat>,
+    /// 覆盖其他账号的 cookie.json，或导入已过期的票据
+    #[arg(long)]
+    force: bool,
+}
+
+/// 加密参数
+#[derive(Args, Debug)]
+pub struct EncryptArgs {
+    /// 加密方式：keyring 使用系统密钥环（不可用或重启后会清空时保存为密钥文件），passphrase 使用口令
+    #[arg(long, default_value = "keyring")]
+    method: KeyMethod,
+}
+
+/// 登录状态参数
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// 只检查本地票据的有效期，不请求接口
+    #[arg(long)]
+    offline: bool,
+}
+
+/// 执行login子命令
+pub async fn run(command: LoginCommand) -> Result<()> {
+    match command {
+        LoginCommand::Import(args) => import(args),
+        LoginCommand::Refresh => refresh().await,
+        LoginCommand::Encrypt(args) => encrypt(args),
+        LoginCommand::Status(args) => status(args).await,
+    }
+}
+
+fn import(args: ImportArgs) -> Result<()> {
+    let data = if args.file.as_os_str() == "-" {
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else {
+        fs::read(&args.file).with_context(|| format!("无法读取 {}", args.file.display()))?
+    };
+    let format = args.format.unwrap_or_else(|| CaptureFormat::detect(&data));
+    let cookie = capture::find_exchange(&data, format)?;
+    let info = cookie.to_login_info()?;
+
+    if cookie.is_expired() && !args.force {
+        bail!("票据已过期，请重新抓包；使用 --force 仍然导入");
+    }
+
+    let path = Profile::current().cookie_path();
+    if path.exists() && !args.force {
+        let old = Cookie::load_from_file(&path)?;
+        if old.account != cookie.account {
+            bail!(
+                "{} 中已有账号 {}，使用 --force 覆盖，或用 --profile 导入到其他配置",
+                path.display(),
+                old.account
+            );
+        }
+    }
+    cookie.save_to_file(&path)?;
+
+    Helper::echo(
+        &format!("已导入 {}({})，有效期至 {}: {}", info.nickname, info.uin, format_expire(info.expire_at), path.display()),
+        "green",
+    );
+    Ok(())
+}
+
+async fn refresh() -> Result<()> {
+    let path = Profile::current().cookie_path();
+    let cookie = login::refresh::refresh_file(&reqwest::Client::new(), &login::refresh::refresh_url(), &path).await?;
+    let info = cookie.to_login_info()?;
+    Helper::echo(
+        &format!("已刷新 {}({})，有效期至 {}", info.nickname, info.uin, format_expire(info.expire_at)),
+        "green",
+    );
+    Ok(())
+}
+
+fn encrypt(args: EncryptArgs) -> Result<()> {
+    let path = Profile::current().cookie_path();
+    if let Some(envelope) = Envelope::read(&path) {
+        bail!("{} 已经使用{}加密", path.display(), envelope.describe());
+    }
+    let cookie = Cookie::load_from_file(&path)?;
+    cookie.save_encrypted(&path, args.method)?;
+    let envelope = Envelope::read(&path).context("加密后无法读取")?;
+    Helper::echo(&format!("已使用{}加密: {}", envelope.describe(), path.display()), "green");
+    if args.method == KeyMethod::Passphrase {
+        Helper::echo(&format!("之后每次运行需要输入口令，或设置环境变量 {}", PASSPHRASE_ENV), "cyan");
+    }
+    Ok(())
+}
+
+async fn status(args: StatusArgs) -> Result<()> {
+    let path = Profile::current().cookie_path();
+    if !path.exists() {
+        bail!("未找到登录信息: {}", path.display());
+    }
+    let cookie = Cookie::load_from_file(&path)?;
+    Helper::echo(&format!("账号: {}({})  {}", cookie.nickname, cookie.account, path.display()), "cyan");
+
+    let now = chrono::Utc::now().timestamp();
+    let rows = cookie
+        .tickets
+        .iter()
+        .map(|ticket| {
+            let remaining = match ticket.expire_at {
+                None => "未知".to_string(),
+                Some(t) if t > now => format!("剩余{}", format_duration(t - now)),
+                Some(t) => format!("已过期{}", format_duration(now - t)),
+            };
+            vec![
+                ticket.name.clone(),
+                ticket.domain.clone().unwrap_or_default(),
+                ticket.expire_at.map(format_expire).unwrap_or_default(),
+                remaining,
+                Helper::gtk(&ticket.ticket).to_string(),
+                if ticket.refresh_token.is_some() { "有" } else { "" }.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["名称", "域", "过期时间", "有效期", "g_tk", "refresh_token"], rows);
+
+    if cookie.is_expired() {
+        bail!("myqq.qq.com 票据已过期或不存在{}", refresh_hint(&cookie));
+    }
+    if args.offline {
+        return Ok(());
+    }
+
+    let api = Api::new(cookie.to_login_info()?)?;
+    if !api.check_login().await? {
+        bail!("登录态已失效{}", refresh_hint(&cookie));
+    }
+    Helper::echo("登录态有效", "green");
+    Ok(())
+}
+
+fn refresh_hint(cookie: &Cookie) -> &'static str {
+    if cookie.refresh_ticket().is_some() {
+        "，可以运行 `qqhistory login refresh`"
+    } else {
+        "，请重新登录"
+    }
+}
+
+fn format_expire(expire_at: i64) -> String {
+    chrono::DateTime::from_timestamp(expire_at, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,9 @@
+pub mod db;
+pub mod export;
+pub mod import;
+pub mod login;
+pub mod pb;
+pub mod profiles;
+pub mod serve;
+pub mod stats;
+pub mod watch;
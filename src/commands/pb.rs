@@ -0,0 +1,338 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use clap::{Args, Subcommand, ValueEnum};
+use colored::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use crate::database::Database;
+use crate::profile::Profile;
+use crate::protobuf::Protobuf;
+use crate::protobuf::inspect::{Difference, Interpretation, Node};
+use crate::protobuf::schema::{Field, FieldKind, Label, Schema};
+
+/// Protobuf调试工具
+#[derive(Subcommand, Debug)]
+pub enum PbCommand {
+    /// 解码并打印带偏移和所有可能解释的字段树
+    Decode {
+        #[command(flatten)]
+        input: PbInput,
+        /// 以JSON输出
+        #[arg(long)]
+        json: bool,
+        /// 按指定的字段类型解码并输出JSON，格式为 `路径=类型`，可重复，如 `1=sint64`、`3=group`、`3.1=string`、`4=uint32[]`；
+        /// 类型为proto标量类型名、`message` 或 `group`，`[]` 后缀表示重复字段（数值类型兼容packed编码），未指定的字段按启发式解码
+        #[arg(long = "type", value_name = "PATH=TYPE")]
+        types: Vec<String>,
+    },
+    /// 比较两个负载的字段差异
+    Diff {
+        /// 第一个负载（base64/hex，@文件 或 - 读取标准输入）
+        a: String,
+        /// 第二个负载
+        b: String,
+        /// 输入格式
+        #[arg(long, value_enum, default_value = "auto")]
+        format: InputFormat,
+    },
+}
+
+/// 负载来源
+#[derive(Args, Debug)]
+pub struct PbInput {
+    /// 负载（base64/hex，@文件 或 - 读取标准输入）
+    #[arg(required_unless_present = "id")]
+    input: Option<String>,
+    /// 输入格式
+    #[arg(long, value_enum, default_value = "auto")]
+    format: InputFormat,
+    /// 从数据库读取的消息id
+    #[arg(long, requires_all = ["db", "path"], conflicts_with = "input")]
+    id: Option<i64>,
+    /// 消息JSON中负载所在的路径；富媒体元素的原始protobuf为 body.0.raw、body.0.content.1.raw 等
+    #[arg(long)]
+    path: Option<String>,
+    /// 数据库文件
+    #[arg(long)]
+    db: Option<String>,
+}
+
+/// 输入格式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum InputFormat {
+    /// 带 `0x` 前缀或以空格、冒号分隔的按hex，否则优先base64；文件输入都不是时按原始字节处理
+    Auto,
+    Base64,
+    Hex,
+    Raw,
+}
+
+/// 执行pb子命令
+pub fn run(command: PbCommand) -> Result<()> {
+    match command {
+        PbCommand::Decode { input, json, types } => {
+            let data = load_input(&input)?;
+            if !types.is_empty() {
+                let value = Protobuf::deserialize_with(&data, build_schema(&types)?)?;
+                println!("{}", serde_json::to_string_pretty(&value)?);
+                return Ok(());
+            }
+            let nodes = Protobuf::inspect(&data)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&nodes)?);
+            } else {
+                println!("{} 字节", data.len());
+                print_tree(&nodes, 0);
+            }
+        }
+        PbCommand::Diff { a, b, format } => {
+            let a = Protobuf::inspect(&read_spec(&a, format)?)?;
+            let b = Protobuf::inspect(&read_spec(&b, format)?)?;
+            let diff = Protobuf::diff(&a, &b);
+            if diff.is_empty() {
+                println!("没有差异");
+            }
+            for d in diff {
+                match d {
+                    Difference::Removed(path, v) => println!("{}", format!("- {}: {}", path, v).red()),
+                    Difference::Added(path, v) => println!("{}", format!("+ {}: {}", path, v).green()),
+                    Difference::Changed(path, old, new) => {
+                        println!("{}", format!("~ {}: {} -> {}", path, old, new).yellow())
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 读取负载
+fn load_input(input: &PbInput) -> Result<Vec<u8>> {
+    if let Some(id) = input.id {
+        let db_file = input.db.as_deref().context("缺少 --db")?;
+        let path = input.path.as_deref().context("缺少 --path")?;
+        let db_file = Profile::current().resolve_db(db_file);
+        if !db_file.exists() {
+            return Err(anyhow!("数据库不存在: {}", db_file.display()));
+        }
+
+        let db = Database::new(&db_file.to_string_lossy())?;
+        // 富媒体的原始protobuf单独保存，不在消息JSON中
+        let raw = db.get_raw(id)?.with_context(|| format!("消息不存在: {}", id))?;
+        if let Some(text) = raw.get(path.trim_matches('.')) {
+            return decode_text(text, input.format);
+        }
+        let message = db.get_message(id)?.with_context(|| format!("消息不存在: {}", id))?;
+        let value = serde_json::to_value(&message)?;
+        let text = json_path(&value, path)?
+            .as_str()
+            .with_context(|| format!("{} 不是字符串", path))?;
+        return decode_text(text, input.format);
+    }
+
+    let spec = input.input.as_deref().context("缺少输入")?;
+    read_spec(spec, input.format)
+}
+
+/// 按 `.` 分隔的路径取值，数组使用数字下标
+fn json_path<'a>(value: &'a Value, path: &str) -> Result<&'a Value> {
+    path.split('.')
+        .filter(|p| !p.is_empty())
+        .try_fold(value, |v, key| match v {
+            Value::Array(arr) => key.parse::<usize>().ok().and_then(|i| arr.get(i)),
+            _ => v.get(key),
+        }
+        .with_context(|| format!("路径不存在: {}", path)))
+}
+
+/// 读取 base64/hex 字面量、`@文件` 或 `-`（标准输入）
+fn read_spec(spec: &str, format: InputFormat) -> Result<Vec<u8>> {
+    let bytes = if spec == "-" {
+        let mut buf = Vec::new();
+        io::stdin().read_to_end(&mut buf)?;
+        buf
+    } else if let Some(path) = spec.strip_prefix('@') {
+        fs::read(path).with_context(|| format!("无法读取文件: {}", path))?
+    } else {
+        return decode_text(spec, format);
+    };
+
+    match format {
+        InputFormat::Raw => Ok(bytes),
+        InputFormat::Auto => Ok(std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|text| decode_text(text, format).ok())
+            .unwrap_or(bytes)),
+        _ => decode_text(std::str::from_utf8(&bytes).context("输入不是文本")?, format),
+    }
+}
+
+/// 解码文本形式的负载
+fn decode_text(text: &str, format: InputFormat) -> Result<Vec<u8>> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    match format {
+        InputFormat::Hex => decode_hex(text),
+        InputFormat::Base64 => decode_base64(&compact),
+        InputFormat::Raw => Ok(compact.into_bytes()),
+        // 只由hex字符组成的内容也可能是base64（如 CAFEBABE），没有明确标记时按base64
+        InputFormat::Auto if looks_like_hex(text) => decode_hex(text),
+        InputFormat::Auto => decode_base64(&compact).or_else(|_| decode_hex(text)),
+    }
+}
+
+/// 带 `0x` 前缀，或以空白、冒号分隔的hex字节
+fn looks_like_hex(text: &str) -> bool {
+    let text = text.trim();
+    if text.starts_with("0x") {
+        return true;
+    }
+    let is_separator = |c: char| c.is_whitespace() || c == ':';
+    text.contains(is_separator) && text.chars().all(|c| c.is_ascii_hexdigit() || is_separator(c))
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+    let text = text.strip_prefix("0x").unwrap_or(&text);
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return Err(anyhow!("无效的hex"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| anyhow!("无效的hex")))
+        .collect()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(text)
+        .or_else(|_| general_purpose::URL_SAFE.decode(text))
+        .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(text))
+        .map_err(|_| anyhow!("无效的base64"))
+}
+
+/// 命令行指定的字段，路径中间的字段默认为嵌套消息
+#[derive(Default)]
+struct FieldSpec {
+    kind: Option<String>,
+    repeated: bool,
+    children: BTreeMap<u32, FieldSpec>,
+}
+
+/// 由 `--type` 生成临时schema，只在本次命令中使用，因此直接泄漏为 `'static`
+fn build_schema(types: &[String]) -> Result<&'static Schema> {
+    let mut root: BTreeMap<u32, FieldSpec> = BTreeMap::new();
+    for item in types {
+        let (path, kind) = item
+            .split_once('=')
+            .with_context(|| format!("字段类型格式应为 路径=类型: {}", item))?;
+        let mut fields = &mut root;
+        let mut numbers = path.split('.').peekable();
+        while let Some(number) = numbers.next() {
+            let number: u32 = number.trim().parse().with_context(|| format!("无效的字段号: {}", path))?;
+            let spec = fields.entry(number).or_default();
+            if numbers.peek().is_none() {
+                let kind = kind.trim();
+                spec.repeated = kind.ends_with("[]");
+                spec.kind = Some(kind.trim_end_matches("[]").to_string());
+            }
+            fields = &mut spec.children;
+        }
+    }
+    to_schema(&root)
+}
+
+fn to_schema(specs: &BTreeMap<u32, FieldSpec>) -> Result<&'static Schema> {
+    let mut fields = Vec::new();
+    for (number, spec) in specs {
+        let kind = match spec.kind.as_deref().unwrap_or("message") {
+            "message" => FieldKind::Message(to_schema(&spec.children)?),
+            "group" => FieldKind::Group(to_schema(&spec.children)?),
+            name => {
+                let kind = FieldKind::from_type_name(name).with_context(|| format!("未知的字段类型: {}", name))?;
+                if !spec.children.is_empty() {
+                    return Err(anyhow!("字段 {} 的类型为 {}，不能有子字段", number, name));
+                }
+                kind
+            }
+        };
+        let label = match (spec.repeated, kind.is_scalar_numeric()) {
+            (false, _) => Label::Optional,
+            (true, true) => Label::Packed,
+            (true, false) => Label::Repeated,
+        };
+        let name: &'static str = Box::leak(number.to_string().into_boxed_str());
+        fields.push(Field { number: *number, name, kind, label });
+    }
+    Ok(Box::leak(Box::new(Schema { name: "cli", fields: Box::leak(fields.into_boxed_slice()) })))
+}
+
+/// 打印字段树
+fn print_tree(nodes: &[Node], depth: usize) {
+    let indent = "  ".repeat(depth);
+    for node in nodes {
+        println!(
+            "{}{} {} {}",
+            indent,
+            format!("#{}", node.field_number).cyan().bold(),
+            format!("{:?}", node.wire_type).yellow(),
+            format!("@{} [{}..{}] len={}", node.offset, node.start, node.end, node.end - node.start).dimmed(),
+        );
+        for interpretation in &node.interpretations {
+            // 字节在可以解码为字符串时不再重复显示
+            if matches!(interpretation, Interpretation::Bytes(_))
+                && node.interpretations.iter().any(|i| matches!(i, Interpretation::String(_)))
+            {
+                continue;
+            }
+            println!("{}  {}", indent, Protobuf::describe(interpretation));
+        }
+        if let Some(children) = &node.children {
+            if !children.is_empty() {
+                println!("{}  {}", indent, "message:".magenta());
+                print_tree(children, depth + 2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_format() {
+        // 只含hex字符的base64
+        assert_eq!(decode_text("CAFEBABE", InputFormat::Auto).unwrap(), vec![0x08, 0x01, 0x44, 0x04, 0x00, 0x44]);
+        assert_eq!(decode_text("0x0801", InputFormat::Auto).unwrap(), vec![0x08, 0x01]);
+        assert_eq!(decode_text("08 01 10 02", InputFormat::Auto).unwrap(), vec![0x08, 0x01, 0x10, 0x02]);
+        assert_eq!(decode_text("08:01", InputFormat::Auto).unwrap(), vec![0x08, 0x01]);
+        assert_eq!(decode_text("CAFEBABE", InputFormat::Hex).unwrap(), vec![0xca, 0xfe, 0xba, 0xbe]);
+    }
+
+    #[test]
+    fn test_build_schema() {
+        let types: Vec<String> = ["1=sint64", "2=fixed64", "3=group", "3.1=string", "4=uint32[]", "5=double"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let data = decode_hex(
+            "08 01 11 ff ff ff ff ff ff ff ff 1b 0a 01 61 1c 22 03 01 02 03 29 00 00 00 00 00 00 f8 3f",
+        )
+        .unwrap();
+        let value = Protobuf::deserialize_with(&data, build_schema(&types).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "1": -1,
+                "2": "18446744073709551615",
+                "3": {"1": "a"},
+                "4": [1, 2, 3],
+                "5": 1.5,
+            })
+        );
+        assert!(build_schema(&["1=varint".to_string()]).is_err());
+        assert!(build_schema(&["1=int32".to_string(), "1.2=string".to_string()]).is_err());
+    }
+}
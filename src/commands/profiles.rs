@@ -0,0 +1,68 @@
+use anyhow::Result;
+use clap::Subcommand;
+use std::fs;
+use crate::cookie::Cookie;
+use crate::helper::Helper;
+use crate::login::crypto::Envelope;
+use crate::profile::{Profile, PROFILE_ENV};
+
+/// 账号配置子命令
+#[derive(Subcommand, Debug)]
+pub enum ProfilesCommand {
+    /// 列出所有账号配置
+    List,
+}
+
+/// 执行profiles子命令
+pub fn run(command: ProfilesCommand) -> Result<()> {
+    match command {
+        ProfilesCommand::List => list(),
+    }
+}
+
+fn list() -> Result<()> {
+    let current = Profile::current();
+    let profiles = Profile::list()?;
+    if profiles.is_empty() {
+        Helper::echo(&format!("暂无账号配置: {}", Profile::profiles_dir()?.display()), "yellow");
+        Helper::echo(&format!("使用 --profile <名称>（或环境变量 {}）执行 login import 创建", PROFILE_ENV), "yellow");
+        return Ok(());
+    }
+
+    for profile in &profiles {
+        let active = profile.name() == current.name();
+        let name = profile.name().unwrap_or_default();
+        // 加密的 cookie.json 不解密，避免逐个询问口令
+        let cookie_path = profile.cookie_path();
+        let account = match Envelope::read(&cookie_path) {
+            Some(envelope) => format!("已加密（{}）", envelope.describe()),
+            None => match Cookie::load_from_file(&cookie_path) {
+                Ok(cookie) if cookie.is_expired() => format!("{}({}) 已过期", cookie.nickname, cookie.account),
+                Ok(cookie) => format!("{}({})", cookie.nickname, cookie.account),
+                Err(_) => "未登录".to_string(),
+            },
+        };
+        let line = format!(
+            "{} {}  {}  {} 个会话  {}",
+            if active { "*" } else { " " },
+            name,
+            account,
+            count_databases(profile),
+            profile.root().display()
+        );
+        Helper::echo(&line, if active { "green" } else { "white" });
+    }
+    Ok(())
+}
+
+/// db 目录下的数据库文件数
+fn count_databases(profile: &Profile) -> usize {
+    fs::read_dir(profile.db_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "db"))
+                .count()
+        })
+        .unwrap_or(0)
+}
@@ -0,0 +1,69 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::cookie::Cookie;
+use crate::export::SenderNames;
+use crate::helper::Helper;
+use crate::profile::Profile;
+use crate::serve::onebot::{self, OneBotState};
+use crate::serve::{router, ServeState};
+
+/// 本地HTTP服务参数
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// 存放 <uid>.db 的目录，默认为当前账号配置的 db 目录
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    /// 监听地址
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    bind: SocketAddr,
+    /// 发送者名称，格式为 UIN=名称，可重复
+    #[arg(long = "name", value_name = "UIN=NAME")]
+    names: Vec<String>,
+    /// 自己的QQ号，默认从未加密的 cookie.json 读取
+    #[arg(long)]
+    self_uin: Option<i64>,
+    /// 同时提供OneBot v11 HTTP API的只读动作
+    #[arg(long)]
+    onebot: bool,
+    /// OneBot API的access_token
+    #[arg(long, requires = "onebot")]
+    access_token: Option<String>,
+}
+
+pub async fn run(args: ServeArgs) -> Result<()> {
+    let profile = Profile::current();
+    let dir = args.dir.unwrap_or_else(|| profile.db_dir());
+    if !dir.is_dir() {
+        bail!("目录不存在: {}", dir.display());
+    }
+
+    // 未加密的 cookie.json 中有自己的QQ号和昵称
+    let cookie = Cookie::load_unencrypted(profile.cookie_path());
+    let self_uin = args
+        .self_uin
+        .or_else(|| cookie.as_ref().and_then(|c| c.account.parse().ok()));
+    let self_name = cookie
+        .filter(|c| Some(c.account.as_str()) == self_uin.map(|u| u.to_string()).as_deref())
+        .map(|c| c.nickname);
+    let mut overrides = profile.load_config()?.name_overrides();
+    overrides.extend(args.names);
+    let names = SenderNames::new(self_uin, self_name, &overrides)?;
+
+    let state = Arc::new(ServeState::new(dir, names)?);
+    let mut app = router(state.clone());
+    if args.onebot {
+        app = app.merge(onebot::router(Arc::new(OneBotState::new(state, args.access_token))));
+    }
+    let listener = tokio::net::TcpListener::bind(args.bind)
+        .await
+        .with_context(|| format!("无法监听 {}", args.bind))?;
+    Helper::echo(&format!("服务已启动，打开 http://{} 浏览消息记录", args.bind), "green");
+    if args.onebot {
+        Helper::echo(&format!("OneBot v11 API: http://{}/<action>", args.bind), "green");
+    }
+    axum::serve(listener, app).await?;
+    Ok(())
+}
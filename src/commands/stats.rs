@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use crate::commands::export::{RangeArgs, SourceArgs};
+use crate::helper::Helper;
+use crate::stats::{StatsCollector, StatsOptions};
+
+/// 统计参数
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+    #[command(flatten)]
+    range: RangeArgs,
+    /// 高频词、消息最多的日期和最长沉默的条数
+    #[arg(long, default_value_t = 20)]
+    top: usize,
+    /// 输出JSON而不是表格
+    #[arg(long)]
+    json: bool,
+    /// JSON输出文件，默认输出到标准输出
+    #[arg(long, requires = "json")]
+    out: Option<PathBuf>,
+}
+
+/// 执行stats子命令
+pub fn run(args: StatsArgs) -> Result<()> {
+    let (db, names) = args.source.open()?;
+    let (start, end) = args.range.bounds()?;
+    let options = StatsOptions {
+        start,
+        end,
+        timezone: args.range.timezone,
+        top: args.top,
+    };
+    let report = StatsCollector::collect(&db, &names, options)?;
+
+    if !args.json {
+        report.print(args.range.timezone, args.top);
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(&report)?;
+    match args.out {
+        Some(path) => {
+            fs::write(&path, json).with_context(|| format!("无法写入 {}", path.display()))?;
+            Helper::echo(&format!("统计结果已保存到 {}", path.display()), "green");
+        }
+        None => writeln!(io::stdout().lock(), "{}", json)?,
+    }
+    Ok(())
+}
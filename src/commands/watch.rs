@@ -0,0 +1,514 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::api::Api;
+use crate::cookie::Cookie;
+use crate::helper::Helper;
+use crate::login::crypto::write_atomic;
+use crate::login::refresh;
+use crate::message::Message;
+use crate::profile::Profile;
+use crate::sync::{self, Archives};
+
+/// 默认轮询间隔（秒）
+const DEFAULT_INTERVAL: u64 = 60;
+
+/// 连续失败时间隔加倍，最多放大 2^4 = 16 倍
+const MAX_BACKOFF_SHIFT: u32 = 4;
+
+/// 每轮每个私聊最多向前翻的漫游消息页数
+const MAX_ROAM_PAGES: u32 = 20;
+
+/// watch参数，未指定的从 config.json 的 `watch` 读取
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// 私聊对象的QQ号或uid，可多次指定
+    #[arg(long = "peer")]
+    peers: Vec<String>,
+    /// 群号，可多次指定
+    #[arg(long = "group")]
+    groups: Vec<i64>,
+    /// 轮询间隔（秒），默认60
+    #[arg(long)]
+    interval: Option<u64>,
+    /// 同时用漫游消息接口拉取私聊，补上离线消息接口取不到的消息
+    #[arg(long)]
+    roam: bool,
+    /// 只轮询一次，失败时以非零状态退出
+    #[arg(long)]
+    once: bool,
+    /// 日志文件，默认为账号配置下的 watch.log
+    #[arg(long)]
+    log: Option<PathBuf>,
+    /// 状态文件，默认为账号配置下的 watch.json
+    #[arg(long)]
+    status: Option<PathBuf>,
+}
+
+/// 状态文件内容，重启后从中恢复同步进度
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct WatchStatus {
+    pid: u32,
+    started_at: i64,
+    last_poll: i64,
+    last_success: i64,
+    last_error: Option<String>,
+    consecutive_failures: u32,
+    polls: u64,
+    /// 启动以来新增的消息数
+    saved: u64,
+    /// 离线消息接口的同步游标
+    sync_cookie: String,
+    conversations: BTreeMap<String, ConversationStatus>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ConversationStatus {
+    /// 已保存的最新消息时间
+    last_time: i64,
+    /// 累计新增的消息数
+    saved: u64,
+}
+
+/// 轮询器
+struct Watcher<'a> {
+    profile: &'a Profile,
+    api: Api,
+    self_uin: i64,
+    peers: Vec<String>,
+    groups: HashSet<i64>,
+    roam: bool,
+    archives: Archives<'a>,
+    status: WatchStatus,
+    status_path: PathBuf,
+    log: fs::File,
+}
+
+/// 执行watch子命令
+pub async fn run(args: WatchArgs) -> Result<()> {
+    let profile = Profile::current();
+    let config = profile.load_config()?.watch;
+    let peers = if args.peers.is_empty() { config.peers } else { args.peers };
+    let groups: HashSet<i64> = if args.groups.is_empty() { config.groups } else { args.groups }
+        .into_iter()
+        .collect();
+    if peers.is_empty() && groups.is_empty() {
+        bail!("没有要同步的会话，请使用 --peer/--group 或在 config.json 的 watch 中配置");
+    }
+    let interval = args.interval.or(config.interval).unwrap_or(DEFAULT_INTERVAL).max(5);
+
+    let cookie_path = profile.cookie_path();
+    let mut cookie = Cookie::load_from_file(&cookie_path)?;
+    if cookie.is_expired() {
+        if cookie.refresh_ticket().is_none() {
+            bail!("登录信息已过期，请重新登录");
+        }
+        cookie = refresh::refresh_file(&reqwest::Client::new(), &refresh::refresh_url(), &cookie_path).await?;
+    }
+    let self_uin: i64 = cookie.account.parse().context("cookie.json 中的账号不是QQ号")?;
+    let api = Api::new(cookie.to_login_info()?)?.with_refresh(cookie_path);
+
+    let log_path = args.log.unwrap_or_else(|| profile.root().join("watch.log"));
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("无法打开日志文件: {}", log_path.display()))?;
+    let status_path = args.status.unwrap_or_else(|| profile.root().join("watch.json"));
+    let mut status = WatchStatus::load(&status_path);
+    status.pid = std::process::id();
+    status.started_at = chrono::Utc::now().timestamp();
+    status.saved = 0;
+
+    let mut watcher = Watcher {
+        profile,
+        api,
+        self_uin,
+        peers,
+        groups,
+        roam: args.roam || config.roam,
+        archives: Archives::new(profile),
+        status,
+        status_path,
+        log,
+    };
+    watcher.log(
+        &format!(
+            "开始同步 {} 个私聊、{} 个群，间隔 {} 秒，状态文件: {}",
+            watcher.peers.len(),
+            watcher.groups.len(),
+            interval,
+            watcher.status_path.display()
+        ),
+        "cyan",
+    );
+
+    loop {
+        // 轮询中途也响应中断：已保存的消息保留，游标不推进，下次启动时重新拉取
+        let result = tokio::select! {
+            result = watcher.poll() => result,
+            _ = tokio::signal::ctrl_c() => return watcher.interrupted(),
+        };
+        let now = chrono::Utc::now().timestamp();
+        watcher.status.polls += 1;
+        watcher.status.last_poll = now;
+        let failed = match result {
+            Ok(_) => {
+                watcher.status.last_success = now;
+                watcher.status.last_error = None;
+                watcher.status.consecutive_failures = 0;
+                false
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                watcher.log(&format!("同步失败: {}", message), "red");
+                watcher.status.last_error = Some(message);
+                watcher.status.consecutive_failures += 1;
+                true
+            }
+        };
+        watcher.save_status();
+
+        if args.once {
+            if failed {
+                bail!("同步失败");
+            }
+            return Ok(());
+        }
+
+        let backoff = 1u64 << watcher.status.consecutive_failures.min(MAX_BACKOFF_SHIFT);
+        let delay = Duration::from_secs(interval * backoff);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => return watcher.interrupted(),
+        }
+    }
+}
+
+impl WatchStatus {
+    /// 读取状态文件，不存在或无法解析时为默认值
+    fn load(path: &Path) -> WatchStatus {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Watcher<'_> {
+    /// 轮询一次，返回新增的消息数
+    async fn poll(&mut self) -> Result<usize> {
+        let data = self.api.sso_get_offline_msg(687, &self.status.sync_cookie).await?;
+        Api::save_uid_to(self.profile, &data)?;
+        // 所有消息保存后才推进游标，保存失败时下一轮重新拉取
+        let sync_cookie = data.get("sync_cookie").and_then(Value::as_str);
+
+        let (uids, uins) = self.resolve_peers();
+        let mut batches: BTreeMap<String, Vec<Message>> = BTreeMap::new();
+        for item in sync::split_offline(&data, self.self_uin) {
+            let wanted = match item.peer_uin {
+                Some(uin) => uids.contains(&item.conversation) || uins.contains(&uin),
+                None => item.conversation.parse().is_ok_and(|code| self.groups.contains(&code)),
+            };
+            if wanted {
+                batches.entry(item.conversation).or_default().push(item.message);
+            }
+        }
+
+        if self.roam {
+            for uid in &uids {
+                match self.roam_peer(uid).await {
+                    Ok(messages) => batches.entry(uid.clone()).or_default().extend(messages),
+                    // 单个私聊失败不影响离线消息的保存
+                    Err(e) => self.log(&format!("{}: 拉取漫游消息失败: {:#}", uid, e), "yellow"),
+                }
+            }
+        }
+
+        let mut total = 0;
+        for (conversation, messages) in batches {
+            if messages.is_empty() {
+                continue;
+            }
+            let new = self.archives.save(&conversation, &messages)?;
+            let last_time = messages.iter().map(|m| m.content_head.msg_time).max().unwrap_or(0);
+            let entry = self.status.conversations.entry(conversation.clone()).or_default();
+            entry.last_time = entry.last_time.max(last_time);
+            entry.saved += new as u64;
+            if new > 0 {
+                self.log(&format!("{}: 新增 {} 条消息", conversation, new), "green");
+            }
+            total += new;
+        }
+        self.status.saved += total as u64;
+        if let Some(cookie) = sync_cookie {
+            self.status.sync_cookie = cookie.to_string();
+        }
+        Ok(total)
+    }
+
+    /// 从最新的漫游消息向前翻页，直到已保存的时间
+    ///
+    /// 会话还没有消息时只取最新一页，完整的历史请用 `--uin` 拉取。
+    async fn roam_peer(&mut self, uid: &str) -> Result<Vec<Message>> {
+        let since = match self.status.conversations.get(uid).map(|c| c.last_time).filter(|t| *t > 0) {
+            Some(t) => Some(t),
+            None => self.archives.latest_time(uid)?,
+        };
+
+        let mut messages = Vec::new();
+        let mut msg_time = chrono::Utc::now().timestamp();
+        let mut random = 0;
+        for _ in 0..MAX_ROAM_PAGES {
+            let page = self.api.sso_get_roam_msg(uid, msg_time, random, 50, 1).await?;
+            let msgs = match page.get("msg").and_then(Value::as_array) {
+                Some(m) if !m.is_empty() => m,
+                _ => break,
+            };
+            let parsed: Vec<Message> = msgs.iter().filter_map(|m| sync::parse_message(m).ok()).collect();
+            let oldest = parsed.iter().map(|m| m.content_head.msg_time).min().unwrap_or(0);
+            messages.extend(parsed);
+
+            match (since, page.get("res_last_time").and_then(Value::as_i64)) {
+                (Some(since), Some(next)) if oldest > since => {
+                    msg_time = next;
+                    random = page.get("random").and_then(Value::as_i64).unwrap_or(0);
+                }
+                _ => break,
+            }
+        }
+        Ok(messages)
+    }
+
+    /// 配置的私聊对象：uid直接使用，QQ号通过 uids.json 查找
+    fn resolve_peers(&self) -> (Vec<String>, HashSet<i64>) {
+        let known = self.profile.load_uids();
+        let mut uids = Vec::new();
+        let mut uins = HashSet::new();
+        for peer in &self.peers {
+            match peer.parse::<i64>() {
+                Ok(uin) => {
+                    uins.insert(uin);
+                    if let Some(uid) = known.get(peer) {
+                        uids.push(uid.clone());
+                    }
+                }
+                Err(_) => uids.push(peer.clone()),
+            }
+        }
+        (uids, uins)
+    }
+
+    /// 输出并追加到日志文件
+    fn log(&mut self, text: &str, color: &str) {
+        Helper::echo(text, color);
+        let line = format!("[{}] {}\n", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), text);
+        if let Err(e) = self.log.write_all(line.as_bytes()) {
+            Helper::echo(&format!("写入日志失败: {}", e), "red");
+        }
+    }
+
+    /// 先写临时文件再重命名，读取状态的程序不会读到一半的内容
+    ///
+    /// 写入失败（如磁盘已满）只记录日志，不中断同步。
+    fn save_status(&mut self) {
+        let result = serde_json::to_string_pretty(&self.status)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| write_atomic(&self.status_path, content.as_bytes()));
+        if let Err(e) = result {
+            self.log(&format!("保存状态失败: {:#}", e), "red");
+        }
+    }
+
+    /// 收到中断信号时保存状态后退出
+    fn interrupted(&mut self) -> Result<()> {
+        self.log("收到中断信号，退出", "yellow");
+        self.save_status();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cookie::LoginInfo;
+    use axum::routing::post;
+    use axum::Json;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// 已保存消息的时间
+    const SINCE: i64 = 1700000000;
+
+    fn raw(from_uin: i64, from_uid: &str, to_uin: i64, to_uid: &str, seq: i64, msg_time: i64) -> Value {
+        json!({
+            "content_head": {"msg_time": msg_time, "nt_msg_seq": seq},
+            "routing_head": {"from_uin": from_uin, "from_uid": from_uid, "to_uin": to_uin, "to_uid": to_uid},
+            "body": {"rich_text": {"elems": [{"text": {"str": "aGk="}}]}},
+        })
+    }
+
+    /// 模拟离线消息和漫游消息接口，记录漫游消息的请求
+    ///
+    /// `u_peer` 的漫游消息第一页有两条比 [`SINCE`] 新的消息，第二页翻到 [`SINCE`] 之前；其他会话只有一页。
+    async fn mock_api() -> (String, Arc<Mutex<Vec<Value>>>) {
+        let offline = json!({"retcode": 0, "data": {
+            "sync_cookie": "c1",
+            "c2c_msg_list": [{"msgs": [
+                raw(2, "u_peer", 1, "u_self", 1, SINCE + 10),
+                raw(1, "u_self", 2, "u_peer", 2, SINCE + 20),
+                raw(4, "u_other", 1, "u_self", 3, SINCE + 30),
+            ]}],
+            "group_msg_list": [
+                {"group_code": 123456, "msg": [raw(3, "u_member", 0, "", 4, SINCE + 40)]},
+                {"group_code": 999, "msg": [raw(3, "u_member", 0, "", 5, SINCE + 50)]},
+            ],
+        }});
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let app = axum::Router::new()
+            .route("/trpc.msg.nt_register_proxy.RegisterProxy", post(move || async move { Json(offline) }))
+            .route(
+                "/trpc.msg.nt_register_proxy.RegisterProxy/SsoGetRoamMsg",
+                post(move |Json(body): Json<Value>| async move {
+                    recorded.lock().unwrap().push(body.clone());
+                    let peer = body["peer_uid"].as_str().unwrap_or("").to_string();
+                    let data = match body["msg_time"].as_i64() {
+                        Some(5000) => json!({"msg": [
+                            raw(2, &peer, 1, "u_self", 12, SINCE + 100),
+                            raw(2, &peer, 1, "u_self", 11, SINCE - 50),
+                        ], "res_last_time": 4000, "random": 8}),
+                        Some(4000) => json!({"msg": []}),
+                        _ => json!({"msg": [
+                            raw(2, &peer, 1, "u_self", 14, SINCE + 300),
+                            raw(2, &peer, 1, "u_self", 13, SINCE + 200),
+                        ], "res_last_time": 5000, "random": 7}),
+                    };
+                    Json(json!({"retcode": 0, "data": data}))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    fn login_info() -> LoginInfo {
+        LoginInfo {
+            uin: "1".to_string(),
+            nickname: String::new(),
+            avatar: String::new(),
+            p_skey: "p".to_string(),
+            g_tk: 0,
+            expire_at: 0,
+            cookie: String::new(),
+        }
+    }
+
+    fn watcher<'a>(profile: &'a Profile, url: &str, roam: bool) -> Watcher<'a> {
+        Watcher {
+            profile,
+            api: Api::new(login_info()).unwrap().with_urls(url, url),
+            self_uin: 1,
+            peers: vec!["2".to_string()],
+            groups: HashSet::from([123456]),
+            roam,
+            archives: Archives::new(profile),
+            status: WatchStatus::default(),
+            status_path: profile.root().join("watch.json"),
+            log: fs::File::create(profile.root().join("watch.log")).unwrap(),
+        }
+    }
+
+    fn temp_profile(name: &str) -> Profile {
+        let dir = std::env::temp_dir().join(format!("qqhistory-watch-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        Profile::at(dir)
+    }
+
+    #[tokio::test]
+    async fn test_poll() {
+        let profile = temp_profile("poll");
+        let (url, _) = mock_api().await;
+        let mut watcher = watcher(&profile, &url, false);
+
+        // 只保存配置的私聊和群，QQ号通过离线消息中的uid映射找到会话
+        assert_eq!(watcher.poll().await.unwrap(), 3);
+        let conversations: Vec<_> = watcher.status.conversations.iter().map(|(k, v)| (k.as_str(), v.last_time, v.saved)).collect();
+        assert_eq!(conversations, [("123456", SINCE + 40, 1), ("u_peer", SINCE + 20, 2)]);
+        assert_eq!(watcher.status.sync_cookie, "c1");
+        assert_eq!(profile.load_uids()["2"], "u_peer");
+        assert!(!profile.db_file("u_other").exists());
+        assert!(!profile.db_file("999").exists());
+
+        // 重复的消息不计入
+        assert_eq!(watcher.poll().await.unwrap(), 0);
+        assert_eq!(watcher.status.saved, 3);
+        fs::remove_dir_all(profile.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_roam_peer() {
+        let profile = temp_profile("roam");
+        let (url, requests) = mock_api().await;
+        let mut watcher = watcher(&profile, &url, true);
+
+        // 没有已保存的消息时只取最新一页
+        assert_eq!(watcher.roam_peer("u_new").await.unwrap().len(), 2);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // 向前翻页直到早于已保存的时间
+        watcher.status.conversations.insert("u_peer".to_string(), ConversationStatus { last_time: SINCE, saved: 0 });
+        let seqs: Vec<i64> = watcher.roam_peer("u_peer").await.unwrap().iter().map(|m| m.content_head.msg_seq).collect();
+        assert_eq!(seqs, [14, 13, 12, 11]);
+        let pages: Vec<_> = requests.lock().unwrap()[1..].iter().map(|r| (r["msg_time"].clone(), r["random"].clone())).collect();
+        assert_eq!(pages[1], (json!(5000), json!(7)));
+        assert_eq!(pages.len(), 2);
+
+        // 空页时停止
+        watcher.status.conversations.get_mut("u_peer").unwrap().last_time = SINCE - 100;
+        assert_eq!(watcher.roam_peer("u_peer").await.unwrap().len(), 4);
+        assert_eq!(requests.lock().unwrap().len(), 6);
+
+        // 完整的一轮：离线消息和漫游消息合并保存
+        assert_eq!(watcher.poll().await.unwrap(), 7);
+        fs::remove_dir_all(profile.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_status_roundtrip() {
+        let profile = temp_profile("status");
+        let (url, _) = mock_api().await;
+        let mut watcher = watcher(&profile, &url, false);
+        watcher.status.sync_cookie = "c0".to_string();
+        watcher.status.consecutive_failures = 2;
+        watcher.status.conversations.insert("u_peer".to_string(), ConversationStatus { last_time: SINCE, saved: 5 });
+        watcher.save_status();
+
+        let loaded = WatchStatus::load(&watcher.status_path);
+        assert_eq!(loaded.sync_cookie, "c0");
+        assert_eq!(loaded.consecutive_failures, 2);
+        assert_eq!(loaded.conversations["u_peer"].last_time, SINCE);
+        assert_eq!(loaded.conversations["u_peer"].saved, 5);
+
+        // 旧版本的状态文件缺少字段，损坏的文件从头开始
+        fs::write(&watcher.status_path, r#"{"sync_cookie":"c1"}"#).unwrap();
+        assert_eq!(WatchStatus::load(&watcher.status_path).sync_cookie, "c1");
+        fs::write(&watcher.status_path, "{").unwrap();
+        assert_eq!(WatchStatus::load(&watcher.status_path).sync_cookie, "");
+
+        // 写入失败时不中断
+        watcher.status_path = profile.root().join("missing").join("watch.json");
+        watcher.save_status();
+        assert!(fs::read_to_string(profile.root().join("watch.log")).unwrap().contains("保存状态失败"));
+        fs::remove_dir_all(profile.root()).ok();
+    }
+}
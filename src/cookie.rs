@@ -1,7 +1,11 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
 use std::fs;
+use std::path::Path;
 use crate::helper::Helper;
+use crate::login::crypto::{write_atomic, Envelope, KeyMethod};
 
 /// Cookie票据信息
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -18,10 +22,13 @@ pub struct Ticket {
     pub openid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<String>,
+    /// 未知字段，重写cookie.json时原样保留
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// Cookie数据结构
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Cookie {
     pub result: i32,
     pub msg: String,
@@ -30,10 +37,12 @@ pub struct Cookie {
     pub nickname: String,
     pub avatar_url: String,
     pub tickets: Vec<Ticket>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// 登录信息
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LoginInfo {
     pub uin: String,
     pub nickname: String,
@@ -45,18 +54,101 @@ pub struct LoginInfo {
     pub cookie: String,
 }
 
+impl fmt::Debug for LoginInfo {
+    /// 不输出票据，避免出现在日志中
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginInfo")
+            .field("uin", &self.uin)
+            .field("nickname", &self.nickname)
+            .field("g_tk", &self.g_tk)
+            .field("expire_at", &self.expire_at)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Cookie {
-    /// 从JSON文件加载Cookie
-    pub fn load_from_file(path: &str) -> Result<Self> {
+    /// 从JSON文件加载Cookie，加密的文件先解密
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let content = fs::read_to_string(path)
-            .with_context(|| format!("无法读取cookie文件: {}", path))?;
+            .with_context(|| format!("无法读取cookie文件: {}", path.display()))?;
         
-        let cookie: Cookie = serde_json::from_str(&content)
+        let value: Value = serde_json::from_str(&content)
             .with_context(|| "解析cookie.json失败")?;
+        let cookie: Cookie = match Envelope::from_value(&value) {
+            Some(envelope) => {
+                let plain = envelope.open()
+                    .with_context(|| format!("无法解密: {}", path.display()))?;
+                serde_json::from_slice(&plain).with_context(|| "解析cookie.json失败")?
+            }
+            None => serde_json::from_value(value).with_context(|| "解析cookie.json失败")?,
+        };
         
         Ok(cookie)
     }
 
+    /// 只读取未加密的文件，用于获取自己的QQ号和昵称
+    ///
+    /// 加密的文件不解密，避免只为显示名称而询问口令；无法读取时为None，需要时可用 `--self-uin` 指定。
+    pub fn load_unencrypted<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        if Envelope::read(path).is_some() {
+            return None;
+        }
+        Self::load_from_file(path).ok()
+    }
+
+    /// 原子地写入JSON文件：先写临时文件再重命名，中途失败不会损坏原文件
+    ///
+    /// 原文件已加密时用同一密钥加密后写入。
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let plain = serde_json::to_string_pretty(self)?;
+        let content = match Envelope::read(path) {
+            Some(envelope) => serde_json::to_string_pretty(&envelope.reseal(plain.as_bytes())?.to_value())?,
+            None => plain,
+        };
+        write_atomic(path, content.as_bytes())
+    }
+
+    /// 加密后原子地写入
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, method: KeyMethod) -> Result<()> {
+        let plain = serde_json::to_string_pretty(self)?;
+        let envelope = Envelope::seal(plain.as_bytes(), method)?;
+        write_atomic(path.as_ref(), serde_json::to_string_pretty(&envelope.to_value())?.as_bytes())
+    }
+
+    /// 带有refresh_token的票据，优先 myqq.qq.com 域
+    pub fn refresh_ticket(&self) -> Option<&Ticket> {
+        self.tickets
+            .iter()
+            .filter(|t| t.refresh_token.is_some())
+            .max_by_key(|t| t.domain.as_deref() == Some("myqq.qq.com"))
+    }
+
+    /// 合并刷新得到的票据
+    ///
+    /// 按名称和域替换已有票据，新票据没有 refresh_token/appid/openid 时沿用旧值。
+    pub fn merge_refreshed(&mut self, fresh: Cookie) {
+        for mut ticket in fresh.tickets {
+            match self.tickets.iter_mut().find(|t| t.name == ticket.name && t.domain == ticket.domain) {
+                Some(old) => {
+                    ticket.refresh_token = ticket.refresh_token.or(old.refresh_token.take());
+                    ticket.appid = ticket.appid.or(old.appid.take());
+                    ticket.openid = ticket.openid.or(old.openid.take());
+                    *old = ticket;
+                }
+                None => self.tickets.push(ticket),
+            }
+        }
+        if !fresh.nickname.is_empty() {
+            self.nickname = fresh.nickname;
+        }
+        if !fresh.avatar_url.is_empty() {
+            self.avatar_url = fresh.avatar_url;
+        }
+    }
+
     /// 转换为登录信息
     pub fn to_login_info(&self) -> Result<LoginInfo> {
         // 查找 myqq.qq.com 域的票据
@@ -95,3 +187,27 @@ impl Cookie {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_refreshed() {
+        let mut cookie: Cookie = serde_json::from_str(r#"{"result":0,"msg":"","req_id":"","account":"1",
+            "nickname":"a","avatar_url":"","unknown":1,"tickets":[
+            {"name":"pskey","domain":"myqq.qq.com","ticket":"old","expire_at":1,"refresh_token":"rt","appid":"wx"},
+            {"name":"skey","ticket":"s"}]}"#).unwrap();
+        let fresh: Cookie = serde_json::from_str(r#"{"result":0,"msg":"","req_id":"","account":"1",
+            "nickname":"","avatar_url":"","tickets":[
+            {"name":"pskey","domain":"myqq.qq.com","ticket":"new","expire_at":2}]}"#).unwrap();
+        cookie.merge_refreshed(fresh);
+
+        let ticket = cookie.refresh_ticket().unwrap();
+        assert_eq!(ticket.ticket, "new");
+        assert_eq!(ticket.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(ticket.appid.as_deref(), Some("wx"));
+        assert_eq!(cookie.tickets.len(), 2);
+        assert_eq!(cookie.nickname, "a");
+        assert_eq!(serde_json::to_value(&cookie).unwrap()["unknown"], 1);
+    }
+}
@@ -1,7 +1,29 @@
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
-use serde_json::Value;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use crate::helper::Helper;
+use crate::message::{ContentHead, Element, Message, RoutingHead, SCHEMA_VERSION};
+
+/// 提供SQLCipher数据库密钥的环境变量
+pub const DB_KEY_ENV: &str = "QQHISTORY_DB_KEY";
+
+/// 本次运行中已输入的数据库密钥
+#[cfg(feature = "sqlcipher")]
+static DB_KEY: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// 消息查询的列顺序
+const MESSAGE_COLUMNS: &str = "id, from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, 
+             random, client_seq, msg_time, body, created_at, body_version";
+
+/// 数据库中保存的消息
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredMessage {
+    pub id: i64,
+    #[serde(flatten)]
+    pub message: Message,
+    pub created_at: String,
+}
 
 /// 数据库操作类
 pub struct Database {
@@ -10,9 +32,11 @@ pub struct Database {
 
 impl Database {
     /// 创建新的数据库实例
+    ///
+    /// 启用 sqlcipher 特性时，设置了 [`DB_KEY_ENV`] 则用其打开（新建的数据库也会加密），
+    /// 否则遇到加密的数据库时在终端询问密钥。
     pub fn new(db_file: &str) -> Result<Self> {
-        let conn = Connection::open(db_file)
-            .with_context(|| format!("无法打开数据库: {}", db_file))?;
+        let conn = open_connection(db_file)?;
 
         let db = Database { conn };
         db.create_table()?;
@@ -34,11 +58,35 @@ impl Database {
                 client_seq INTEGER NOT NULL,
                 msg_time INTEGER NOT NULL,
                 body TEXT NOT NULL,
+                body_version INTEGER NOT NULL DEFAULT 0,
+                raw TEXT NOT NULL DEFAULT '',
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 UNIQUE(msg_seq)
             )",
             [],
         )?;
+
+        // 旧版本数据库没有 body_version 列，其中的body均为版本0格式
+        let has_body_version = self
+            .conn
+            .prepare("SELECT 1 FROM pragma_table_info('messages') WHERE name = 'body_version'")?
+            .exists([])?;
+        if !has_body_version {
+            self.conn.execute(
+                "ALTER TABLE messages ADD COLUMN body_version INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        // 旧版本把富媒体的原始protobuf保存在body中，移到单独的raw列
+        let has_raw = self
+            .conn
+            .prepare("SELECT 1 FROM pragma_table_info('messages') WHERE name = 'raw'")?
+            .exists([])?;
+        if !has_raw {
+            self.conn.execute("ALTER TABLE messages ADD COLUMN raw TEXT NOT NULL DEFAULT ''", [])?;
+            self.move_raw_out_of_body()?;
+        }
         
         // 创建索引以提高查询性能
         self.conn.execute(
@@ -57,60 +105,41 @@ impl Database {
         Ok(())
     }
 
-    /// 保存单条消息
-    pub fn save_message(&self, message: &Value) -> Result<bool> {
-        let routing_head = message
-            .get("routing_head")
-            .with_context(|| "消息缺少routing_head")?;
-        let content_head = message
-            .get("content_head")
-            .with_context(|| "消息缺少content_head")?;
-
-        // 辅助函数：解析可能是字符串或数字的值
-        let parse_to_i64 = |v: &Value| -> i64 {
-            match v {
-                Value::Number(n) => n.as_i64().unwrap_or(0),
-                Value::String(s) => s.parse::<i64>().unwrap_or(0),
-                _ => 0,
-            }
-        };
+    /// 把body中的 `raw` 字段移到raw列，body按当前格式重新序列化
+    fn move_raw_out_of_body(&self) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let rows: Vec<(i64, String)> = tx
+            .prepare("SELECT id, body FROM messages WHERE body_version > 0 AND body LIKE '%\"raw\":%'")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        for (id, body) in rows {
+            let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&body) else { continue };
+            let mut raw = BTreeMap::new();
+            take_raw(&mut value, "body", &mut raw);
+            let Ok(elems) = serde_json::from_value::<Vec<Element>>(value) else { continue };
+            tx.execute(
+                "UPDATE messages SET body = ?1, raw = ?2 WHERE id = ?3",
+                params![serde_json::to_string(&elems)?, raw_column(&raw)?, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
 
-        let from_uin = routing_head.get("from_uin").map(parse_to_i64).unwrap_or(0);
-        let to_uin = routing_head.get("to_uin").map(parse_to_i64).unwrap_or(0);
-        let from_uid = routing_head
-            .get("from_uid")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let to_uid = routing_head
-            .get("to_uid")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-
-        // msg_seq可能为0，也需要保存
-        let msg_seq = content_head
-            .get("msg_seq")
-            .map(parse_to_i64)
-            .unwrap_or_else(|| {
-                // 如果没有msg_seq，使用msg_time作为备用标识
-                content_head.get("msg_time").map(parse_to_i64).unwrap_or(0)
-            });
-        let msg_uid = content_head
-            .get("msg_uid")
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let random = content_head.get("random").map(parse_to_i64).unwrap_or(0);
-        let client_seq = content_head.get("client_seq").map(parse_to_i64).unwrap_or(0);
-        let msg_time = content_head.get("msg_time").map(parse_to_i64).unwrap_or(0);
-
-        let body = message.get("body").with_context(|| "消息缺少body")?;
-        let body_str = serde_json::to_string(body)?;
+    /// 保存单条消息
+    pub fn save_message(&self, message: &Message) -> Result<bool> {
+        let routing_head = &message.routing_head;
+        let content_head = &message.content_head;
+        let body_str = serde_json::to_string(&message.body)?;
+        let mut raw = BTreeMap::new();
+        Element::collect_raw(&message.body, "body", &mut raw);
 
         // 使用INSERT OR REPLACE来处理重复消息
         // 如果msg_seq已存在，则更新；否则插入新记录
         self.conn.execute(
             "INSERT INTO messages (from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, 
-             random, client_seq, msg_time, body) 
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             random, client_seq, msg_time, body, body_version, raw) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
              ON CONFLICT(msg_seq) DO UPDATE SET
              from_uin=excluded.from_uin,
              to_uin=excluded.to_uin,
@@ -120,10 +149,22 @@ impl Database {
              random=excluded.random,
              client_seq=excluded.client_seq,
              msg_time=excluded.msg_time,
-             body=excluded.body",
+             body=excluded.body,
+             body_version=excluded.body_version,
+             raw=excluded.raw",
             params![
-                from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, random, client_seq,
-                msg_time, body_str
+                routing_head.from_uin,
+                routing_head.to_uin,
+                routing_head.from_uid,
+                routing_head.to_uid,
+                content_head.msg_seq,
+                content_head.msg_uid,
+                content_head.random,
+                content_head.client_seq,
+                content_head.msg_time,
+                body_str,
+                SCHEMA_VERSION,
+                raw_column(&raw)?
             ],
         )?;
 
@@ -131,7 +172,7 @@ impl Database {
     }
 
     /// 批量保存消息
-    pub fn save_messages(&self, messages: &[Value]) -> Result<(usize, usize)> {
+    pub fn save_messages(&self, messages: &[Message]) -> Result<(usize, usize)> {
         let mut success = 0;
         let mut failed = 0;
 
@@ -156,7 +197,6 @@ impl Database {
     }
 
     /// 检查消息是否存在
-    #[allow(dead_code)]
     pub fn message_exists(&self, msg_seq: i64) -> Result<bool> {
         let count: i64 = self.conn.query_row(
             "SELECT COUNT(*) FROM messages WHERE msg_seq = ?1",
@@ -167,7 +207,6 @@ impl Database {
     }
 
     /// 获取消息总数
-    #[allow(dead_code)]
     pub fn get_message_count(&self) -> Result<i64> {
         let count: i64 = self
             .conn
@@ -175,36 +214,86 @@ impl Database {
         Ok(count)
     }
 
-    /// 获取所有消息（按msg_time降序排序）
-    #[allow(dead_code)]
-    pub fn get_all_messages(&self, limit: i64, offset: i64) -> Result<Vec<Value>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, 
-             random, client_seq, msg_time, body, created_at 
+    /// 按id获取单条消息
+    pub fn get_message(&self, id: i64) -> Result<Option<StoredMessage>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM messages WHERE id = ?1",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query_map(params![id], Self::row_to_message)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// 按msg_seq获取单条消息
+    pub fn get_message_by_seq(&self, msg_seq: i64) -> Result<Option<StoredMessage>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM messages WHERE msg_seq = ?1",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query_map(params![msg_seq], Self::row_to_message)?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// 按时间升序逐条读取消息，不会一次性加载全部结果
+    ///
+    /// `start_time`/`end_time` 为闭区间，None表示不限制。返回读取的条数。
+    pub fn for_each_message<F>(&self, start_time: Option<i64>, end_time: Option<i64>, mut f: F) -> Result<usize>
+    where
+        F: FnMut(StoredMessage) -> Result<()>,
+    {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} 
+             FROM messages 
+             WHERE msg_time >= ?1 AND msg_time <= ?2 
+             ORDER BY msg_time ASC, id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query(params![start_time.unwrap_or(i64::MIN), end_time.unwrap_or(i64::MAX)])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            f(Self::row_to_message(row)?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// 按 (msg_time, id) 游标升序分页读取，返回游标之后的至多 `limit` 条消息
+    pub fn get_messages_after(&self, cursor: Option<(i64, i64)>, limit: i64) -> Result<Vec<StoredMessage>> {
+        let (msg_time, id) = cursor.unwrap_or((i64::MIN, i64::MIN));
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} 
              FROM messages 
+             WHERE (msg_time, id) > (?1, ?2) 
+             ORDER BY msg_time ASC, id ASC 
+             LIMIT ?3",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map(params![msg_time, id, limit], Self::row_to_message)?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row?);
+        }
+        Ok(results)
+    }
+
+    /// 按 (msg_time, id) 游标降序分页读取，返回游标之前的至多 `limit` 条消息
+    pub fn get_messages_before(&self, cursor: Option<(i64, i64)>, limit: i64) -> Result<Vec<StoredMessage>> {
+        let (msg_time, id) = cursor.unwrap_or((i64::MAX, i64::MAX));
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} 
+             FROM messages 
+             WHERE (msg_time, id) < (?1, ?2) 
              ORDER BY msg_time DESC, id DESC 
-             LIMIT ?1 OFFSET ?2"
-        )?;
+             LIMIT ?3",
+            MESSAGE_COLUMNS
+        ))?;
 
-        let rows = stmt.query_map(params![limit, offset], |row| {
-            let body_str: String = row.get(10)?;
-            let body: Value = serde_json::from_str(&body_str).unwrap_or(serde_json::json!([]));
-            
-            Ok(serde_json::json!({
-                "id": row.get::<_, i64>(0)?,
-                "from_uin": row.get::<_, i64>(1)?,
-                "to_uin": row.get::<_, i64>(2)?,
-                "from_uid": row.get::<_, String>(3)?,
-                "to_uid": row.get::<_, String>(4)?,
-                "msg_seq": row.get::<_, i64>(5)?,
-                "msg_uid": row.get::<_, String>(6)?,
-                "random": row.get::<_, i64>(7)?,
-                "client_seq": row.get::<_, i64>(8)?,
-                "msg_time": row.get::<_, i64>(9)?,
-                "body": body,
-                "created_at": row.get::<_, String>(11)?,
-            }))
-        })?;
+        let rows = stmt.query_map(params![msg_time, id, limit], Self::row_to_message)?;
 
         let mut results = Vec::new();
         for row in rows {
@@ -213,36 +302,65 @@ impl Database {
         Ok(results)
     }
 
+    /// 按关键词搜索消息的纯文本（不区分大小写），从游标之前按时间降序返回至多 `limit` 条
+    pub fn search_messages(&self, keyword: &str, cursor: Option<(i64, i64)>, limit: usize) -> Result<Vec<StoredMessage>> {
+        let (msg_time, id) = cursor.unwrap_or((i64::MAX, i64::MAX));
+        let keyword = keyword.to_lowercase();
+        // body为JSON：只有单个文本元素的消息可用LIKE粗筛，其余（占位符、提及、多元素拼接）都需解码后比较纯文本。
+        // 关键词含JSON转义字符或非ASCII大小写字母时LIKE无法匹配，不做粗筛
+        let pattern = keyword
+            .chars()
+            .all(|c| c != '"' && c != '\\' && !c.is_control() && (c.is_ascii() || c.to_uppercase().eq([c])))
+            .then(|| format!("%{}%", keyword.replace('%', "\\%").replace('_', "\\_")));
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} 
+             FROM messages 
+             WHERE (msg_time, id) < (?1, ?2) 
+               AND (?3 IS NULL 
+                    OR body LIKE ?3 ESCAPE '\\' 
+                    OR NOT (body LIKE '[{{\"type\":\"text\",%' 
+                            AND length(body) - length(replace(body, '\"type\":\"', '')) = 8)) 
+             ORDER BY msg_time DESC, id DESC",
+            MESSAGE_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query(params![msg_time, id, pattern])?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            let stored = Self::row_to_message(row)?;
+            if stored.message.plain_text().to_lowercase().contains(&keyword) {
+                results.push(stored);
+                if results.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// 消息中富媒体元素的原始protobuf（base64），键为消息JSON中的路径，如 `body.0.raw`；消息不存在时为None
+    pub fn get_raw(&self, id: i64) -> Result<Option<BTreeMap<String, String>>> {
+        let mut stmt = self.conn.prepare("SELECT raw FROM messages WHERE id = ?1")?;
+        let mut rows = stmt.query(params![id])?;
+        let Some(row) = rows.next()? else { return Ok(None) };
+        let raw: String = row.get(0)?;
+        if raw.is_empty() {
+            return Ok(Some(BTreeMap::new()));
+        }
+        Ok(Some(serde_json::from_str(&raw).context("raw列格式错误")?))
+    }
+
     /// 按时间范围查询消息（按msg_time降序）
-    #[allow(dead_code)]
-    pub fn get_messages_by_time_range(&self, start_time: i64, end_time: i64) -> Result<Vec<Value>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, 
-             random, client_seq, msg_time, body, created_at 
+    pub fn get_messages_by_time_range(&self, start_time: i64, end_time: i64) -> Result<Vec<StoredMessage>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} 
              FROM messages 
              WHERE msg_time BETWEEN ?1 AND ?2 
-             ORDER BY msg_time DESC, id DESC"
-        )?;
+             ORDER BY msg_time DESC, id DESC",
+            MESSAGE_COLUMNS
+        ))?;
 
-        let rows = stmt.query_map(params![start_time, end_time], |row| {
-            let body_str: String = row.get(10)?;
-            let body: Value = serde_json::from_str(&body_str).unwrap_or(serde_json::json!([]));
-            
-            Ok(serde_json::json!({
-                "id": row.get::<_, i64>(0)?,
-                "from_uin": row.get::<_, i64>(1)?,
-                "to_uin": row.get::<_, i64>(2)?,
-                "from_uid": row.get::<_, String>(3)?,
-                "to_uid": row.get::<_, String>(4)?,
-                "msg_seq": row.get::<_, i64>(5)?,
-                "msg_uid": row.get::<_, String>(6)?,
-                "random": row.get::<_, i64>(7)?,
-                "client_seq": row.get::<_, i64>(8)?,
-                "msg_time": row.get::<_, i64>(9)?,
-                "body": body,
-                "created_at": row.get::<_, String>(11)?,
-            }))
-        })?;
+        let rows = stmt.query_map(params![start_time, end_time], Self::row_to_message)?;
 
         let mut results = Vec::new();
         for row in rows {
@@ -250,5 +368,352 @@ impl Database {
         }
         Ok(results)
     }
+
+    /// 将查询结果行转换为消息，列顺序见 [`MESSAGE_COLUMNS`]
+    fn row_to_message(row: &rusqlite::Row) -> rusqlite::Result<StoredMessage> {
+        let id: i64 = row.get(0)?;
+        let body_str: String = row.get(10)?;
+        let body_version: u32 = row.get(12)?;
+        let body = Element::parse_body(&body_str, body_version).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                10,
+                rusqlite::types::Type::Text,
+                format!("消息 {} 的body格式错误: {:#}", id, e).into(),
+            )
+        })?;
+
+        Ok(StoredMessage {
+            id,
+            message: Message {
+                version: SCHEMA_VERSION,
+                content_head: ContentHead {
+                    msg_uid: row.get(6)?,
+                    random: row.get(7)?,
+                    client_seq: row.get(8)?,
+                    msg_time: row.get(9)?,
+                    msg_seq: row.get(5)?,
+                },
+                routing_head: RoutingHead {
+                    from_uin: row.get(1)?,
+                    to_uin: row.get(2)?,
+                    from_uid: row.get(3)?,
+                    to_uid: row.get(4)?,
+                },
+                body,
+            },
+            created_at: row.get(11)?,
+        })
+    }
+}
+
+/// raw列的内容：没有原始protobuf时为空字符串，否则为路径到base64的JSON对象
+fn raw_column(raw: &BTreeMap<String, String>) -> Result<String> {
+    if raw.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(serde_json::to_string(raw)?)
 }
 
+/// 取出旧版本body中元素的 `raw` 字段，键为消息JSON中的路径
+fn take_raw(value: &mut serde_json::Value, path: &str, out: &mut BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                take_raw(item, &format!("{}.{}", path, i), out);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(serde_json::Value::String(raw)) = map.remove("raw") {
+                if !raw.is_empty() {
+                    out.insert(format!("{}.raw", path), raw);
+                }
+            }
+            for key in ["source", "content"] {
+                if let Some(child) = map.get_mut(key) {
+                    take_raw(child, &format!("{}.{}", path, key), out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 能否读取数据库，文件加密且密钥不对时不能读取
+fn readable(conn: &Connection) -> bool {
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())).is_ok()
+}
+
+/// 打开数据库，必要时设置SQLCipher密钥
+#[cfg(feature = "sqlcipher")]
+fn open_connection(db_file: &str) -> Result<Connection> {
+    let open = || Connection::open(db_file).with_context(|| format!("无法打开数据库: {}", db_file));
+    let configured = DB_KEY
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| std::env::var(DB_KEY_ENV).ok().filter(|k| !k.is_empty()));
+
+    match configured {
+        Some(key) => {
+            let conn = open()?;
+            apply_key(&conn, &key)?;
+            if readable(&conn) {
+                return Ok(conn);
+            }
+            // 未加密的旧数据库
+            let conn = open()?;
+            if readable(&conn) {
+                return Ok(conn);
+            }
+            anyhow::bail!("数据库密钥不正确: {}", db_file);
+        }
+        None => {
+            let conn = open()?;
+            if readable(&conn) {
+                return Ok(conn);
+            }
+            let key = crate::login::crypto::secret_from_env_or_prompt(DB_KEY_ENV, "数据库密钥", false)?;
+            let conn = open()?;
+            apply_key(&conn, &key)?;
+            if !readable(&conn) {
+                anyhow::bail!("数据库密钥不正确: {}", db_file);
+            }
+            *DB_KEY.lock().unwrap() = Some(key);
+            Ok(conn)
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn open_connection(db_file: &str) -> Result<Connection> {
+    let conn = Connection::open(db_file)
+        .with_context(|| format!("无法打开数据库: {}", db_file))?;
+    if !readable(&conn) {
+        anyhow::bail!(
+            "无法读取数据库: {}，如果已加密，需要使用 `cargo build --features sqlcipher` 编译",
+            db_file
+        );
+    }
+    Ok(conn)
+}
+
+#[cfg(feature = "sqlcipher")]
+fn apply_key(conn: &Connection, key: &str) -> Result<()> {
+    conn.pragma_update(None, "key", key)?;
+    Ok(())
+}
+
+/// 数据库文件是否已加密：不设置密钥时无法读取
+#[cfg(feature = "sqlcipher")]
+pub fn is_encrypted(path: &std::path::Path) -> Result<bool> {
+    let conn = Connection::open(path).with_context(|| format!("无法打开数据库: {}", path.display()))?;
+    Ok(!readable(&conn))
+}
+
+/// 用 `dst_key` 重新写出数据库，`None` 表示不加密，完成后替换原文件
+///
+/// 使用 sqlcipher_export 复制到同目录的临时文件再重命名，中途失败不影响原文件。
+#[cfg(feature = "sqlcipher")]
+pub fn convert(path: &std::path::Path, src_key: Option<&str>, dst_key: Option<&str>) -> Result<()> {
+    let conn = Connection::open(path).with_context(|| format!("无法打开数据库: {}", path.display()))?;
+    if let Some(key) = src_key {
+        apply_key(&conn, key)?;
+    }
+    if !readable(&conn) {
+        anyhow::bail!("无法读取数据库，密钥不正确: {}", path.display());
+    }
+
+    let tmp = path.with_extension("db.tmp");
+    if tmp.exists() {
+        std::fs::remove_file(&tmp)?;
+    }
+    let exported = (|| -> Result<()> {
+        conn.execute(
+            "ATTACH DATABASE ?1 AS target KEY ?2",
+            params![tmp.to_string_lossy(), dst_key.unwrap_or("")],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('target')", [], |_| Ok(()))?;
+        // sqlcipher_export 不复制 user_version
+        let user_version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        conn.pragma_update(Some(rusqlite::DatabaseName::Attached("target")), "user_version", user_version)?;
+        conn.execute("DETACH DATABASE target", [])?;
+        Ok(())
+    })();
+    drop(conn);
+    if let Err(e) = exported {
+        // 不留下写了一半的临时文件
+        std::fs::remove_file(&tmp).ok();
+        return Err(e.context(format!("无法转换数据库: {}", path.display())));
+    }
+
+    std::fs::rename(&tmp, path).with_context(|| format!("无法替换数据库: {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{ContentHead, Element, ImageElement, MentionElement, RoutingHead, TextElement, SCHEMA_VERSION};
+
+    fn message(seq: i64, body: Vec<Element>) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_time: 1700000000 + seq, msg_seq: seq, ..Default::default() },
+            routing_head: RoutingHead { from_uin: 10001, to_uin: 123456, ..Default::default() },
+            body,
+        }
+    }
+
+    #[test]
+    fn test_search_messages() {
+        let db = Database::new(":memory:").unwrap();
+        let text = |t: &str| Element::Text(TextElement { text: t.to_string() });
+        db.save_messages(&[
+            message(1, vec![text("他说\"好的\"\n然后走了 C:\\temp")]),
+            message(2, vec![Element::Mention(MentionElement { name: "张三".into(), ..Default::default() }), text(" 来开会")]),
+            message(3, vec![Element::Image(ImageElement::default())]),
+            message(4, vec![text("Hello World")]),
+            message(5, vec![text("100%_done")]),
+        ])
+        .unwrap();
+
+        let seqs = |keyword: &str| -> Vec<i64> {
+            db.search_messages(keyword, None, 10)
+                .unwrap()
+                .iter()
+                .map(|m| m.message.content_head.msg_seq)
+                .collect()
+        };
+        assert_eq!(seqs("@张三"), vec![2]);
+        assert_eq!(seqs("张三 来"), vec![2]);
+        assert_eq!(seqs("[图片]"), vec![3]);
+        assert_eq!(seqs("图片"), vec![3]);
+        assert_eq!(seqs("\"好的\""), vec![1]);
+        assert_eq!(seqs("\n然后"), vec![1]);
+        assert_eq!(seqs("c:\\"), vec![1]);
+        assert_eq!(seqs("hello"), vec![4]);
+        assert_eq!(seqs("%_"), vec![5]);
+        assert_eq!(seqs("0%_d"), vec![5]);
+        assert!(seqs("0_d").is_empty());
+        assert!(seqs("text").is_empty());
+
+        // 游标与数量限制
+        assert_eq!(db.search_messages("", None, 2).unwrap().len(), 2);
+        let cursor = Some((1700000000 + 4, 0));
+        let found = db.search_messages("", cursor, 10).unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|m| m.message.content_head.msg_seq < 4));
+    }
+
+    #[test]
+    fn test_raw_column() {
+        let db = Database::new(":memory:").unwrap();
+        let image = |raw: &str| Element::Image(ImageElement { raw: raw.to_string(), ..Default::default() });
+        db.save_message(&message(1, vec![
+            image("CAE="),
+            Element::Reply(crate::message::ReplyElement { content: vec![image(""), image("CAI=")], ..Default::default() }),
+        ]))
+        .unwrap();
+        db.save_message(&message(2, vec![Element::Text(TextElement { text: "hi".into() })])).unwrap();
+
+        // raw不出现在消息JSON中
+        let stored = db.get_message(1).unwrap().unwrap();
+        assert!(!serde_json::to_string(&stored).unwrap().contains("raw"));
+        let raw = db.get_raw(1).unwrap().unwrap();
+        assert_eq!(raw.into_iter().collect::<Vec<_>>(), [
+            ("body.0.raw".to_string(), "CAE=".to_string()),
+            ("body.1.content.1.raw".to_string(), "CAI=".to_string()),
+        ]);
+        assert!(db.get_raw(2).unwrap().unwrap().is_empty());
+        assert!(db.get_raw(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_body() {
+        let db = Database::new(":memory:").unwrap();
+        db.save_message(&message(1, vec![Element::Text(TextElement { text: "hi".into() })])).unwrap();
+        db.conn.execute("UPDATE messages SET body = '[{\"type\":\"text\",\"text\":1}]'", []).unwrap();
+        let err = db.get_message(1).unwrap_err();
+        assert!(format!("{:#}", err).contains("消息 1 的body格式错误"), "{:#}", err);
+        assert!(db.for_each_message(None, None, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_move_raw_out_of_body() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-raw-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("old.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE messages (id INTEGER PRIMARY KEY AUTOINCREMENT, from_uin INTEGER NOT NULL,
+                to_uin INTEGER NOT NULL, from_uid TEXT NOT NULL, to_uid TEXT NOT NULL, msg_seq INTEGER NOT NULL,
+                msg_uid TEXT NOT NULL, random INTEGER NOT NULL, client_seq INTEGER NOT NULL, msg_time INTEGER NOT NULL,
+                body TEXT NOT NULL, body_version INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP, UNIQUE(msg_seq));
+            INSERT INTO messages (from_uin, to_uin, from_uid, to_uid, msg_seq, msg_uid, random, client_seq, msg_time, body, body_version)
+            VALUES (1, 2, '', '', 1, '', 0, 0, 1700000000,
+                '[{"type":"reply","seq":0,"source":[{"type":"voice","duration":3,"raw":"CAM="}],"content":[]},{"type":"image","url":"u","raw":"CAE="}]', 1);"#,
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::new(&path.to_string_lossy()).unwrap();
+        let raw = db.get_raw(1).unwrap().unwrap();
+        assert_eq!(raw["body.0.source.0.raw"], "CAM=");
+        assert_eq!(raw["body.1.raw"], "CAE=");
+        let body: String = db.conn.query_row("SELECT body FROM messages WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert!(!body.contains("raw"));
+        match &db.get_message(1).unwrap().unwrap().message.body[1] {
+            Element::Image(image) => assert_eq!(image.url, "u"),
+            other => panic!("{:?}", other),
+        }
+        drop(db);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    fn rows(conn: &Connection) -> (i64, i64) {
+        let user_version = conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        let count = conn.query_row("SELECT count(*) FROM t", [], |row| row.get(0)).unwrap();
+        (user_version, count)
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_convert() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-cipher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE t (a INTEGER); INSERT INTO t VALUES (1), (2); PRAGMA user_version = 3;")
+            .unwrap();
+        drop(conn);
+
+        // 加密后用 DB_KEY_ENV 打开
+        convert(&path, None, Some("first")).unwrap();
+        assert!(is_encrypted(&path).unwrap());
+        std::env::set_var(DB_KEY_ENV, "first");
+        let conn = open_connection(&path.to_string_lossy()).unwrap();
+        std::env::remove_var(DB_KEY_ENV);
+        assert_eq!(rows(&conn), (3, 2));
+        drop(conn);
+
+        // 更换密钥后旧密钥不可用
+        assert!(convert(&path, Some("wrong"), Some("second")).is_err());
+        convert(&path, Some("first"), Some("second")).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        apply_key(&conn, "first").unwrap();
+        assert!(!readable(&conn));
+        let conn = Connection::open(&path).unwrap();
+        apply_key(&conn, "second").unwrap();
+        assert_eq!(rows(&conn), (3, 2));
+        drop(conn);
+
+        // 解密
+        convert(&path, Some("second"), None).unwrap();
+        assert!(!is_encrypted(&path).unwrap());
+        assert_eq!(rows(&Connection::open(&path).unwrap()), (3, 2));
+        assert!(!path.with_extension("db.tmp").exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,150 @@
+use serde_json::{json, Value};
+use std::io::Read;
+use crate::elem::ParserInterface;
+use crate::message::{CustomElement, Element};
+use crate::onebot::{CUSTOM_FORWARD, CUSTOM_JSON, CUSTOM_XML};
+use base64::{Engine as _, engine::general_purpose};
+use flate2::read::ZlibDecoder;
+
+/// 合并转发的rich_msg服务号
+const SERVICE_ID_FORWARD: u64 = 35;
+/// 合并转发的小程序卡片app名
+const APP_FORWARD: &str = "com.tencent.multimsg";
+
+/// 解码卡片内容：首字节为1时其余部分经zlib压缩，为0时为原文
+fn decode_card(data: &Value, key: &str) -> Option<String> {
+    let bytes = general_purpose::STANDARD.decode(data.get(key)?.as_str()?).ok()?;
+    let (&flag, rest) = bytes.split_first()?;
+    let mut out = String::new();
+    match flag {
+        0 => out = String::from_utf8(rest.to_vec()).ok()?,
+        1 => {
+            ZlibDecoder::new(rest).read_to_string(&mut out).ok()?;
+        }
+        _ => return None,
+    }
+    Some(out).filter(|s| !s.is_empty())
+}
+
+/// 元素自带的 bytes_msg_resid
+fn msg_resid(data: &Value) -> Option<String> {
+    let bytes = general_purpose::STANDARD.decode(data.get("bytes_msg_resid")?.as_str()?).ok()?;
+    String::from_utf8(bytes).ok().filter(|s| !s.is_empty())
+}
+
+fn forward(res_id: String) -> Element {
+    Element::Custom(CustomElement { kind: CUSTOM_FORWARD.to_string(), data: json!({ "res_id": res_id }) })
+}
+
+/// 小程序卡片解析器（`light_app` 元素）
+///
+/// `com.tencent.multimsg` 卡片解析为合并转发，其他卡片保留JSON原文。
+pub struct LightAppElem;
+
+impl ParserInterface for LightAppElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let content = decode_card(data, "bytes_data")?;
+        let card: Value = serde_json::from_str(&content).unwrap_or(Value::Null);
+        if card.get("app").and_then(Value::as_str) == Some(APP_FORWARD) {
+            let res_id = card
+                .pointer("/meta/detail/resid")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .filter(|s| !s.is_empty())
+                .or_else(|| msg_resid(data));
+            if let Some(res_id) = res_id {
+                return Some(forward(res_id));
+            }
+        }
+        Some(Element::Custom(CustomElement { kind: CUSTOM_JSON.to_string(), data: json!({ "data": content }) }))
+    }
+}
+
+/// 富文本卡片解析器（`rich_msg` 元素）
+///
+/// 服务号35为合并转发，res_id取自XML的 `m_resid` 属性；内容为JSON时按JSON卡片保存，其余保留XML原文。
+pub struct RichMsgElem;
+
+impl ParserInterface for RichMsgElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let content = decode_card(data, "bytes_template_1")?;
+        let service_id = data.get("uint32_service_id").and_then(Value::as_u64).unwrap_or(0);
+        if service_id == SERVICE_ID_FORWARD {
+            if let Some(res_id) = xml_attr(&content, "m_resid").or_else(|| msg_resid(data)) {
+                return Some(forward(res_id));
+            }
+        }
+        let kind = if content.trim_start().starts_with('{') { CUSTOM_JSON } else { CUSTOM_XML };
+        Some(Element::Custom(CustomElement { kind: kind.to_string(), data: json!({ "data": content }) }))
+    }
+}
+
+/// 读取XML中第一个名为 `name` 的属性值
+fn xml_attr(xml: &str, name: &str) -> Option<String> {
+    let pattern = format!("{}=\"", name);
+    let start = xml
+        .match_indices(&pattern)
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || xml.as_bytes()[i - 1].is_ascii_whitespace())?
+        + pattern.len();
+    let end = xml[start..].find('"')?;
+    Some(xml[start..start + end].to_string()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::parser::ElemParser;
+    use crate::onebot::Segment;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn compressed(content: &str) -> String {
+        let mut encoder = ZlibEncoder::new(vec![1], Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        general_purpose::STANDARD.encode(encoder.finish().unwrap())
+    }
+
+    fn plain(content: &str) -> String {
+        general_purpose::STANDARD.encode([&[0], content.as_bytes()].concat())
+    }
+
+    #[test]
+    fn test_cards_to_segments() {
+        let forward_xml = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?><msg serviceID="35" templateID="1" action="viewMultiMsg" brief="[聊天记录]" m_resid="xml-resid" m_fileName="f" tSum="3" flag="3"><item layout="1"><title>群聊的聊天记录</title></item></msg>"#;
+        let forward_json = r#"{"app":"com.tencent.multimsg","meta":{"detail":{"resid":"json-resid","summary":"查看3条转发消息"}}}"#;
+        let card = r#"{"app":"com.tencent.miniapp_01","prompt":"[QQ小程序]","meta":{}}"#;
+        let share_xml = r#"<?xml version='1.0' encoding='UTF-8' ?><msg serviceID="1" brief="[分享]"><item/></msg>"#;
+        let elems = vec![
+            json!({"text": {"str": general_purpose::STANDARD.encode("看看")}}),
+            json!({"rich_msg": {"bytes_template_1": compressed(forward_xml), "uint32_service_id": 35}}),
+            json!({"light_app": {"bytes_data": compressed(forward_json)}}),
+            json!({"light_app": {"bytes_data": plain(card)}}),
+            json!({"rich_msg": {"bytes_template_1": plain(share_xml), "uint32_service_id": 1}}),
+            json!({"light_app": {"bytes_data": general_purpose::STANDARD.encode([2, 0])}}),
+        ];
+
+        let body = ElemParser::new(elems).build();
+        assert_eq!(body.len(), 5);
+        assert_eq!(body[4], Element::Custom(CustomElement {
+            kind: CUSTOM_XML.to_string(),
+            data: json!({ "data": share_xml }),
+        }));
+
+        let segments = Segment::from_elements(&body, &mut |_| None);
+        assert_eq!(segments[..4], [
+            Segment::Text { text: "看看".to_string() },
+            Segment::Forward { id: "xml-resid".to_string() },
+            Segment::Forward { id: "json-resid".to_string() },
+            Segment::Json { data: card.to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_xml_attr() {
+        assert_eq!(xml_attr(r#"<msg m_resid="a" resid="b">"#, "resid").as_deref(), Some("b"));
+        assert_eq!(xml_attr(r#"<msg m_resid="">"#, "m_resid"), None);
+        assert_eq!(xml_attr("<msg>", "m_resid"), None);
+    }
+}
@@ -1,72 +1,51 @@
-use serde_json::{json, Value};
-use crate::elem::ParserInterface;
-use crate::protobuf::Protobuf;
-use base64::{Engine as _, engine::general_purpose};
+use serde_json::Value;
+use crate::elem::{decode_pb_elem, download_url, msg_info_rkey, pb_file_info, raw_pb_elem, schemas, ParserInterface};
+use crate::message::{Element, ImageDownload, ImageElement};
+use crate::protobuf::view::SchemaView;
 
 /// 图片消息解析器
 pub struct ImageElem;
 
 impl ParserInterface for ImageElem {
-    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Value> {
-        let pb_elem = data.get("bytes_pb_elem")?.as_str()?;
-        let pb_bytes = general_purpose::STANDARD.decode(pb_elem).ok()?;
-        let image_body = Protobuf::deserialize(&pb_bytes).ok()?;
-        let image_body = serde_json::to_value(&image_body).ok()?;
-
-        let file_info = Self::extract_file_info(&image_body)?;
-        let download_info = Self::extract_download_info(&image_body)?;
-        let url = Self::build_image_url(&download_info);
-
-        Some(json!({
-            "type": "image",
-            "image": {
-                "url": url,
-                "richmedia": {
-                    "file_info": file_info,
-                    "file_uuid": image_body.get("1")?.get("1")?.get("2").unwrap_or(&json!("")),
-                    "download_info": download_info
-                },
-                "upload_time": image_body.get("1")?.get("1")?.get("4").unwrap_or(&json!("")),
-            }
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let pb_bytes = decode_pb_elem(data)?;
+        let msg_info = SchemaView::new(&pb_bytes, &schemas::MSG_INFO);
+        let body = msg_info.messages("bodies").next()?;
+        let index = body.message("index")?;
+
+        let download = Self::extract_download_info(&msg_info, &body.message("picture")?);
+        let url = Self::build_image_url(&download);
+
+        Some(Element::Image(ImageElement {
+            url,
+            file: pb_file_info(&index.message("info")?),
+            file_uuid: index.string("file_uuid").unwrap_or("").to_string(),
+            upload_time: index.uint("upload_time").unwrap_or(0) as i64,
+            download,
+            raw: raw_pb_elem(data).to_string(),
         }))
     }
 }
 
 impl ImageElem {
-    fn extract_file_info(image_body: &Value) -> Option<Value> {
-        let file_info = image_body.get("1")?.get("1")?.get("1")?;
-
-        Some(json!({
-            "size": file_info.get("1").unwrap_or(&json!(0)),
-            "md5": file_info.get("2").unwrap_or(&json!("")),
-            "sha1": file_info.get("3").unwrap_or(&json!("")),
-            "file_name": file_info.get("4").unwrap_or(&json!("")),
-            "width": file_info.get("6").unwrap_or(&json!(0)),
-            "height": file_info.get("7").unwrap_or(&json!(0)),
-        }))
-    }
-
-    fn extract_download_info(image_body: &Value) -> Option<Value> {
-        let download_req = image_body.get("1")?.get("2")?;
-
-        Some(json!({
-            "pic_url_ext_info": {
-                "original_parameter": download_req.get("2")?.get("1").unwrap_or(&json!(null)),
-                "big_parameter": download_req.get("2")?.get("2").unwrap_or(&json!(null)),
-                "thumb_parameter": download_req.get("2")?.get("3").unwrap_or(&json!(null)),
-            },
-            "domain": download_req.get("3").unwrap_or(&json!("")),
-            "url_path": download_req.get("1").unwrap_or(&json!("")),
-            "rkey": image_body.get("2")?.get("1")?.get("11")?.get("30").unwrap_or(&json!("")),
-        }))
+    fn extract_download_info(msg_info: &SchemaView, picture: &SchemaView) -> ImageDownload {
+        let ext = picture.message("ext");
+        let ext_str = |name: &str| {
+            ext.and_then(|e| e.string(name))
+                .unwrap_or("")
+                .to_string()
+        };
+        ImageDownload {
+            domain: picture.string("domain").unwrap_or("").to_string(),
+            url_path: picture.string("url_path").unwrap_or("").to_string(),
+            rkey: msg_info_rkey(msg_info).to_string(),
+            original_parameter: ext_str("original_parameter"),
+            big_parameter: ext_str("big_parameter"),
+            thumb_parameter: ext_str("thumb_parameter"),
+        }
     }
 
-    fn build_image_url(download_info: &Value) -> String {
-        let domain = download_info.get("domain").and_then(|v| v.as_str()).unwrap_or("");
-        let url_path = download_info.get("url_path").and_then(|v| v.as_str()).unwrap_or("");
-        let rkey = download_info.get("rkey").and_then(|v| v.as_str()).unwrap_or("");
-
-        format!("https://{}{}{}", domain, url_path, rkey)
+    fn build_image_url(download: &ImageDownload) -> String {
+        download_url(&download.domain, &download.url_path, &download.rkey)
     }
 }
-
@@ -1,15 +1,85 @@
 pub mod parser;
 pub mod text_elem;
+pub mod face_elem;
 pub mod image_elem;
 pub mod video_elem;
 pub mod voice_elem;
 pub mod common_elem;
 pub mod reply_elem;
+pub mod card_elem;
+pub mod registry;
+pub mod schemas;
 
 use serde_json::Value;
+use crate::message::{value_to_i64, Element, FileInfo};
+use crate::protobuf::view::SchemaView;
+use base64::{Engine as _, engine::general_purpose};
 
 /// 解析器接口trait
 pub trait ParserInterface {
-    fn parse(&self, data: &Value, full_elem: Option<&Value>) -> Option<Value>;
+    fn parse(&self, data: &Value, full_elem: Option<&Value>) -> Option<Element>;
 }
 
+/// 读取protobuf解码结果中的字符串字段
+pub(crate) fn pb_str(v: &Value, key: &str) -> String {
+    v.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string()
+}
+
+/// 读取protobuf解码结果中的整数字段
+pub(crate) fn pb_int(v: &Value, key: &str) -> i64 {
+    v.get(key).map(value_to_i64).unwrap_or(0)
+}
+
+/// 读取 common_elem 的 bytes_pb_elem，按 [`schemas::MSG_INFO`] 访问
+pub(crate) fn decode_pb_elem(data: &Value) -> Option<Vec<u8>> {
+    general_purpose::STANDARD.decode(raw_pb_elem(data)).ok()
+}
+
+/// common_elem 的 bytes_pb_elem 原文（base64）
+pub(crate) fn raw_pb_elem(data: &Value) -> &str {
+    data.get("bytes_pb_elem").and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// MSG_INFO 中的rkey：私聊在 c2c_reserve 中，群聊在 troop_reserve 中
+pub(crate) fn msg_info_rkey<'a>(msg_info: &SchemaView<'a>) -> &'a str {
+    msg_info
+        .message("ext_biz_info")
+        .and_then(|v| v.message("pic"))
+        .and_then(|p| p.message("c2c_reserve").or_else(|| p.message("troop_reserve")))
+        .and_then(|r| r.string("rkey"))
+        .unwrap_or("")
+}
+
+/// 拼接下载链接 `https://{domain}{url_path}{rkey}`，缺少域名或路径时为空字符串
+pub(crate) fn download_url(domain: &str, url_path: &str, rkey: &str) -> String {
+    if domain.is_empty() || url_path.is_empty() {
+        return String::new();
+    }
+    format!("https://{}{}{}", domain, url_path, rkey)
+}
+
+/// MSG_INFO 中一个body的下载链接，按其 `picture` 中的域名和路径拼接
+pub(crate) fn body_url(msg_info: &SchemaView, body: &SchemaView) -> String {
+    let Some(picture) = body.message("picture") else {
+        return String::new();
+    };
+    download_url(
+        picture.string("domain").unwrap_or(""),
+        picture.string("url_path").unwrap_or(""),
+        msg_info_rkey(msg_info),
+    )
+}
+
+/// 从 [`schemas::FILE_INFO`] 视图中提取文件信息
+pub(crate) fn pb_file_info(v: &SchemaView) -> FileInfo {
+    FileInfo {
+        size: v.uint("size").unwrap_or(0),
+        md5: v.string("md5").unwrap_or("").to_string(),
+        sha1: v.string("sha1").unwrap_or("").to_string(),
+        file_name: v.string("file_name").unwrap_or("").to_string(),
+        width: v.uint("width").unwrap_or(0) as u32,
+        height: v.uint("height").unwrap_or(0) as u32,
+        duration: v.uint("duration").unwrap_or(0) as u32,
+        format: v.uint("format").unwrap_or(0) as u32,
+    }
+}
@@ -1,87 +1,65 @@
 use serde_json::Value;
-use crate::elem::ParserInterface;
-use crate::elem::text_elem::TextElem;
-use crate::elem::common_elem::CommonElem;
-use crate::elem::reply_elem::ReplyElem;
-// use base64::{Engine as _, engine::general_purpose};
+use std::sync::Arc;
+use crate::elem::registry::{ParserRegistry, ELEM_SRC_MSG};
+use crate::message::Element;
 
 /// ELEM解析器主类
 pub struct ElemParser {
     elems: Vec<Value>,
+    registry: Arc<ParserRegistry>,
 }
 
 impl ElemParser {
-    /// 创建新的解析器
+    /// 使用内置解析器创建新的解析器
     pub fn new(elems: Vec<Value>) -> Self {
-        ElemParser { elems }
+        Self::with_registry(elems, ParserRegistry::builtin())
+    }
+
+    /// 使用指定的解析器注册表创建新的解析器
+    pub fn with_registry(elems: Vec<Value>, registry: Arc<ParserRegistry>) -> Self {
+        ElemParser { elems, registry }
     }
 
     /// 构建消息数组
-    pub fn build(&self) -> Vec<Value> {
+    pub fn build(&self) -> Vec<Element> {
         let mut arrays = Vec::new();
 
-        let text_parser = TextElem;
-        let common_parser = CommonElem::new();
-        let reply_parser = ReplyElem;
-
         // 检查是否是回复消息
-        let has_src_msg = self.elems.iter().any(|elem| elem.get("src_msg").is_some());
+        let has_src_msg = self.elems.iter().any(|elem| elem.get(ELEM_SRC_MSG).is_some());
 
         if has_src_msg {
             // 处理回复消息
             let mut reply_content = Vec::new();
-            let mut reply_to_content = None;
             let mut reply_info = None;
 
             for elem in &self.elems {
-                if let Some(src_msg) = elem.get("src_msg") {
+                if let Some(src_msg) = elem.get(ELEM_SRC_MSG) {
                     // 解析被回复的消息
-                    if let Some(parsed) = reply_parser.parse(src_msg, Some(elem)) {
-                        reply_info = Some(parsed);
-                    }
-                    // 获取被回复的消息内容
-                    if let Some(elems) = src_msg.get("elems").and_then(|v| v.as_array()) {
-                        let parser = ElemParser::new(elems.clone());
-                        reply_to_content = Some(parser.build());
+                    if let Some(Element::Reply(mut reply)) =
+                        self.registry.parse_key(ELEM_SRC_MSG, src_msg, elem)
+                    {
+                        // 被回复的消息内容使用同一注册表解析
+                        if let Some(elems) = src_msg.get("elems").and_then(|v| v.as_array()) {
+                            reply.source = Self::with_registry(elems.clone(), self.registry.clone()).build();
+                        }
+                        reply_info = Some(reply);
                     }
-                } else if let Some(text_data) = elem.get("text") {
+                } else if let Some(parsed) = self.registry.parse(elem) {
                     // 当前回复的内容
-                    if let Some(parsed) = text_parser.parse(text_data, Some(elem)) {
-                        reply_content.push(parsed);
-                    }
+                    reply_content.push(parsed);
                 }
             }
 
             // 组合回复消息
-            if let Some(mut info) = reply_info {
-                if let Some(reply_obj) = info.get_mut("reply") {
-                    if let Some(obj) = reply_obj.as_object_mut() {
-                        obj.insert("reply_msg".to_string(), serde_json::json!(reply_content));
-                        if let Some(to_content) = reply_to_content {
-                            obj.insert("reply_to".to_string(), serde_json::json!(to_content));
-                        }
-                    }
-                }
-                arrays.push(info);
+            if let Some(mut reply) = reply_info {
+                reply.content = reply_content;
+                arrays.push(Element::Reply(reply));
             }
         } else {
             // 普通消息处理
-            for elem in &self.elems {
-                let parsed = if let Some(text_data) = elem.get("text") {
-                    text_parser.parse(text_data, Some(elem))
-                } else if let Some(common_data) = elem.get("common_elem") {
-                    common_parser.parse(common_data, Some(elem))
-                } else {
-                    None
-                };
-
-                if let Some(p) = parsed {
-                    arrays.push(p);
-                }
-            }
+            arrays.extend(self.elems.iter().filter_map(|elem| self.registry.parse(elem)));
         }
 
         arrays
     }
 }
-
@@ -0,0 +1,158 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use crate::elem::ParserInterface;
+use crate::elem::common_elem::{
+    CommonElem, BUSINESS_TYPE_FACE, BUSINESS_TYPE_IMAGE, BUSINESS_TYPE_VIDEO, BUSINESS_TYPE_VOICE,
+    SERVICE_TYPE_FACE, SERVICE_TYPE_RICH_MEDIA, SERVICE_TYPE_SUPER_FACE,
+};
+use crate::elem::card_elem::{LightAppElem, RichMsgElem};
+use crate::elem::face_elem::{FaceElem, FaceExtraElem};
+use crate::elem::image_elem::ImageElem;
+use crate::elem::reply_elem::ReplyElem;
+use crate::elem::text_elem::TextElem;
+use crate::elem::video_elem::VideoElem;
+use crate::elem::voice_elem::VoiceElem;
+use crate::message::Element;
+
+/// 文本元素键
+pub const ELEM_TEXT: &str = "text";
+/// 系统表情元素键
+pub const ELEM_FACE: &str = "face";
+/// 通用元素键，按 (service_type, business_type) 二次分发
+pub const ELEM_COMMON: &str = "common_elem";
+/// 回复元素键
+pub const ELEM_SRC_MSG: &str = "src_msg";
+/// 小程序卡片元素键
+pub const ELEM_LIGHT_APP: &str = "light_app";
+/// 富文本（XML）卡片元素键
+pub const ELEM_RICH_MSG: &str = "rich_msg";
+
+/// 共享的解析器
+pub type SharedParser = Arc<dyn ParserInterface + Send + Sync>;
+
+/// 元素解析器注册表
+///
+/// 解析器按elem键（如 `text`、`src_msg`）注册，`common_elem` 额外按
+/// (service_type, business_type) 注册。重复注册会覆盖已有的解析器，
+/// 因此可以在内置注册表的基础上替换或扩展：
+///
+/// ```ignore
+/// let mut registry = ParserRegistry::with_builtins();
+/// registry.register("custom_card", Arc::new(MyCardElem));
+/// registry.register_common(48, 21, Arc::new(MyFileElem));
+/// let parser = ElemParser::with_registry(elems, Arc::new(registry));
+/// ```
+#[derive(Clone, Default)]
+pub struct ParserRegistry {
+    elem_parsers: HashMap<String, SharedParser>,
+    common_parsers: HashMap<(u32, u32), SharedParser>,
+}
+
+impl ParserRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建包含内置解析器的注册表
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(ELEM_TEXT, Arc::new(TextElem));
+        registry.register(ELEM_FACE, Arc::new(FaceElem));
+        registry.register(ELEM_SRC_MSG, Arc::new(ReplyElem));
+        registry.register(ELEM_LIGHT_APP, Arc::new(LightAppElem));
+        registry.register(ELEM_RICH_MSG, Arc::new(RichMsgElem));
+        registry.register_common(SERVICE_TYPE_FACE, BUSINESS_TYPE_FACE, Arc::new(FaceExtraElem));
+        registry.register_common(SERVICE_TYPE_SUPER_FACE, BUSINESS_TYPE_FACE, Arc::new(FaceExtraElem));
+        registry.register_common(SERVICE_TYPE_RICH_MEDIA, BUSINESS_TYPE_IMAGE, Arc::new(ImageElem));
+        registry.register_common(SERVICE_TYPE_RICH_MEDIA, BUSINESS_TYPE_VOICE, Arc::new(VoiceElem));
+        registry.register_common(SERVICE_TYPE_RICH_MEDIA, BUSINESS_TYPE_VIDEO, Arc::new(VideoElem));
+        registry
+    }
+
+    /// 全局共享的内置注册表
+    pub fn builtin() -> Arc<ParserRegistry> {
+        static BUILTIN: OnceLock<Arc<ParserRegistry>> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| Arc::new(Self::with_builtins()))
+            .clone()
+    }
+
+    /// 按elem键注册解析器，返回被覆盖的解析器
+    pub fn register(&mut self, key: &str, parser: SharedParser) -> Option<SharedParser> {
+        self.elem_parsers.insert(key.to_string(), parser)
+    }
+
+    /// 是否已按elem键注册解析器
+    pub fn contains(&self, key: &str) -> bool {
+        self.elem_parsers.contains_key(key)
+    }
+
+    /// 按 (service_type, business_type) 注册 `common_elem` 解析器，返回被覆盖的解析器
+    pub fn register_common(
+        &mut self,
+        service_type: u32,
+        business_type: u32,
+        parser: SharedParser,
+    ) -> Option<SharedParser> {
+        self.common_parsers.insert((service_type, business_type), parser)
+    }
+
+    /// 解析单个elem
+    ///
+    /// 依次尝试elem中的每个键，返回第一个解析成功的结果。
+    pub fn parse(&self, elem: &Value) -> Option<Element> {
+        let obj = elem.as_object()?;
+        obj.iter()
+            .find_map(|(key, data)| self.parse_key(key, data, elem))
+    }
+
+    /// 使用指定键的解析器解析
+    pub fn parse_key(&self, key: &str, data: &Value, full_elem: &Value) -> Option<Element> {
+        if key == ELEM_COMMON {
+            let types = CommonElem::types(data)?;
+            return self.common_parsers.get(&types)?.parse(data, Some(full_elem));
+        }
+        self.elem_parsers.get(key)?.parse(data, Some(full_elem))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::TextElement;
+    use serde_json::json;
+
+    struct FixedElem(&'static str);
+
+    impl ParserInterface for FixedElem {
+        fn parse(&self, _data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+            Some(Element::Text(TextElement { text: self.0.to_string() }))
+        }
+    }
+
+    #[test]
+    fn test_register_and_override() {
+        let mut registry = ParserRegistry::with_builtins();
+        let text = json!({"text": {"str": "aGk="}});
+        assert_eq!(
+            registry.parse(&text),
+            Some(Element::Text(TextElement { text: "hi".to_string() }))
+        );
+
+        assert!(registry.register(ELEM_TEXT, Arc::new(FixedElem("override"))).is_some());
+        assert_eq!(
+            registry.parse(&text),
+            Some(Element::Text(TextElement { text: "override".to_string() }))
+        );
+
+        let card = json!({"common_elem": {"uint32_service_type": 99, "uint32_business_type": 1}});
+        assert_eq!(registry.parse(&card), None);
+        registry.register_common(99, 1, Arc::new(FixedElem("card")));
+        assert_eq!(
+            registry.parse(&card),
+            Some(Element::Text(TextElement { text: "card".to_string() }))
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! 元素中内嵌的protobuf结构描述
+
+use crate::protobuf::schema::{Field, FieldKind, Schema};
+
+/// 富媒体文件信息
+pub static FILE_INFO: Schema = Schema {
+    name: "FileInfo",
+    fields: &[
+        Field::optional(1, "size", FieldKind::Int),
+        Field::optional(2, "md5", FieldKind::String),
+        Field::optional(3, "sha1", FieldKind::String),
+        Field::optional(4, "file_name", FieldKind::String),
+        Field::optional(5, "type", FieldKind::Message(&FILE_TYPE)),
+        Field::optional(6, "width", FieldKind::Int),
+        Field::optional(7, "height", FieldKind::Int),
+        Field::optional(8, "duration", FieldKind::Int),
+        Field::optional(9, "format", FieldKind::Int),
+    ],
+};
+
+/// 富媒体文件类型
+pub static FILE_TYPE: Schema = Schema {
+    name: "FileType",
+    fields: &[
+        Field::optional(1, "type", FieldKind::Int),
+        Field::optional(2, "pic_format", FieldKind::Int),
+        Field::optional(3, "video_format", FieldKind::Int),
+        Field::optional(4, "voice_format", FieldKind::Int),
+    ],
+};
+
+/// 富媒体索引节点
+pub static INDEX_NODE: Schema = Schema {
+    name: "IndexNode",
+    fields: &[
+        Field::optional(1, "info", FieldKind::Message(&FILE_INFO)),
+        Field::optional(2, "file_uuid", FieldKind::String),
+        Field::optional(3, "store_id", FieldKind::Int),
+        Field::optional(4, "upload_time", FieldKind::Int),
+        Field::optional(5, "ttl", FieldKind::Int),
+        Field::optional(6, "sub_type", FieldKind::Int),
+    ],
+};
+
+/// 图片URL参数
+pub static PIC_URL_EXT_INFO: Schema = Schema {
+    name: "PicUrlExtInfo",
+    fields: &[
+        Field::optional(1, "original_parameter", FieldKind::String),
+        Field::optional(2, "big_parameter", FieldKind::String),
+        Field::optional(3, "thumb_parameter", FieldKind::String),
+    ],
+};
+
+/// 图片下载信息
+pub static PICTURE_INFO: Schema = Schema {
+    name: "PictureInfo",
+    fields: &[
+        Field::optional(1, "url_path", FieldKind::String),
+        Field::optional(2, "ext", FieldKind::Message(&PIC_URL_EXT_INFO)),
+        Field::optional(3, "domain", FieldKind::String),
+    ],
+};
+
+/// 单个富媒体文件
+pub static MSG_INFO_BODY: Schema = Schema {
+    name: "MsgInfoBody",
+    fields: &[
+        Field::optional(1, "index", FieldKind::Message(&INDEX_NODE)),
+        Field::optional(2, "picture", FieldKind::Message(&PICTURE_INFO)),
+        Field::optional(5, "file_exist", FieldKind::Bool),
+    ],
+};
+
+/// 图片下载参数（bytes_pb_reserve_c2c/troop）
+pub static PIC_RESERVE: Schema = Schema {
+    name: "PicReserve",
+    fields: &[Field::optional(30, "rkey", FieldKind::String)],
+};
+
+/// 图片业务扩展信息
+pub static PIC_EXT_BIZ_INFO: Schema = Schema {
+    name: "PicExtBizInfo",
+    fields: &[
+        Field::optional(1, "biz_type", FieldKind::Int),
+        Field::optional(2, "text_summary", FieldKind::String),
+        Field::optional(11, "c2c_reserve", FieldKind::Message(&PIC_RESERVE)),
+        Field::optional(12, "troop_reserve", FieldKind::Message(&PIC_RESERVE)),
+    ],
+};
+
+/// 富媒体业务扩展信息
+pub static EXT_BIZ_INFO: Schema = Schema {
+    name: "ExtBizInfo",
+    fields: &[
+        Field::optional(1, "pic", FieldKind::Message(&PIC_EXT_BIZ_INFO)),
+        Field::optional(10, "busi_type", FieldKind::Int),
+    ],
+};
+
+/// common_elem（service_type=48）的 bytes_pb_elem
+///
+/// 图片、语音只有一个 `bodies`，视频的第二个 `bodies` 为缩略图。
+pub static MSG_INFO: Schema = Schema {
+    name: "MsgInfo",
+    fields: &[
+        Field::repeated(1, "bodies", FieldKind::Message(&MSG_INFO_BODY)),
+        Field::optional(2, "ext_biz_info", FieldKind::Message(&EXT_BIZ_INFO)),
+    ],
+};
+
+/// src_msg 的 bytes_pb_reserve
+pub static SRC_MSG_RESERVE: Schema = Schema {
+    name: "SrcMsgReserve",
+    fields: &[
+        Field::optional(6, "to_uid", FieldKind::String),
+        Field::optional(7, "from_uid", FieldKind::String),
+        Field::optional(8, "seq", FieldKind::Int),
+    ],
+};
+
+/// text 的 bytes_pb_reserve，@提及的信息
+pub static TEXT_RESERVE: Schema = Schema {
+    name: "TextResvAttr",
+    fields: &[
+        Field::optional(3, "at_type", FieldKind::Int),
+        Field::optional(4, "at_member_uin", FieldKind::Int),
+        Field::optional(5, "at_member_tinyid", FieldKind::Int),
+        Field::optional(9, "at_member_uid", FieldKind::String),
+    ],
+};
+
+/// 新版表情 common_elem 的 bytes_pb_elem
+pub static FACE_EXTRA: Schema = Schema {
+    name: "FaceExtra",
+    fields: &[
+        Field::optional(1, "face_id", FieldKind::Int),
+        Field::optional(2, "text", FieldKind::String),
+        Field::optional(3, "compat_text", FieldKind::String),
+    ],
+};
@@ -1,12 +1,16 @@
-use serde_json::{json, Value};
-use crate::elem::ParserInterface;
+use serde_json::Value;
+use crate::elem::{schemas, ParserInterface};
+use crate::message::{Element, MentionElement, TextElement};
+use crate::protobuf::view::SchemaView;
 use base64::{Engine as _, engine::general_purpose};
 
 /// 文本消息解析器
+///
+/// 带有@信息的文本解析为 [`Element::Mention`]。
 pub struct TextElem;
 
 impl ParserInterface for TextElem {
-    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Value> {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
         let str_value = data.get("str")?.as_str()?;
         let content = general_purpose::STANDARD.decode(str_value).ok()?;
         let content_str = String::from_utf8(content).ok()?;
@@ -15,10 +19,49 @@ impl ParserInterface for TextElem {
             return None;
         }
 
-        Some(json!({
-            "type": "text",
-            "content": content_str
-        }))
+        if let Some(mention) = Self::parse_mention(data, &content_str) {
+            return Some(Element::Mention(mention));
+        }
+
+        Some(Element::Text(TextElement { text: content_str }))
     }
 }
 
+impl TextElem {
+    /// 解析@信息，NT版本在 bytes_pb_reserve 中，旧版本在 bytes_attr6_buf 中
+    fn parse_mention(data: &Value, text: &str) -> Option<MentionElement> {
+        let decode = |key: &str| {
+            data.get(key)
+                .and_then(|v| v.as_str())
+                .and_then(|s| general_purpose::STANDARD.decode(s).ok())
+                .filter(|b| !b.is_empty())
+        };
+        let name = text.strip_prefix('@').unwrap_or(text).to_string();
+
+        if let Some(reserve) = decode("bytes_pb_reserve") {
+            let view = SchemaView::new(&reserve, &schemas::TEXT_RESERVE);
+            let at_type = view.uint("at_type").unwrap_or(0);
+            if at_type != 0 {
+                return Some(MentionElement {
+                    uin: view.uint("at_member_uin").unwrap_or(0) as i64,
+                    uid: view.string("at_member_uid").unwrap_or("").to_string(),
+                    name,
+                    all: at_type == 1,
+                });
+            }
+        }
+
+        // 旧版格式：count(2) start(2) length(2) flag(1) uin(4) ...
+        let attr6 = decode("bytes_attr6_buf")?;
+        if attr6.len() < 11 {
+            return None;
+        }
+        let uin = u32::from_be_bytes([attr6[7], attr6[8], attr6[9], attr6[10]]) as i64;
+        Some(MentionElement {
+            uin,
+            uid: String::new(),
+            name,
+            all: attr6[6] == 1,
+        })
+    }
+}
@@ -1,91 +1,82 @@
-use serde_json::{json, Value};
-use crate::elem::ParserInterface;
-use crate::protobuf::Protobuf;
-use base64::{Engine as _, engine::general_purpose};
+use serde_json::Value;
+use crate::elem::{body_url, decode_pb_elem, pb_file_info, raw_pb_elem, schemas, ParserInterface};
+use crate::message::{Element, VideoElement, VideoThumb};
+use crate::protobuf::view::SchemaView;
 
 /// 视频消息解析器
 pub struct VideoElem;
 
 impl ParserInterface for VideoElem {
-    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Value> {
-        let pb_elem = data.get("bytes_pb_elem")?.as_str()?;
-        let pb_bytes = general_purpose::STANDARD.decode(pb_elem).ok()?;
-        let video_body = Protobuf::deserialize(&pb_bytes).ok()?;
-        let video_body = serde_json::to_value(&video_body).ok()?;
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let pb_bytes = decode_pb_elem(data)?;
+        let msg_info = SchemaView::new(&pb_bytes, &schemas::MSG_INFO);
+        let mut bodies = msg_info.messages("bodies");
 
-        let items = video_body.get("1")?.as_array()?;
-        if items.is_empty() {
-            return None;
-        }
+        let mut video = Self::extract_video_info(&msg_info, &bodies.next()?)?;
+        video.thumb = bodies.next().and_then(|b| Self::extract_thumb_info(&msg_info, &b));
+        video.raw = raw_pb_elem(data).to_string();
 
-        let video_info = Self::extract_video_info(items)?;
-        let thumb_info = Self::extract_thumb_info(items);
-
-        Some(json!({
-            "type": "video",
-            "video": {
-                "url": "",
-                "duration": video_info.get("file_info")?.get("duration").unwrap_or(&json!(0)),
-                "thumb_url": thumb_info.as_ref().and_then(|v| v.get("url")).unwrap_or(&json!("")),
-                "richmedia": {
-                    "video": video_info,
-                    "thumb": thumb_info,
-                }
-            }
-        }))
+        Some(Element::Video(video))
     }
 }
 
 impl VideoElem {
-    fn extract_video_info(items: &[Value]) -> Option<Value> {
-        let video_item = items.get(0)?;
-        let video_data = video_item.get("1")?;
-        let file_info = video_data.get("1")?;
+    fn extract_video_info(msg_info: &SchemaView, body: &SchemaView) -> Option<VideoElement> {
+        let index = body.message("index")?;
+        let file = pb_file_info(&index.message("info")?);
+
+        Some(VideoElement {
+            url: body_url(msg_info, body),
+            duration: file.duration,
+            file,
+            file_uuid: index.string("file_uuid").unwrap_or("").to_string(),
+            upload_time: index.uint("upload_time").unwrap_or(0) as i64,
+            thumb: None,
+            raw: String::new(),
+        })
+    }
 
-        let default_empty = json!("");
-        let default_zero = json!(0);
-        
-        let file_uuid = video_data.get("2").unwrap_or(&default_empty);
+    fn extract_thumb_info(msg_info: &SchemaView, body: &SchemaView) -> Option<VideoThumb> {
+        let index = body.message("index")?;
 
-        Some(json!({
-            "file_info": {
-                "size": file_info.get("1").unwrap_or(&default_zero),
-                "md5": file_info.get("2").unwrap_or(&default_empty),
-                "sha1": file_info.get("3").unwrap_or(&default_empty),
-                "file_name": file_info.get("4").unwrap_or(&default_empty),
-                "width": file_info.get("6").unwrap_or(&default_zero),
-                "height": file_info.get("7").unwrap_or(&default_zero),
-                "duration": file_info.get("8").unwrap_or(&default_zero),
-                "format": file_info.get("9").unwrap_or(&default_zero),
-            },
-            "file_uuid": file_uuid,
-            "upload_time": video_data.get("4").unwrap_or(&default_zero),
-        }))
+        Some(VideoThumb {
+            url: body_url(msg_info, body),
+            file: pb_file_info(&index.message("info")?),
+            file_uuid: index.string("file_uuid").unwrap_or("").to_string(),
+            upload_time: index.uint("upload_time").unwrap_or(0) as i64,
+        })
     }
+}
 
-    fn extract_thumb_info(items: &[Value]) -> Option<Value> {
-        let thumb_item = items.get(1)?;
-        let thumb_data = thumb_item.get("1")?;
-        let file_info = thumb_data.get("1")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protobuf::Protobuf;
+    use base64::{Engine as _, engine::general_purpose};
+    use serde_json::json;
 
-        let default_empty = json!("");
-        let default_zero = json!(0);
-        
-        let file_uuid = thumb_data.get("2").unwrap_or(&default_empty);
+    #[test]
+    fn test_thumb_url() {
+        let msg_info = json!({
+            "bodies": [
+                {"index": {"info": {"size": 1024, "duration": 12}, "file_uuid": "video"}},
+                {
+                    "index": {"info": {"width": 320, "height": 240}, "file_uuid": "thumb"},
+                    "picture": {"domain": "multimedia.nt.qq.com.cn", "url_path": "/download?appid=1415&fileid=thumb"},
+                },
+            ],
+            "ext_biz_info": {"pic": {"c2c_reserve": {"rkey": "&rkey=abc"}}},
+        });
+        let pb_elem = general_purpose::STANDARD.encode(Protobuf::serialize_with(&msg_info, &schemas::MSG_INFO).unwrap());
+        let data = json!({"uint32_service_type": 48, "uint32_business_type": 11, "bytes_pb_elem": pb_elem});
 
-        Some(json!({
-            "file_info": {
-                "size": file_info.get("1").unwrap_or(&default_zero),
-                "md5": file_info.get("2").unwrap_or(&default_empty),
-                "sha1": file_info.get("3").unwrap_or(&default_empty),
-                "file_name": file_info.get("4").unwrap_or(&default_empty),
-                "width": file_info.get("6").unwrap_or(&default_zero),
-                "height": file_info.get("7").unwrap_or(&default_zero),
-            },
-            "file_uuid": file_uuid,
-            "url": "",
-            "upload_time": thumb_data.get("4").unwrap_or(&default_zero),
-        }))
+        let Some(Element::Video(video)) = VideoElem.parse(&data, None) else {
+            panic!("not a video");
+        };
+        assert_eq!(video.url, "");
+        assert_eq!(video.duration, 12);
+        let thumb = video.thumb.unwrap();
+        assert_eq!(thumb.url, "https://multimedia.nt.qq.com.cn/download?appid=1415&fileid=thumb&rkey=abc");
+        assert_eq!(thumb.file.width, 320);
     }
 }
-
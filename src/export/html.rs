@@ -0,0 +1,364 @@
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use crate::database::{Database, StoredMessage};
+use crate::export::{media_file_name, ExportTimezone, MediaFetcher, SenderNames};
+use crate::message::Element;
+
+/// 页面样式
+const STYLE: &str = r#"
+body { margin: 0; font-family: -apple-system, "PingFang SC", "Microsoft YaHei", sans-serif; background: #f2f2f2; color: #222; }
+header { position: sticky; top: 0; background: #fff; border-bottom: 1px solid #ddd; padding: 8px 16px; display: flex; flex-wrap: wrap; gap: 12px; align-items: center; z-index: 1; }
+header h1 { font-size: 18px; margin: 0; flex: 1; }
+header input { padding: 4px 8px; font-size: 14px; }
+nav { text-align: center; padding: 8px; font-size: 14px; }
+nav a, nav span { margin: 0 4px; }
+main { max-width: 860px; margin: 0 auto; padding: 8px 16px; }
+.day { text-align: center; color: #888; font-size: 12px; margin: 16px 0 8px; }
+.msg { display: flex; flex-direction: column; align-items: flex-start; margin: 8px 0; }
+.msg.self { align-items: flex-end; }
+.meta { font-size: 12px; color: #888; margin-bottom: 2px; }
+.bubble { background: #fff; border-radius: 8px; padding: 8px 12px; max-width: 75%; word-break: break-word; white-space: pre-wrap; }
+.msg.self .bubble { background: #95ec69; }
+.msg:target .bubble { outline: 2px solid #f90; }
+.bubble img { max-width: 320px; max-height: 320px; border-radius: 4px; display: block; }
+.placeholder { color: #888; }
+.face { color: #c90; }
+.mention { color: #1e6fd9; }
+.reply { border-left: 3px solid #bbb; margin: 0 0 6px; padding: 2px 8px; color: #666; font-size: 13px; }
+.reply a { color: inherit; text-decoration: none; }
+.video { position: relative; display: inline-block; }
+.video span { position: absolute; left: 6px; bottom: 6px; color: #fff; background: rgba(0,0,0,.5); padding: 0 4px; border-radius: 3px; font-size: 12px; }
+.result { display: block; padding: 6px 0; border-bottom: 1px solid #ddd; color: inherit; text-decoration: none; }
+.result small { color: #888; }
+"#;
+
+/// 搜索页脚本，数据来自 search-index.js（file:// 下也可加载）
+const SEARCH_SCRIPT: &str = r#"
+const params = new URLSearchParams(location.search);
+const q = (params.get('q') || '').trim();
+const input = document.querySelector('header input');
+input.value = q;
+const main = document.querySelector('main');
+const status = document.createElement('p');
+main.appendChild(status);
+if (q) {
+  const needle = q.toLowerCase();
+  const hits = window.SEARCH_INDEX.filter(e => e[4].toLowerCase().includes(needle) || e[3].toLowerCase().includes(needle));
+  status.textContent = '共 ' + hits.length + ' 条结果' + (hits.length > 1000 ? '，仅显示前 1000 条' : '');
+  for (const [page, seq, time, sender, text] of hits.slice(0, 1000)) {
+    const a = document.createElement('a');
+    a.className = 'result';
+    a.href = page + '#m' + seq;
+    const meta = document.createElement('small');
+    meta.textContent = time + ' ' + sender;
+    a.appendChild(meta);
+    a.appendChild(document.createElement('br'));
+    a.appendChild(document.createTextNode(text));
+    main.appendChild(a);
+  }
+} else {
+  status.textContent = '请输入关键词';
+}
+"#;
+
+/// HTML导出器
+///
+/// 每页一个HTML文件（第1页为 index.html），样式内联，媒体保存在 media 目录，
+/// 搜索在 search.html 中基于 search-index.js 离线完成。
+pub struct HtmlExporter<'a> {
+    db: &'a Database,
+    out_dir: PathBuf,
+    title: String,
+    page_size: usize,
+    names: &'a SenderNames,
+    media: MediaFetcher,
+    timezone: ExportTimezone,
+}
+
+impl<'a> HtmlExporter<'a> {
+    pub fn new(
+        db: &'a Database,
+        out_dir: PathBuf,
+        title: String,
+        page_size: usize,
+        names: &'a SenderNames,
+        fetch_media: bool,
+    ) -> Result<Self> {
+        let media = MediaFetcher::new(&out_dir.join("media"), fetch_media)?;
+        Ok(HtmlExporter {
+            db,
+            out_dir,
+            title,
+            page_size: page_size.max(1),
+            names,
+            media,
+            timezone: ExportTimezone::Local,
+        })
+    }
+
+    /// 日期分隔、消息时间和搜索结果使用的时区，默认为系统时区
+    pub fn with_timezone(mut self, timezone: ExportTimezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// 导入的本地媒体所在的根目录，见 [`MediaFetcher::with_local_root`]
+    pub fn with_local_media(mut self, root: PathBuf) -> Self {
+        self.media = self.media.with_local_root(root);
+        self
+    }
+
+    /// 导出全部消息，返回页数
+    pub async fn export(&self) -> Result<usize> {
+        fs::create_dir_all(&self.out_dir)?;
+
+        // 第一遍：msg_seq 所在页码，用于回复链接
+        let mut seq_pages = HashMap::new();
+        let count = self.db.for_each_message(None, None, |stored| {
+            seq_pages.insert(stored.message.content_head.msg_seq, seq_pages.len() / self.page_size + 1);
+            Ok(())
+        })?;
+        let total_pages = count.div_ceil(self.page_size).max(1);
+
+        // 第二遍：逐页渲染
+        let mut search_index = Vec::new();
+        let mut cursor = None;
+        for page in 1..=total_pages {
+            let batch = self.db.get_messages_after(cursor, self.page_size as i64)?;
+            cursor = batch
+                .last()
+                .map(|m| (m.message.content_head.msg_time, m.id));
+
+            let mut body = String::new();
+            let mut last_day = String::new();
+            for stored in &batch {
+                let msg_time = stored.message.content_head.msg_time;
+                let day = self.timezone.format(msg_time, "%Y-%m-%d");
+                if day != last_day {
+                    body.push_str(&format!("<div class=\"day\">{}</div>\n", day));
+                    last_day = day;
+                }
+                body.push_str(&self.render_message(stored, &seq_pages).await);
+
+                search_index.push(json!([
+                    Self::page_file(page),
+                    stored.message.content_head.msg_seq,
+                    self.timezone.format(msg_time, "%Y-%m-%d %H:%M:%S"),
+                    self.names.name(stored.message.routing_head.from_uin),
+                    stored.message.plain_text(),
+                ]));
+            }
+
+            let nav = Self::render_nav(page, total_pages);
+            let html = self.render_document(
+                &format!("第 {} / {} 页", page, total_pages),
+                &format!("{}<main>\n{}</main>\n{}", nav, body, nav),
+                "",
+            );
+            fs::write(self.out_dir.join(Self::page_file(page)), html)?;
+        }
+
+        fs::write(
+            self.out_dir.join("search-index.js"),
+            format!("window.SEARCH_INDEX = {};\n", serde_json::to_string(&search_index)?),
+        )?;
+        fs::write(
+            self.out_dir.join("search.html"),
+            self.render_document(
+                "搜索",
+                "<nav><a href=\"index.html\">返回第一页</a></nav><main></main>",
+                &format!("<script src=\"search-index.js\"></script><script>{}</script>", SEARCH_SCRIPT),
+            ),
+        )?;
+
+        Ok(total_pages)
+    }
+
+    /// 第n页的文件名
+    fn page_file(page: usize) -> String {
+        if page == 1 {
+            "index.html".to_string()
+        } else {
+            format!("page-{}.html", page)
+        }
+    }
+
+    /// 完整的HTML文档
+    fn render_document(&self, subtitle: &str, body: &str, scripts: &str) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{title} - {subtitle}</title>\n<style>{style}</style>\n</head>\n<body>\n\
+             <header><h1>{title}</h1><form action=\"search.html\"><input name=\"q\" placeholder=\"搜索消息\"></form></header>\n\
+             {body}\n{scripts}\n</body>\n</html>\n",
+            title = escape_html(&self.title),
+            subtitle = escape_html(subtitle),
+            style = STYLE,
+            body = body,
+            scripts = scripts,
+        )
+    }
+
+    /// 分页导航
+    fn render_nav(page: usize, total_pages: usize) -> String {
+        let mut nav = String::from("<nav>");
+        if page > 1 {
+            nav.push_str(&format!("<a href=\"{}\">上一页</a>", Self::page_file(page - 1)));
+        }
+        for p in 1..=total_pages {
+            // 只显示首尾和当前页附近的页码
+            if p == 1 || p == total_pages || p.abs_diff(page) <= 3 {
+                if p == page {
+                    nav.push_str(&format!("<span>{}</span>", p));
+                } else {
+                    nav.push_str(&format!("<a href=\"{}\">{}</a>", Self::page_file(p), p));
+                }
+            } else if p.abs_diff(page) == 4 {
+                nav.push_str("<span>…</span>");
+            }
+        }
+        if page < total_pages {
+            nav.push_str(&format!("<a href=\"{}\">下一页</a>", Self::page_file(page + 1)));
+        }
+        nav.push_str("</nav>\n");
+        nav
+    }
+
+    /// 单条消息
+    async fn render_message(&self, stored: &StoredMessage, seq_pages: &HashMap<i64, usize>) -> String {
+        let message = &stored.message;
+        let mut bubble = String::new();
+        for elem in &message.body {
+            bubble.push_str(&self.render_element(elem, seq_pages).await);
+        }
+
+        format!(
+            "<div class=\"msg{}\" id=\"m{}\"><div class=\"meta\"><span class=\"sender\">{}</span> <time>{}</time></div>\
+             <div class=\"bubble\">{}</div></div>\n",
+            if self.names.is_self(message) { " self" } else { "" },
+            message.content_head.msg_seq,
+            escape_html(&self.names.name(message.routing_head.from_uin)),
+            self.timezone.format(message.content_head.msg_time, "%H:%M:%S"),
+            bubble,
+        )
+    }
+
+    /// 单个元素
+    async fn render_element(&self, elem: &Element, seq_pages: &HashMap<i64, usize>) -> String {
+        match elem {
+            Element::Text(t) => escape_html(&t.text),
+            Element::Face(_) => format!("<span class=\"face\">{}</span>", escape_html(&elem.plain_text())),
+            Element::Mention(_) => format!("<span class=\"mention\">{}</span>", escape_html(&elem.plain_text())),
+            Element::Image(image) => {
+                let name = media_file_name(&image.file.md5, &image.file_uuid, &image.file.file_name, "jpg");
+                match name {
+                    Some(name) => match self.media.fetch(&image.url, &name).await {
+                        Some(file) => format!(
+                            "<a href=\"media/{0}\"><img src=\"media/{0}\" loading=\"lazy\" alt=\"[图片]\"></a>",
+                            escape_html(&file)
+                        ),
+                        None => "<span class=\"placeholder\">[图片]</span>".to_string(),
+                    },
+                    None => "<span class=\"placeholder\">[图片]</span>".to_string(),
+                }
+            }
+            Element::Voice(voice) => {
+                format!("<span class=\"placeholder\">[语音 {}s]</span>", voice.duration)
+            }
+            Element::Video(video) => {
+                let thumb = match &video.thumb {
+                    Some(thumb) => match media_file_name(&thumb.file.md5, &thumb.file_uuid, &thumb.file.file_name, "jpg") {
+                        Some(name) => self.media.fetch(&thumb.url, &name).await,
+                        None => None,
+                    },
+                    None => None,
+                };
+                match thumb {
+                    Some(file) => format!(
+                        "<span class=\"video\"><img src=\"media/{}\" loading=\"lazy\" alt=\"[视频]\"><span>▶ {}s</span></span>",
+                        escape_html(&file),
+                        video.duration
+                    ),
+                    None => format!("<span class=\"placeholder\">[视频 {}s]</span>", video.duration),
+                }
+            }
+            Element::Reply(reply) => {
+                let quote: String = reply.source.iter().map(Element::plain_text).collect();
+                let quote = escape_html(&quote);
+                let quote = match seq_pages.get(&reply.seq) {
+                    Some(page) => format!("<a href=\"{}#m{}\">{}</a>", Self::page_file(*page), reply.seq, quote),
+                    None => quote,
+                };
+                let mut html = format!("<div class=\"reply\">{}</div>", quote);
+                for elem in &reply.content {
+                    html.push_str(&Box::pin(self.render_element(elem, seq_pages)).await);
+                }
+                html
+            }
+            Element::Custom(c) => format!("<span class=\"placeholder\">[{}]</span>", escape_html(&c.kind)),
+        }
+    }
+}
+
+/// 转义HTML特殊字符
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;");
+        assert_eq!(HtmlExporter::page_file(1), "index.html");
+        assert_eq!(HtmlExporter::page_file(3), "page-3.html");
+    }
+
+    #[tokio::test]
+    async fn test_timezone() {
+        use crate::message::{ContentHead, Message, RoutingHead, TextElement, SCHEMA_VERSION};
+
+        let db = Database::new(":memory:").unwrap();
+        db.save_message(&Message {
+            version: SCHEMA_VERSION,
+            // 2023-11-14 22:13:20 UTC
+            content_head: ContentHead { msg_time: 1700000000, msg_seq: 1, ..Default::default() },
+            routing_head: RoutingHead { from_uin: 1, to_uin: 2, ..Default::default() },
+            body: vec![Element::Text(TextElement { text: "hi".to_string() })],
+        })
+        .unwrap();
+        let names = SenderNames::new(None, None, &[]).unwrap();
+        let dir = std::env::temp_dir().join(format!("qqhistory-html-{}", std::process::id()));
+
+        for (timezone, day, time) in [("+08:00", "2023-11-15", "06:13:20"), ("utc", "2023-11-14", "22:13:20")] {
+            HtmlExporter::new(&db, dir.clone(), "t".to_string(), 10, &names, false)
+                .unwrap()
+                .with_timezone(timezone.parse().unwrap())
+                .export()
+                .await
+                .unwrap();
+            let index = fs::read_to_string(dir.join("index.html")).unwrap();
+            assert!(index.contains(&format!("<div class=\"day\">{}</div>", day)), "{}", index);
+            assert!(index.contains(&format!("<time>{}</time>", time)), "{}", index);
+            let search = fs::read_to_string(dir.join("search-index.js")).unwrap();
+            assert!(search.contains(&format!("{} {}", day, time)), "{}", search);
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,272 @@
+pub mod html;
+pub mod onebot;
+pub mod table;
+pub mod telegram;
+pub mod text;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::helper::Helper;
+use crate::message::Message;
+
+/// 导出时使用的发送者名称
+pub struct SenderNames {
+    self_uin: Option<i64>,
+    names: HashMap<i64, String>,
+}
+
+impl SenderNames {
+    /// `overrides` 为 `UIN=名称` 形式的列表，优先于自己的昵称
+    pub fn new(self_uin: Option<i64>, self_name: Option<String>, overrides: &[String]) -> Result<Self> {
+        let mut names = HashMap::new();
+        if let (Some(uin), Some(name)) = (self_uin, self_name) {
+            names.insert(uin, name);
+        }
+        for item in overrides {
+            let (uin, name) = item
+                .split_once('=')
+                .with_context(|| format!("名称格式应为 UIN=名称: {}", item))?;
+            let uin = uin.trim().parse::<i64>().with_context(|| format!("无效的uin: {}", uin))?;
+            names.insert(uin, name.trim().to_string());
+        }
+        Ok(SenderNames { self_uin, names })
+    }
+
+    /// 发送者名称，未知时为uin
+    pub fn name(&self, uin: i64) -> String {
+        self.names.get(&uin).cloned().unwrap_or_else(|| uin.to_string())
+    }
+
+    /// 自己的QQ号
+    pub fn self_uin(&self) -> Option<i64> {
+        self.self_uin
+    }
+
+    /// 是否是自己发送的消息
+    pub fn is_self(&self, message: &Message) -> bool {
+        self.self_uin == Some(message.routing_head.from_uin)
+    }
+}
+
+/// 导出使用的时区
+#[derive(Debug, Clone, Copy)]
+pub enum ExportTimezone {
+    /// 系统本地时区
+    Local,
+    /// 固定偏移，如 UTC+8
+    Fixed(FixedOffset),
+}
+
+impl FromStr for ExportTimezone {
+    type Err = anyhow::Error;
+
+    /// 支持 `local`、`utc` 以及 `+08:00`、`+0800`、`-5` 形式的偏移
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(ExportTimezone::Local);
+        }
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(ExportTimezone::Fixed(FixedOffset::east_opt(0).unwrap()));
+        }
+
+        let invalid = || anyhow!("无效的时区: {}，应为 local、utc 或 +08:00 形式", s);
+        let (sign, rest) = match s.as_bytes().first() {
+            Some(b'+') => (1, &s[1..]),
+            Some(b'-') => (-1, &s[1..]),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = match rest.split_once(':') {
+            Some((h, m)) => (h, m),
+            None if rest.len() == 4 => rest.split_at(2),
+            None => (rest, "0"),
+        };
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(ExportTimezone::Fixed)
+            .ok_or_else(invalid)
+    }
+}
+
+impl ExportTimezone {
+    /// 按时区格式化Unix时间戳
+    pub fn format(&self, timestamp: i64, fmt: &str) -> String {
+        let utc = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+        match self {
+            ExportTimezone::Local => utc.with_timezone(&Local).format(fmt).to_string(),
+            ExportTimezone::Fixed(offset) => utc.with_timezone(offset).format(fmt).to_string(),
+        }
+    }
+
+    /// 解析该时区下的 `YYYY-MM-DD` 或 `YYYY-MM-DD HH:MM[:SS]`
+    ///
+    /// 只有日期时，`end_of_day` 为true取当天最后一秒，否则取当天0点。
+    pub fn parse_time(&self, s: &str, end_of_day: bool) -> Result<i64> {
+        let s = s.trim();
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M"))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| {
+                    if end_of_day {
+                        d.and_hms_opt(23, 59, 59).unwrap()
+                    } else {
+                        d.and_hms_opt(0, 0, 0).unwrap()
+                    }
+                })
+            })
+            .with_context(|| format!("无效的时间: {}，应为 YYYY-MM-DD 或 YYYY-MM-DD HH:MM:SS", s))?;
+
+        let timestamp = match self {
+            ExportTimezone::Local => Local.from_local_datetime(&naive).earliest().map(|t| t.timestamp()),
+            ExportTimezone::Fixed(offset) => offset.from_local_datetime(&naive).single().map(|t| t.timestamp()),
+        };
+        timestamp.with_context(|| format!("该时区下不存在的时间: {}", s))
+    }
+}
+
+/// 导入的本地媒体的链接前缀，其后为相对数据库目录下 `media/` 的路径，如 `local:<会话>/<文件名>`
+pub const LOCAL_MEDIA_PREFIX: &str = "local:";
+
+/// 导出时下载富媒体文件
+///
+/// 图片等链接中的rkey会过期，只能在导出时尽量下载；已存在的文件不会重复下载。
+/// 只下载 http(s) 链接；[`LOCAL_MEDIA_PREFIX`] 开头的本地媒体只从 `local_root` 内复制。
+pub struct MediaFetcher {
+    client: Client,
+    dir: PathBuf,
+    enabled: bool,
+    /// 本地媒体的根目录，即数据库所在目录下的 media
+    local_root: Option<PathBuf>,
+}
+
+impl MediaFetcher {
+    /// `dir` 为媒体目录，`enabled` 为false时不下载
+    pub fn new(dir: &Path, enabled: bool) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()?;
+        Ok(MediaFetcher { client, dir: dir.to_path_buf(), enabled, local_root: None })
+    }
+
+    /// 设置本地媒体的根目录
+    pub fn with_local_root(mut self, root: PathBuf) -> Self {
+        self.local_root = Some(root);
+        self
+    }
+
+    /// 下载文件，`file_name` 可包含子目录，返回保存的文件名；未启用、无链接或下载失败时返回None
+    pub async fn fetch(&self, url: &str, file_name: &str) -> Option<String> {
+        let path = self.dir.join(file_name);
+        if path.exists() {
+            return Some(file_name.to_string());
+        }
+        if !self.enabled || url.is_empty() {
+            return None;
+        }
+
+        match self.download(url, &path).await {
+            Ok(()) => Some(file_name.to_string()),
+            Err(e) => {
+                Helper::echo(&format!("下载媒体失败 {}: {}", file_name, e), "yellow");
+                None
+            }
+        }
+    }
+
+    async fn download(&self, url: &str, path: &Path) -> Result<()> {
+        // 导入的消息中的图片为本地文件
+        if let Some(relative) = url.strip_prefix(LOCAL_MEDIA_PREFIX) {
+            let source = self.resolve_local(relative)?;
+            std::fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+            std::fs::copy(source, path)?;
+            return Ok(());
+        }
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(anyhow!("不支持的链接"));
+        }
+
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("HTTP {}", response.status()));
+        }
+        let bytes = response.bytes().await?;
+        std::fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+        std::fs::write(path, &bytes)?;
+        Ok(())
+    }
+
+    /// 本地媒体的实际路径，规范化后必须是 `local_root` 内的文件
+    fn resolve_local(&self, relative: &str) -> Result<PathBuf> {
+        let root = self.local_root.as_ref().context("没有本地媒体目录")?;
+        let root = root.canonicalize().with_context(|| format!("本地媒体目录不存在: {}", root.display()))?;
+        let source = root
+            .join(relative)
+            .canonicalize()
+            .with_context(|| format!("本地媒体不存在: {}", relative))?;
+        if !source.starts_with(&root) || !source.is_file() {
+            return Err(anyhow!("本地媒体不在 {} 内: {}", root.display(), relative));
+        }
+        Ok(source)
+    }
+}
+
+/// 由哈希或uuid和原文件名生成媒体文件名
+pub fn media_file_name(md5: &str, file_uuid: &str, original_name: &str, default_ext: &str) -> Option<String> {
+    let stem = if !md5.is_empty() { md5 } else { file_uuid };
+    let stem: String = stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    if stem.is_empty() {
+        return None;
+    }
+
+    let ext = Path::new(original_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or(default_ext)
+        .to_ascii_lowercase();
+    Some(format!("{}.{}", stem.to_ascii_lowercase(), ext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timezone() {
+        let tz: ExportTimezone = "+08:00".parse().unwrap();
+        assert_eq!(tz.format(0, "%Y-%m-%d %H:%M"), "1970-01-01 08:00");
+        assert_eq!(tz.parse_time("1970-01-01", true).unwrap(), 16 * 3600 - 1);
+        assert_eq!(tz.parse_time("1970-01-01 08:00", false).unwrap(), 0);
+
+        let tz: ExportTimezone = "-0530".parse().unwrap();
+        assert_eq!(tz.format(0, "%H:%M"), "18:30");
+        assert!("utc".parse::<ExportTimezone>().is_ok());
+        assert!("Asia/Shanghai".parse::<ExportTimezone>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_local() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-local-{}", std::process::id()));
+        let root = dir.join("media");
+        std::fs::create_dir_all(root.join("10001")).unwrap();
+        std::fs::write(root.join("10001/a.png"), b"png").unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+
+        let fetcher = MediaFetcher::new(&dir.join("out"), true).unwrap().with_local_root(root.clone());
+        assert!(fetcher.resolve_local("10001/a.png").is_ok());
+        assert!(fetcher.resolve_local("../secret.txt").is_err());
+        assert!(fetcher.resolve_local("10001").is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
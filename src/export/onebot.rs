@@ -0,0 +1,49 @@
+use anyhow::Result;
+use serde_json::json;
+use std::io::Write;
+use crate::database::Database;
+use crate::export::SenderNames;
+use crate::onebot::{MessageFormat, Segment};
+
+/// OneBot消息导出器
+///
+/// 每行一条消息，`message` 为所选格式的消息段数组或CQ码字符串。
+/// 没有服务端的message_id，`message_id` 和回复段的 `id` 均为msg_seq。
+pub struct OneBotExporter<'a> {
+    db: &'a Database,
+    names: &'a SenderNames,
+    format: MessageFormat,
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl<'a> OneBotExporter<'a> {
+    pub fn new(
+        db: &'a Database,
+        names: &'a SenderNames,
+        format: MessageFormat,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Self {
+        OneBotExporter { db, names, format, start, end }
+    }
+
+    /// 按时间顺序写出消息，返回导出的条数
+    pub fn export<W: Write + ?Sized>(&self, out: &mut W) -> Result<usize> {
+        self.db.for_each_message(self.start, self.end, |stored| {
+            let message = &stored.message;
+            let segments = Segment::from_elements(&message.body, &mut Some);
+            let from_uin = message.routing_head.from_uin;
+            let line = json!({
+                "message_id": message.content_head.msg_seq,
+                "time": message.content_head.msg_time,
+                "user_id": from_uin,
+                "sender": {"user_id": from_uin, "nickname": self.names.name(from_uin)},
+                "message": self.format.render(&segments),
+            });
+            serde_json::to_writer(&mut *out, &line)?;
+            out.write_all(b"\n")?;
+            Ok(())
+        })
+    }
+}
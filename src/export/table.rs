@@ -0,0 +1,302 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use crate::database::{Database, StoredMessage};
+use crate::export::{ExportTimezone, SenderNames};
+use crate::message::Element;
+
+/// JSON Lines/CSV导出的schema版本
+///
+/// 列的删除、改名或语义变化会递增版本号，新增列不会。
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// 每条消息一行
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageRecord {
+    pub schema_version: u32,
+    /// 数据库中的行id
+    pub id: i64,
+    pub msg_seq: i64,
+    pub msg_uid: String,
+    /// Unix时间戳（秒）
+    pub msg_time: i64,
+    /// 按导出时区格式化的RFC 3339时间
+    pub time: String,
+    pub sender_uin: i64,
+    pub sender_uid: String,
+    pub sender_name: String,
+    /// 会话对方；不知道自己的QQ号时为接收者
+    pub peer_uin: i64,
+    pub peer_uid: String,
+    pub peer_name: String,
+    pub is_self: bool,
+    /// 纯文本内容，富媒体为占位符
+    pub text: String,
+    /// 回复的消息的msg_seq
+    pub reply_to_seq: Option<i64>,
+    pub element_count: usize,
+}
+
+/// JSON Lines中的一行，附带完整的元素数组
+#[derive(Debug, Serialize)]
+struct JsonLine<'a> {
+    #[serde(flatten)]
+    record: MessageRecord,
+    elements: &'a [Element],
+}
+
+/// CSV元素文件中的一行，回复的内容元素紧跟在回复元素之后
+#[derive(Debug, Clone, Serialize)]
+pub struct ElementRecord {
+    pub schema_version: u32,
+    /// 所属消息的行id，对应 [`MessageRecord::id`]
+    pub message_id: i64,
+    pub msg_seq: i64,
+    /// 在消息中的序号，从0开始
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// 纯文本内容；回复元素为被回复的内容
+    pub text: String,
+    pub url: String,
+    pub file_name: String,
+    pub md5: String,
+    pub size: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<u32>,
+    /// 被@的QQ号
+    pub mention_uin: Option<i64>,
+    /// 回复的消息的msg_seq
+    pub reply_seq: Option<i64>,
+    /// 元素的完整JSON
+    pub data: String,
+}
+
+/// JSON Lines/CSV导出器，逐条读取数据库，不会一次性加载全部消息
+pub struct TableExporter<'a> {
+    db: &'a Database,
+    names: &'a SenderNames,
+    timezone: ExportTimezone,
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl<'a> TableExporter<'a> {
+    pub fn new(
+        db: &'a Database,
+        names: &'a SenderNames,
+        timezone: ExportTimezone,
+        start: Option<i64>,
+        end: Option<i64>,
+    ) -> Self {
+        TableExporter { db, names, timezone, start, end }
+    }
+
+    /// 每行一个JSON对象，返回导出的条数
+    pub fn export_jsonl<W: Write + ?Sized>(&self, out: &mut W) -> Result<usize> {
+        self.db.for_each_message(self.start, self.end, |stored| {
+            let line = JsonLine {
+                record: self.message_record(&stored),
+                elements: &stored.message.body,
+            };
+            serde_json::to_writer(&mut *out, &line)?;
+            out.write_all(b"\n")?;
+            Ok(())
+        })
+    }
+
+    /// 消息和元素分别写入两个CSV，返回导出的消息条数
+    pub fn export_csv<M: Write, E: Write>(&self, messages: M, elements: E) -> Result<usize> {
+        let mut messages = csv::Writer::from_writer(messages);
+        let mut elements = csv::Writer::from_writer(elements);
+        let count = self.db.for_each_message(self.start, self.end, |stored| {
+            messages.serialize(self.message_record(&stored))?;
+            for record in Self::element_records(&stored)? {
+                elements.serialize(record)?;
+            }
+            Ok(())
+        })?;
+        messages.flush()?;
+        elements.flush()?;
+        Ok(count)
+    }
+
+    pub fn message_record(&self, stored: &StoredMessage) -> MessageRecord {
+        let message = &stored.message;
+        let routing = &message.routing_head;
+        let is_self = self.names.is_self(message);
+        let (peer_uin, peer_uid) = if is_self || self.names.self_uin().is_none() {
+            (routing.to_uin, routing.to_uid.clone())
+        } else {
+            (routing.from_uin, routing.from_uid.clone())
+        };
+        let reply_to_seq = message.body.iter().find_map(|e| match e {
+            Element::Reply(r) => Some(r.seq),
+            _ => None,
+        });
+
+        MessageRecord {
+            schema_version: EXPORT_SCHEMA_VERSION,
+            id: stored.id,
+            msg_seq: message.content_head.msg_seq,
+            msg_uid: message.content_head.msg_uid.clone(),
+            msg_time: message.content_head.msg_time,
+            time: self.timezone.format(message.content_head.msg_time, "%Y-%m-%dT%H:%M:%S%:z"),
+            sender_uin: routing.from_uin,
+            sender_uid: routing.from_uid.clone(),
+            sender_name: self.names.name(routing.from_uin),
+            peer_uin,
+            peer_uid,
+            peer_name: self.names.name(peer_uin),
+            is_self,
+            text: message.plain_text(),
+            reply_to_seq,
+            element_count: message.body.len(),
+        }
+    }
+
+    pub fn element_records(stored: &StoredMessage) -> Result<Vec<ElementRecord>> {
+        let mut records = Vec::new();
+        for elem in &stored.message.body {
+            Self::push_element(stored, elem, &mut records)?;
+            if let Element::Reply(reply) = elem {
+                for content in &reply.content {
+                    Self::push_element(stored, content, &mut records)?;
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn push_element(stored: &StoredMessage, elem: &Element, records: &mut Vec<ElementRecord>) -> Result<()> {
+        let mut record = ElementRecord {
+            schema_version: EXPORT_SCHEMA_VERSION,
+            message_id: stored.id,
+            msg_seq: stored.message.content_head.msg_seq,
+            index: records.len(),
+            kind: elem.kind(),
+            text: elem.plain_text(),
+            url: String::new(),
+            file_name: String::new(),
+            md5: String::new(),
+            size: None,
+            width: None,
+            height: None,
+            duration: None,
+            mention_uin: None,
+            reply_seq: None,
+            data: serde_json::to_string(elem)?,
+        };
+
+        let file = match elem {
+            Element::Image(image) => {
+                record.url = image.url.clone();
+                Some(&image.file)
+            }
+            Element::Voice(voice) => {
+                record.url = voice.url.clone();
+                record.duration = Some(voice.duration);
+                Some(&voice.file)
+            }
+            Element::Video(video) => {
+                record.url = video.url.clone();
+                record.duration = Some(video.duration);
+                Some(&video.file)
+            }
+            Element::Mention(mention) => {
+                record.mention_uin = Some(mention.uin);
+                None
+            }
+            Element::Reply(reply) => {
+                record.text = reply.source.iter().map(Element::plain_text).collect();
+                record.reply_seq = Some(reply.seq);
+                None
+            }
+            _ => None,
+        };
+        if let Some(file) = file {
+            record.file_name = file.file_name.clone();
+            record.md5 = file.md5.clone();
+            record.size = Some(file.size);
+            record.width = Some(file.width).filter(|w| *w > 0);
+            record.height = Some(file.height).filter(|h| *h > 0);
+        }
+
+        records.push(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{
+        ContentHead, FileInfo, ImageElement, MentionElement, Message, ReplyElement, RoutingHead, TextElement,
+        SCHEMA_VERSION,
+    };
+
+    fn message(seq: i64, from_uin: i64, body: Vec<Element>) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_time: 1700000000 + seq, msg_seq: seq, ..Default::default() },
+            routing_head: RoutingHead { from_uin, to_uin: 3 - from_uin, ..Default::default() },
+            body,
+        }
+    }
+
+    #[test]
+    fn test_export() {
+        let db = Database::new(":memory:").unwrap();
+        let text = |t: &str| Element::Text(TextElement { text: t.to_string() });
+        db.save_messages(&[
+            message(1, 1, vec![text("你好，\"世界\"")]),
+            message(2, 2, vec![
+                Element::Reply(ReplyElement {
+                    seq: 1,
+                    source: vec![text("你好")],
+                    content: vec![Element::Mention(MentionElement { uin: 1, name: "我".into(), ..Default::default() })],
+                    ..Default::default()
+                }),
+                Element::Image(ImageElement {
+                    file: FileInfo { size: 2048, md5: "abc".into(), file_name: "a.jpg".into(), width: 640, ..Default::default() },
+                    ..Default::default()
+                }),
+            ]),
+        ])
+        .unwrap();
+        let names = SenderNames::new(Some(1), Some("我".to_string()), &["2=对方".to_string()]).unwrap();
+        let exporter = TableExporter::new(&db, &names, "+08:00".parse().unwrap(), None, None);
+
+        let mut jsonl = Vec::new();
+        assert_eq!(exporter.export_jsonl(&mut jsonl).unwrap(), 2);
+        let jsonl = String::from_utf8(jsonl).unwrap();
+        assert_eq!(
+            jsonl.lines().next().unwrap(),
+            r#"{"schema_version":1,"id":1,"msg_seq":1,"msg_uid":"","msg_time":1700000001,"time":"2023-11-15T06:13:21+08:00","sender_uin":1,"sender_uid":"","sender_name":"我","peer_uin":2,"peer_uid":"","peer_name":"对方","is_self":true,"text":"你好，\"世界\"","reply_to_seq":null,"element_count":1,"elements":[{"type":"text","text":"你好，\"世界\""}]}"#
+        );
+        assert_eq!(jsonl.lines().count(), 2);
+
+        let (mut messages, mut elements) = (Vec::new(), Vec::new());
+        assert_eq!(exporter.export_csv(&mut messages, &mut elements).unwrap(), 2);
+        let messages = String::from_utf8(messages).unwrap();
+        let messages: Vec<&str> = messages.lines().collect();
+        assert_eq!(messages, [
+            "schema_version,id,msg_seq,msg_uid,msg_time,time,sender_uin,sender_uid,sender_name,peer_uin,peer_uid,peer_name,is_self,text,reply_to_seq,element_count",
+            r#"1,1,1,,1700000001,2023-11-15T06:13:21+08:00,1,,我,2,,对方,true,"你好，""世界""",,1"#,
+            "1,2,2,,1700000002,2023-11-15T06:13:22+08:00,2,,对方,2,,对方,false,@我[图片],1,2",
+        ]);
+
+        // 回复的内容元素紧跟在回复元素之后
+        let elements = String::from_utf8(elements).unwrap();
+        let elements: Vec<&str> = elements.lines().collect();
+        assert_eq!(elements.len(), 5);
+        assert_eq!(
+            elements[0],
+            "schema_version,message_id,msg_seq,index,type,text,url,file_name,md5,size,width,height,duration,mention_uin,reply_seq,data"
+        );
+        assert!(elements[2].starts_with("1,2,2,0,reply,你好,,,,,,,,,1,"));
+        assert!(elements[3].starts_with("1,2,2,1,mention,@我,,,,,,,,1,,"));
+        assert!(elements[4].starts_with("1,2,2,2,image,[图片],,a.jpg,abc,2048,640,,,,,"));
+    }
+}
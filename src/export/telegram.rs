@@ -0,0 +1,304 @@
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use crate::database::{Database, StoredMessage};
+use crate::export::{media_file_name, ExportTimezone, MediaFetcher, SenderNames};
+use crate::message::Element;
+
+/// 未下载文件时Telegram Desktop写入的占位文本
+const FILE_NOT_INCLUDED: &str = "(File not included. Change data exporting settings to download.)";
+
+/// 每次从数据库读取的条数
+const BATCH_SIZE: i64 = 500;
+
+/// 导出的会话
+pub struct TelegramChat {
+    pub name: String,
+    /// 私聊为对方QQ号，群聊为群号
+    pub id: i64,
+    pub is_group: bool,
+}
+
+impl TelegramChat {
+    /// Telegram Desktop 中的会话类型
+    fn kind(&self) -> &'static str {
+        if self.is_group { "private_group" } else { "personal_chat" }
+    }
+}
+
+/// Telegram Desktop `result.json` 导出器
+///
+/// 输出目录包含 result.json 以及 photos、video_files 媒体目录。
+/// Telegram每条消息只能带一个媒体，多出的图片等以文本占位符保留。
+pub struct TelegramExporter<'a> {
+    db: &'a Database,
+    names: &'a SenderNames,
+    out_dir: PathBuf,
+    chat: TelegramChat,
+    timezone: ExportTimezone,
+    start: Option<i64>,
+    end: Option<i64>,
+    media: MediaFetcher,
+}
+
+/// 文本实体
+enum Entity {
+    Plain(String),
+    Mention { text: String, user_id: i64 },
+    MentionAll(String),
+}
+
+impl<'a> TelegramExporter<'a> {
+    pub fn new(
+        db: &'a Database,
+        names: &'a SenderNames,
+        out_dir: PathBuf,
+        chat: TelegramChat,
+        timezone: ExportTimezone,
+        range: (Option<i64>, Option<i64>),
+        fetch_media: bool,
+    ) -> Result<Self> {
+        let media = MediaFetcher::new(&out_dir, fetch_media)?;
+        Ok(TelegramExporter {
+            db,
+            names,
+            out_dir,
+            chat,
+            timezone,
+            start: range.0,
+            end: range.1,
+            media,
+        })
+    }
+
+    /// 导入的本地媒体所在的根目录，见 [`MediaFetcher::with_local_root`]
+    pub fn with_local_media(mut self, root: PathBuf) -> Self {
+        self.media = self.media.with_local_root(root);
+        self
+    }
+
+    /// 写出 result.json，返回导出的消息条数
+    pub async fn export(&self) -> Result<usize> {
+        fs::create_dir_all(&self.out_dir)?;
+        let mut out = BufWriter::new(File::create(self.out_dir.join("result.json"))?);
+        writeln!(out, "{{")?;
+        writeln!(out, " \"name\": {},", serde_json::to_string(&self.chat.name)?)?;
+        writeln!(out, " \"type\": \"{}\",", self.chat.kind())?;
+        writeln!(out, " \"id\": {},", self.chat.id)?;
+        write!(out, " \"messages\": [")?;
+
+        // (msg_time, id) 游标从起始时间之前开始
+        let mut cursor = self.start.map(|start| (start, i64::MIN));
+        let mut count = 0;
+        'outer: loop {
+            let batch = self.db.get_messages_after(cursor, BATCH_SIZE)?;
+            for stored in &batch {
+                if self.end.is_some_and(|end| stored.message.content_head.msg_time > end) {
+                    break 'outer;
+                }
+                let message = self.render_message(stored).await;
+                write!(out, "{}\n  {}", if count == 0 { "" } else { "," }, serde_json::to_string(&message)?)?;
+                count += 1;
+            }
+            match batch.last() {
+                Some(last) if batch.len() as i64 == BATCH_SIZE => {
+                    cursor = Some((last.message.content_head.msg_time, last.id));
+                }
+                _ => break,
+            }
+        }
+
+        writeln!(out, "\n ]")?;
+        writeln!(out, "}}")?;
+        out.flush()?;
+        Ok(count)
+    }
+
+    async fn render_message(&self, stored: &StoredMessage) -> Value {
+        let message = &stored.message;
+        let msg_time = message.content_head.msg_time;
+        let from_uin = message.routing_head.from_uin;
+
+        let mut obj = Map::new();
+        obj.insert("id".into(), json!(message.content_head.msg_seq));
+        obj.insert("type".into(), json!("message"));
+        obj.insert("date".into(), json!(self.timezone.format(msg_time, "%Y-%m-%dT%H:%M:%S")));
+        obj.insert("date_unixtime".into(), json!(msg_time.to_string()));
+        obj.insert("from".into(), json!(self.names.name(from_uin)));
+        obj.insert("from_id".into(), json!(format!("user{}", from_uin)));
+
+        let mut entities = Vec::new();
+        let mut has_media = false;
+        for elem in &message.body {
+            if let Element::Reply(reply) = elem {
+                obj.insert("reply_to_message_id".into(), json!(reply.seq));
+                for content in &reply.content {
+                    self.render_element(content, &mut obj, &mut entities, &mut has_media).await;
+                }
+            } else {
+                self.render_element(elem, &mut obj, &mut entities, &mut has_media).await;
+            }
+        }
+
+        let (text, text_entities) = Self::render_entities(entities);
+        obj.insert("text".into(), text);
+        obj.insert("text_entities".into(), text_entities);
+        Value::Object(obj)
+    }
+
+    /// 第一个媒体写入消息的媒体字段，其余元素作为文本实体
+    async fn render_element(&self, elem: &Element, obj: &mut Map<String, Value>, entities: &mut Vec<Entity>, has_media: &mut bool) {
+        match elem {
+            Element::Mention(m) if m.all => entities.push(Entity::MentionAll(elem.plain_text())),
+            Element::Mention(m) if m.uin != 0 => entities.push(Entity::Mention {
+                text: if m.name.is_empty() { self.names.name(m.uin) } else { m.name.clone() },
+                user_id: m.uin,
+            }),
+            Element::Image(image) if !*has_media => {
+                *has_media = true;
+                let file = self.fetch("photos", &image.url, &image.file.md5, &image.file_uuid, &image.file.file_name, "jpg").await;
+                obj.insert("photo".into(), json!(file));
+                if image.file.width > 0 && image.file.height > 0 {
+                    obj.insert("width".into(), json!(image.file.width));
+                    obj.insert("height".into(), json!(image.file.height));
+                }
+            }
+            Element::Video(video) if !*has_media => {
+                *has_media = true;
+                let file = self.fetch("video_files", &video.url, &video.file.md5, &video.file_uuid, &video.file.file_name, "mp4").await;
+                obj.insert("file".into(), json!(file));
+                if let Some(thumb) = &video.thumb {
+                    let thumb = self.fetch("video_files", &thumb.url, &thumb.file.md5, &thumb.file_uuid, &thumb.file.file_name, "jpg").await;
+                    obj.insert("thumbnail".into(), json!(thumb));
+                }
+                obj.insert("media_type".into(), json!("video_file"));
+                obj.insert("mime_type".into(), json!("video/mp4"));
+                obj.insert("duration_seconds".into(), json!(video.duration));
+                if video.file.width > 0 && video.file.height > 0 {
+                    obj.insert("width".into(), json!(video.file.width));
+                    obj.insert("height".into(), json!(video.file.height));
+                }
+            }
+            Element::Voice(voice) if !*has_media => {
+                *has_media = true;
+                let file = self.fetch("voice_messages", &voice.url, &voice.file.md5, &voice.file_uuid, &voice.file.file_name, "amr").await;
+                obj.insert("file".into(), json!(file));
+                obj.insert("media_type".into(), json!("voice_message"));
+                obj.insert("mime_type".into(), json!("audio/amr"));
+                obj.insert("duration_seconds".into(), json!(voice.duration));
+            }
+            _ => entities.push(Entity::Plain(elem.plain_text())),
+        }
+    }
+
+    /// 下载到子目录，返回相对路径；未下载时为Telegram的占位文本
+    async fn fetch(&self, dir: &str, url: &str, md5: &str, file_uuid: &str, original_name: &str, ext: &str) -> String {
+        let Some(name) = media_file_name(md5, file_uuid, original_name, ext) else {
+            return FILE_NOT_INCLUDED.to_string();
+        };
+        self.media
+            .fetch(url, &format!("{}/{}", dir, name))
+            .await
+            .unwrap_or_else(|| FILE_NOT_INCLUDED.to_string())
+    }
+
+    /// 生成 `text` 和 `text_entities`；只有纯文本时 `text` 为字符串，否则为字符串和实体对象的数组
+    fn render_entities(entities: Vec<Entity>) -> (Value, Value) {
+        // 合并相邻的纯文本
+        let mut merged: Vec<Entity> = Vec::new();
+        for entity in entities {
+            match (merged.last_mut(), entity) {
+                (_, Entity::Plain(text)) if text.is_empty() => {}
+                (Some(Entity::Plain(last)), Entity::Plain(text)) => last.push_str(&text),
+                (_, entity) => merged.push(entity),
+            }
+        }
+
+        let text_entities: Vec<Value> = merged
+            .iter()
+            .map(|entity| match entity {
+                Entity::Plain(text) => json!({"type": "plain", "text": text}),
+                Entity::Mention { text, user_id } => {
+                    json!({"type": "mention_name", "text": text, "user_id": user_id})
+                }
+                Entity::MentionAll(text) => json!({"type": "mention", "text": text}),
+            })
+            .collect();
+
+        let text = match merged.as_slice() {
+            [] => json!(""),
+            [Entity::Plain(text)] => json!(text),
+            _ => Value::Array(
+                merged
+                    .iter()
+                    .zip(&text_entities)
+                    .map(|(entity, value)| match entity {
+                        Entity::Plain(text) => json!(text),
+                        _ => value.clone(),
+                    })
+                    .collect(),
+            ),
+        };
+        (text, Value::Array(text_entities))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{ContentHead, MentionElement, Message, ReplyElement, RoutingHead, TextElement, SCHEMA_VERSION};
+
+    fn message(seq: i64, from_uin: i64, body: Vec<Element>) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_time: 1700000000 + seq, msg_seq: seq, ..Default::default() },
+            routing_head: RoutingHead { from_uin, to_uin: 123456, ..Default::default() },
+            body,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_result_json() {
+        let db = Database::new(":memory:").unwrap();
+        let text = |t: &str| Element::Text(TextElement { text: t.to_string() });
+        db.save_messages(&[
+            message(1, 10001, vec![text("hi")]),
+            message(2, 10002, vec![Element::Reply(ReplyElement {
+                seq: 1,
+                content: vec![
+                    Element::Mention(MentionElement { uin: 10001, ..Default::default() }),
+                    text(" ok"),
+                ],
+                ..Default::default()
+            })]),
+        ])
+        .unwrap();
+
+        let names = SenderNames::new(Some(10002), None, &["10001=张三".to_string()]).unwrap();
+        let out_dir = std::env::temp_dir().join(format!("qqhistory-tg-{}", std::process::id()));
+        let chat = TelegramChat { name: "群".to_string(), id: 123456, is_group: true };
+        let timezone: ExportTimezone = "utc".parse().unwrap();
+        let exporter = TelegramExporter::new(&db, &names, out_dir.clone(), chat, timezone, (None, None), false).unwrap();
+        assert_eq!(exporter.export().await.unwrap(), 2);
+
+        let result: Value = serde_json::from_str(&fs::read_to_string(out_dir.join("result.json")).unwrap()).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+        assert_eq!(result["type"], "private_group");
+        assert_eq!(result["id"], 123456);
+        let messages = result["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["text"], "hi");
+        assert_eq!(messages[0]["from"], "张三");
+        assert_eq!(messages[0]["from_id"], "user10001");
+        assert_eq!(messages[1]["reply_to_message_id"], 1);
+        assert_eq!(
+            messages[1]["text_entities"],
+            json!([
+                {"type": "mention_name", "text": "张三", "user_id": 10001},
+                {"type": "plain", "text": " ok"},
+            ])
+        );
+        assert_eq!(messages[1]["text"], json!([{"type": "mention_name", "text": "张三", "user_id": 10001}, " ok"]));
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use std::io::Write;
+use crate::database::Database;
+use crate::export::{ExportTimezone, SenderNames};
+use crate::message::{Element, Message, ReplyElement};
+
+/// 文本导出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextFormat {
+    /// 纯文本
+    Txt,
+    /// Markdown，特殊字符会被转义
+    Markdown,
+}
+
+/// 文本导出选项
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// 起始时间（Unix时间戳，含）
+    pub start: Option<i64>,
+    /// 结束时间（Unix时间戳，含）
+    pub end: Option<i64>,
+    pub timezone: ExportTimezone,
+    /// 是否在日期变化处插入分隔行
+    pub day_separator: bool,
+}
+
+/// 纯文本/Markdown导出器
+///
+/// 每条消息渲染为 `[时间] 发送者: 内容`，回复以引用的形式放在内容之前。
+pub struct TextExporter<'a> {
+    db: &'a Database,
+    names: &'a SenderNames,
+    format: TextFormat,
+    options: TextOptions,
+}
+
+impl<'a> TextExporter<'a> {
+    pub fn new(db: &'a Database, names: &'a SenderNames, format: TextFormat, options: TextOptions) -> Self {
+        TextExporter { db, names, format, options }
+    }
+
+    /// 按时间顺序写出消息，返回导出的条数
+    pub fn export<W: Write + ?Sized>(&self, out: &mut W) -> Result<usize> {
+        let mut last_day = String::new();
+        self.db.for_each_message(self.options.start, self.options.end, |stored| {
+            let msg_time = stored.message.content_head.msg_time;
+            if self.options.day_separator {
+                let day = self.options.timezone.format(msg_time, "%Y-%m-%d");
+                if day != last_day {
+                    match self.format {
+                        TextFormat::Txt => writeln!(out, "---------------- {} ----------------", day)?,
+                        TextFormat::Markdown => writeln!(out, "## {}\n", day)?,
+                    }
+                    last_day = day;
+                }
+            }
+            out.write_all(self.render_message(&stored.message)?.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// 单条消息，以换行结尾
+    fn render_message(&self, message: &Message) -> Result<String> {
+        let time = self
+            .options
+            .timezone
+            .format(message.content_head.msg_time, "%Y-%m-%d %H:%M:%S");
+        let sender = self.names.name(message.routing_head.from_uin);
+
+        let mut quotes = Vec::new();
+        let mut content = String::new();
+        for elem in &message.body {
+            if let Element::Reply(reply) = elem {
+                quotes.push(self.render_quote(reply)?);
+                content.push_str(&self.render_elements(&reply.content));
+            } else {
+                content.push_str(&self.render_element(elem));
+            }
+        }
+
+        let text = match self.format {
+            TextFormat::Txt => {
+                let mut text = format!("[{}] {}:", time, sender);
+                for quote in &quotes {
+                    for line in quote.lines() {
+                        text.push_str(&format!("\n> {}", line));
+                    }
+                }
+                text.push(if quotes.is_empty() { ' ' } else { '\n' });
+                text.push_str(&content);
+                text.push('\n');
+                text
+            }
+            TextFormat::Markdown => {
+                let mut text = format!("**\\[{}\\] {}:**", time, escape_markdown(&sender));
+                for quote in &quotes {
+                    text.push('\n');
+                    for line in quote.lines() {
+                        text.push_str(&format!("> {}  \n", line));
+                    }
+                }
+                text.push(if quotes.is_empty() { ' ' } else { '\n' });
+                // 换行在Markdown中需要显式的硬换行
+                text.push_str(&content.lines().collect::<Vec<_>>().join("  \n"));
+                text.push_str("\n\n");
+                text
+            }
+        };
+        Ok(text)
+    }
+
+    /// 被回复消息的引用，带上原发送者
+    fn render_quote(&self, reply: &ReplyElement) -> Result<String> {
+        let source = self.render_elements(&reply.source);
+        Ok(match self.db.get_message_by_seq(reply.seq)? {
+            Some(stored) => {
+                let sender = self.names.name(stored.message.routing_head.from_uin);
+                let sender = match self.format {
+                    TextFormat::Txt => sender,
+                    TextFormat::Markdown => escape_markdown(&sender),
+                };
+                format!("{}: {}", sender, source)
+            }
+            None => source,
+        })
+    }
+
+    fn render_elements(&self, elems: &[Element]) -> String {
+        elems.iter().map(|e| self.render_element(e)).collect()
+    }
+
+    fn render_element(&self, elem: &Element) -> String {
+        let text = match elem {
+            Element::Reply(reply) => return self.render_elements(&reply.content),
+            Element::Mention(m) if !m.all && m.name.is_empty() => format!("@{}", self.names.name(m.uin)),
+            _ => elem.plain_text(),
+        };
+        match self.format {
+            TextFormat::Txt => text,
+            TextFormat::Markdown => escape_markdown(&text),
+        }
+    }
+}
+
+/// 转义Markdown特殊字符，以及行首会被当作列表的 `-`、`+` 和 `1.`、`1)`
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let rest = &line.as_bytes()[indent..];
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        let marker = match (digits, rest.get(digits)) {
+            (0, Some(b'-' | b'+')) => Some(indent),
+            (1.., Some(b'.' | b')')) => Some(indent + digits),
+            _ => None,
+        };
+        for (pos, c) in line.char_indices() {
+            if Some(pos) == marker || matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~') {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{ContentHead, MentionElement, RoutingHead, TextElement, SCHEMA_VERSION};
+
+    fn message(msg_time: i64, seq: i64, from_uin: i64, body: Vec<Element>) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_time, msg_seq: seq, ..Default::default() },
+            routing_head: RoutingHead { from_uin, to_uin: 3 - from_uin, ..Default::default() },
+            body,
+        }
+    }
+
+    fn export(db: &Database, format: TextFormat) -> String {
+        let names = SenderNames::new(Some(1), Some("我".to_string()), &["2=对方".to_string()]).unwrap();
+        let options = TextOptions { start: None, end: None, timezone: "+08:00".parse().unwrap(), day_separator: true };
+        let mut out = Vec::new();
+        TextExporter::new(db, &names, format, options).export(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_export() {
+        // 2023-11-15 06:13:20 +08:00
+        const T: i64 = 1700000000;
+        let text = |t: &str| Element::Text(TextElement { text: t.to_string() });
+        let db = Database::new(":memory:").unwrap();
+        db.save_messages(&[
+            message(T, 1, 1, vec![text("今天*开会*")]),
+            message(T + 60, 2, 2, vec![
+                Element::Reply(ReplyElement {
+                    seq: 1,
+                    source: vec![text("今天*开会*")],
+                    content: vec![text("收到 "), Element::Mention(MentionElement { uin: 1, ..Default::default() })],
+                    ..Default::default()
+                }),
+            ]),
+            message(T + 86400, 3, 2, vec![text("- 第一条\n1. 第二条")]),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            export(&db, TextFormat::Txt),
+            "---------------- 2023-11-15 ----------------\n\
+             [2023-11-15 06:13:20] 我: 今天*开会*\n\
+             [2023-11-15 06:14:20] 对方:\n\
+             > 我: 今天*开会*\n\
+             收到 @我\n\
+             ---------------- 2023-11-16 ----------------\n\
+             [2023-11-16 06:13:20] 对方: - 第一条\n\
+             1. 第二条\n"
+        );
+        assert_eq!(
+            export(&db, TextFormat::Markdown),
+            "## 2023-11-15\n\n\
+             **\\[2023-11-15 06:13:20\\] 我:** 今天\\*开会\\*\n\n\
+             **\\[2023-11-15 06:14:20\\] 对方:**\n\
+             > 我: 今天\\*开会\\*  \n\
+             \n\
+             收到 @我\n\n\
+             ## 2023-11-16\n\n\
+             **\\[2023-11-16 06:13:20\\] 对方:** \\- 第一条  \n\
+             1\\. 第二条\n\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_markdown() {
+        assert_eq!(escape_markdown("a_b*c`d"), "a\\_b\\*c\\`d");
+        assert_eq!(escape_markdown("[链接](x) <b>"), "\\[链接\\](x) \\<b\\>");
+        assert_eq!(escape_markdown("- a\n+ b\n  12. c\n3) d"), "\\- a\n\\+ b\n  12\\. c\n3\\) d");
+        assert_eq!(escape_markdown("> 引用\n# 标题"), "\\> 引用\n\\# 标题");
+        // 不在行首的标记保持原样
+        assert_eq!(escape_markdown("a - b 1. c"), "a - b 1. c");
+        assert_eq!(escape_markdown("2024年"), "2024年");
+    }
+}
@@ -23,7 +23,7 @@ impl Helper {
     pub fn gtk(skey: &str) -> i64 {
         let mut hash: i64 = 5381;
         for ch in skey.chars() {
-            hash += (hash << 5 & 2147483647) + (ch as i64) & 2147483647;
+            hash += (((hash << 5) & 2147483647) + (ch as i64)) & 2147483647;
             hash &= 2147483647;
         }
         hash & 2147483647
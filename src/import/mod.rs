@@ -0,0 +1,311 @@
+pub mod ntqq;
+pub mod onebot;
+pub mod qq_pc;
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::database::Database;
+use crate::helper::Helper;
+use crate::profile::Profile;
+use crate::message::{ContentHead, Element, Message, RoutingHead, SCHEMA_VERSION};
+
+/// 从导出文件中解析出的消息，发送者只有名称
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedMessage {
+    pub sender_name: String,
+    /// 导出文件中带有QQ号时为Some
+    pub sender_uin: Option<i64>,
+    pub msg_time: i64,
+    pub body: Vec<Element>,
+}
+
+/// 导入结果
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// 与已有消息重复而跳过的条数
+    pub duplicates: usize,
+    /// 生成的msg_seq与内容不同的消息冲突而跳过的条数
+    pub collisions: usize,
+    /// 写入数据库失败的条数
+    pub failed: usize,
+}
+
+impl fmt::Display for ImportStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "导入 {} 条，重复 {} 条", self.imported, self.duplicates)?;
+        if self.collisions > 0 {
+            write!(f, "，msg_seq冲突 {} 条", self.collisions)?;
+        }
+        if self.failed > 0 {
+            write!(f, "，失败 {} 条", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// 单条消息与数据库比对的结果
+enum Lookup {
+    New,
+    Duplicate,
+    Collision,
+}
+
+/// 将解析出的消息合并到数据库
+///
+/// 导入的消息没有服务端的msg_seq，使用由发送者、时间、内容、媒体文件uuid及同一文件中
+/// 相同消息的序号生成的负数作为msg_seq，重复导入同一文件不会产生重复消息；
+/// 与已拉取的消息（msg_seq为正数）按发送者、时间（±1秒）和文本内容去重，每条已拉取的消息只匹配一次。
+pub struct Importer<'a> {
+    db: &'a Database,
+    self_uin: i64,
+    peer_uin: i64,
+    names: HashMap<String, i64>,
+    uids: HashMap<i64, String>,
+}
+
+impl<'a> Importer<'a> {
+    /// `self_names` 为自己在导出文件中的名称，`overrides` 为 `UIN=名称` 形式的列表
+    pub fn new(db: &'a Database, self_uin: i64, peer_uin: i64, self_names: &[String], overrides: &[String]) -> Result<Self> {
+        let mut names = HashMap::new();
+        for name in self_names {
+            names.insert(name.clone(), self_uin);
+        }
+        for item in overrides {
+            let (uin, name) = item
+                .split_once('=')
+                .with_context(|| format!("名称格式应为 UIN=名称: {}", item))?;
+            let uin = uin.trim().parse::<i64>().with_context(|| format!("无效的uin: {}", uin))?;
+            names.insert(name.trim().to_string(), uin);
+        }
+
+        // uids.json 中有 uin 到 uid 的映射
+        let uids = Profile::current()
+            .load_uids()
+            .into_iter()
+            .filter_map(|(uin, uid)| Some((uin.parse().ok()?, uid)))
+            .collect();
+
+        Ok(Importer { db, self_uin, peer_uin, names, uids })
+    }
+
+    /// 发送者的QQ号，未知名称视为会话对方
+    pub fn resolve_sender(&self, parsed: &ParsedMessage) -> i64 {
+        parsed
+            .sender_uin
+            .or_else(|| self.names.get(parsed.sender_name.trim()).copied())
+            .unwrap_or(self.peer_uin)
+    }
+
+    /// 转换为数据库中的消息
+    ///
+    /// `occurrence` 为同一文件中发送者、时间和文本都相同的消息之前出现的次数，
+    /// 用于区分同一秒内连发的相同消息。
+    pub fn to_message(&self, parsed: &ParsedMessage, occurrence: usize) -> Message {
+        let from_uin = self.resolve_sender(parsed);
+        let to_uin = if from_uin == self.self_uin { self.peer_uin } else { self.self_uin };
+        let hash = fnv1a(dedup_key(from_uin, parsed.msg_time, &parsed.body, occurrence).as_bytes());
+
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead {
+                msg_uid: format!("import:{:016x}", hash),
+                random: 0,
+                client_seq: 0,
+                msg_time: parsed.msg_time,
+                msg_seq: synthetic_seq(hash),
+            },
+            routing_head: RoutingHead {
+                from_uin,
+                to_uin,
+                from_uid: self.uids.get(&from_uin).cloned().unwrap_or_default(),
+                to_uid: self.uids.get(&to_uin).cloned().unwrap_or_default(),
+            },
+            body: parsed.body.clone(),
+        }
+    }
+
+    /// 去重后写入数据库
+    pub fn import(&self, messages: &[ParsedMessage]) -> Result<ImportStats> {
+        let mut seen: HashMap<(i64, i64, String), usize> = HashMap::new();
+        let messages: Vec<Message> = messages
+            .iter()
+            .map(|parsed| {
+                let text = normalize_text(&parsed.body.iter().map(Element::plain_text).collect::<String>());
+                let count = seen.entry((self.resolve_sender(parsed), parsed.msg_time, text)).or_default();
+                let message = self.to_message(parsed, *count);
+                *count += 1;
+                message
+            })
+            .collect();
+        self.import_messages(messages)
+    }
+
+    /// 去重后写入已转换的消息，msg_seq已存在的消息保持不变
+    pub fn import_messages(&self, messages: Vec<Message>) -> Result<ImportStats> {
+        let mut stats = ImportStats::default();
+        let mut pending: Vec<Message> = Vec::new();
+        let mut pending_seqs = HashSet::new();
+        let mut matched = HashSet::new();
+        for message in messages {
+            let seq = message.content_head.msg_seq;
+            let lookup = if pending_seqs.contains(&seq) {
+                let earlier = pending.iter().find(|m| m.content_head.msg_seq == seq);
+                if seq < 0 && earlier.is_some_and(|m| !same_content(m, &message)) {
+                    Lookup::Collision
+                } else {
+                    Lookup::Duplicate
+                }
+            } else {
+                self.lookup(&message, &mut matched)?
+            };
+
+            match lookup {
+                Lookup::New => {
+                    pending_seqs.insert(seq);
+                    pending.push(message);
+                }
+                Lookup::Duplicate => stats.duplicates += 1,
+                Lookup::Collision => {
+                    Helper::echo(
+                        &format!("msg_seq {} 与内容不同的已有消息冲突，已跳过: {}", seq, message.plain_text()),
+                        "yellow",
+                    );
+                    stats.collisions += 1;
+                }
+            }
+        }
+
+        let (success, failed) = self.db.save_messages(&pending)?;
+        stats.imported = success;
+        stats.failed = failed;
+        Ok(stats)
+    }
+
+    /// 与数据库比对，`matched` 记录本次导入中已匹配过的已拉取消息
+    fn lookup(&self, message: &Message, matched: &mut HashSet<i64>) -> Result<Lookup> {
+        let seq = message.content_head.msg_seq;
+        if let Some(stored) = self.db.get_message_by_seq(seq)? {
+            // 正数为服务端的msg_seq，相同即同一条消息；导入生成的msg_seq需比对内容
+            if seq < 0 && !same_content(&stored.message, message) {
+                return Ok(Lookup::Collision);
+            }
+            return Ok(Lookup::Duplicate);
+        }
+
+        let msg_time = message.content_head.msg_time;
+        let text = normalize_text(&message.plain_text());
+        let candidates = self.db.get_messages_by_time_range(msg_time - 1, msg_time + 1)?;
+        let found = candidates.iter().find(|stored| {
+            stored.message.content_head.msg_seq > 0
+                && !matched.contains(&stored.id)
+                && stored.message.routing_head.from_uin == message.routing_head.from_uin
+                && normalize_text(&stored.message.plain_text()) == text
+        });
+        match found {
+            Some(stored) => {
+                matched.insert(stored.id);
+                Ok(Lookup::Duplicate)
+            }
+            None => Ok(Lookup::New),
+        }
+    }
+}
+
+/// 生成导入消息msg_seq的内容，第一次出现且不含媒体的消息与旧版本保持一致
+fn dedup_key(from_uin: i64, msg_time: i64, body: &[Element], occurrence: usize) -> String {
+    let text = normalize_text(&body.iter().map(Element::plain_text).collect::<String>());
+    let mut key = format!("{}\n{}\n{}", from_uin, msg_time, text);
+    for element in body {
+        let file_uuid = match element {
+            Element::Image(image) => &image.file_uuid,
+            Element::Voice(voice) => &voice.file_uuid,
+            Element::Video(video) => &video.file_uuid,
+            _ => continue,
+        };
+        if !file_uuid.is_empty() {
+            key.push('\n');
+            key.push_str(file_uuid);
+        }
+    }
+    if occurrence > 0 {
+        key.push_str(&format!("\n#{}", occurrence));
+    }
+    key
+}
+
+/// 发送者、时间和文本是否相同
+fn same_content(a: &Message, b: &Message) -> bool {
+    a.routing_head.from_uin == b.routing_head.from_uin
+        && a.content_head.msg_time == b.content_head.msg_time
+        && normalize_text(&a.plain_text()) == normalize_text(&b.plain_text())
+}
+
+/// 用于去重比较的文本：去掉空白和方括号，使 `[/微笑]` 与导出文件中的 `/微笑` 一致
+pub fn normalize_text(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && *c != '[' && *c != ']')
+        .collect()
+}
+
+/// 导入消息的msg_seq，始终为负数，不会与服务端的msg_seq冲突
+pub fn synthetic_seq(hash: u64) -> i64 {
+    -((hash >> 1) as i64) - 1
+}
+
+/// 64位FNV-1a哈希，结果在不同平台和版本间保持稳定
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::TextElement;
+
+    fn parsed(name: &str, msg_time: i64, text: &str) -> ParsedMessage {
+        ParsedMessage {
+            sender_name: name.into(),
+            sender_uin: None,
+            msg_time,
+            body: vec![Element::Text(TextElement { text: text.into() })],
+        }
+    }
+
+    #[test]
+    fn test_import_dedup() {
+        let db = Database::new(":memory:").unwrap();
+        let importer = Importer::new(&db, 1, 2, &["me".into()], &[]).unwrap();
+
+        // 已拉取的消息只与导入的一条相同消息匹配
+        let mut server = importer.to_message(&parsed("peer", 1000, "hi"), 0);
+        server.content_head.msg_seq = 100;
+        db.save_message(&server).unwrap();
+
+        let messages = vec![
+            parsed("peer", 1000, "hi"),
+            parsed("peer", 1000, "hi"),
+            parsed("me", 2000, "ok"),
+            parsed("me", 2000, "ok"),
+        ];
+        let stats = importer.import(&messages).unwrap();
+        assert_eq!((stats.imported, stats.duplicates, stats.collisions, stats.failed), (3, 1, 0, 0));
+        assert_eq!(db.get_message_count().unwrap(), 4);
+
+        let stats = importer.import(&messages).unwrap();
+        assert_eq!((stats.imported, stats.duplicates), (0, 4));
+
+        // 导入生成的msg_seq已被内容不同的消息占用
+        let mut other = importer.to_message(&parsed("me", 3000, "a"), 0);
+        other.content_head.msg_seq = importer.to_message(&parsed("me", 3000, "b"), 0).content_head.msg_seq;
+        db.save_message(&other).unwrap();
+        let stats = importer.import(&[parsed("me", 3000, "b")]).unwrap();
+        assert_eq!((stats.imported, stats.collisions), (0, 1));
+    }
+}
@@ -0,0 +1,511 @@
+//! 桌面版NTQQ的本地数据库（解密后的 nt_msg.db）
+//!
+//! 表和列均以数字命名，消息内容（列 40800）为protobuf，其中每个元素为字段 40800 的一项。
+//! 字段号参考社区对NTQQ数据库的分析，未识别的元素类型保留为 `custom` 元素。
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::elem::face_elem::face_name;
+use crate::elem::parser::ElemParser;
+use crate::elem::registry::{ParserRegistry, ELEM_SRC_MSG};
+use crate::elem::ParserInterface;
+use crate::message::{
+    ContentHead, CustomElement, Element, FaceElement, FileInfo, ImageElement, Message,
+    MentionElement, ReplyElement, RoutingHead, TextElement, VideoElement, VoiceElement,
+    SCHEMA_VERSION,
+};
+use crate::protobuf::schema::{Field, FieldKind, Schema};
+use crate::protobuf::view::{FieldValue, MessageView, SchemaView};
+use crate::protobuf::Protobuf;
+
+/// 消息内容中的元素列表
+const NT_ELEMENTS_FIELD: u32 = 40800;
+
+/// 单个元素
+static NT_ELEMENT: Schema = Schema {
+    name: "NtElement",
+    fields: &[
+        Field::optional(45001, "element_id", FieldKind::Int),
+        Field::optional(45002, "element_type", FieldKind::Int),
+        Field::optional(45101, "text", FieldKind::String),
+        Field::optional(45102, "at_type", FieldKind::Int),
+        Field::optional(45103, "at_uin", FieldKind::Int),
+        Field::optional(45105, "at_uid", FieldKind::String),
+        Field::optional(45402, "file_name", FieldKind::String),
+        Field::optional(45405, "file_size", FieldKind::Int),
+        Field::optional(45406, "md5", FieldKind::Bytes),
+        Field::optional(45411, "width", FieldKind::Int),
+        Field::optional(45412, "height", FieldKind::Int),
+        Field::optional(45503, "file_uuid", FieldKind::String),
+        Field::optional(45906, "duration", FieldKind::Int),
+        Field::optional(47401, "reply_msg_id", FieldKind::Int),
+        Field::optional(47402, "reply_msg_seq", FieldKind::Int),
+        Field::optional(47403, "reply_sender_uid", FieldKind::String),
+        Field::optional(47601, "face_index", FieldKind::Int),
+        Field::optional(47602, "face_text", FieldKind::String),
+    ],
+};
+
+/// 元素类型
+const NT_TEXT: u64 = 1;
+const NT_PIC: u64 = 2;
+const NT_PTT: u64 = 4;
+const NT_VIDEO: u64 = 5;
+const NT_FACE: u64 = 6;
+const NT_REPLY: u64 = 7;
+
+/// @类型
+const AT_ALL: u64 = 1;
+
+/// 会话类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NtChat {
+    /// 私聊，按对方QQ号筛选
+    C2c(i64),
+    /// 群聊，按群号筛选
+    Group(i64),
+}
+
+impl NtChat {
+    fn table(&self) -> &'static str {
+        match self {
+            NtChat::C2c(_) => "c2c_msg_table",
+            NtChat::Group(_) => "group_msg_table",
+        }
+    }
+
+    /// 会话对象所在的列
+    fn peer_column(&self) -> &'static str {
+        match self {
+            NtChat::C2c(_) => "40030",
+            NtChat::Group(_) => "40027",
+        }
+    }
+
+    fn peer(&self) -> i64 {
+        match self {
+            NtChat::C2c(uin) | NtChat::Group(uin) => *uin,
+        }
+    }
+}
+
+/// 解密后的NTQQ数据库
+pub struct NtqqDatabase {
+    conn: Connection,
+    /// 去掉文件头后生成的临时文件
+    temp_file: Option<PathBuf>,
+}
+
+impl NtqqDatabase {
+    /// 以只读方式打开
+    ///
+    /// NTQQ的数据库文件前有1024字节的自定义文件头，解密后的副本若仍带有该文件头，
+    /// 会复制到临时目录中新建的文件（权限0600，关闭时删除）并去掉文件头后再打开。
+    pub fn open(path: &Path) -> Result<Self> {
+        const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+        const NT_HEADER_SIZE: usize = 1024;
+
+        let mut file = fs::File::open(path).with_context(|| format!("无法读取 {}", path.display()))?;
+        let mut head = Vec::with_capacity(NT_HEADER_SIZE + SQLITE_HEADER.len());
+        (&mut file)
+            .take((NT_HEADER_SIZE + SQLITE_HEADER.len()) as u64)
+            .read_to_end(&mut head)?;
+
+        let (open_path, temp_file) = if head.starts_with(SQLITE_HEADER) {
+            (path.to_path_buf(), None)
+        } else if head.len() > NT_HEADER_SIZE && head[NT_HEADER_SIZE..].starts_with(SQLITE_HEADER) {
+            file.seek(SeekFrom::Start(NT_HEADER_SIZE as u64))?;
+            let temp = copy_to_temp(&mut file)?;
+            (temp.clone(), Some(temp))
+        } else {
+            bail!("{} 不是SQLite数据库，请先使用数据库密钥解密", path.display());
+        };
+
+        let conn = Connection::open_with_flags(&open_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(NtqqDatabase { conn, temp_file })
+    }
+
+    /// 读取会话中的全部消息（按时间升序），`self_uin` 用于私聊时确定接收者
+    pub fn read_messages(&self, chat: NtChat, self_uin: i64) -> Result<Vec<Message>> {
+        let table = chat.table();
+        let columns = self.columns(table)?;
+        for required in ["40003", "40033", "40050", "40800", chat.peer_column()] {
+            if !columns.iter().any(|c| c == required) {
+                bail!("{} 缺少列 {}，可能不是支持的NTQQ版本", table, required);
+            }
+        }
+        let optional = |name: &'static str| {
+            if columns.iter().any(|c| c == name) {
+                format!("\"{}\"", name)
+            } else {
+                "NULL".to_string()
+            }
+        };
+
+        let sql = format!(
+            "SELECT \"40003\", \"40033\", \"40050\", \"40800\", {}, {}, {}, {} \
+             FROM {} WHERE \"{}\" = ?1 ORDER BY \"40050\" ASC, \"40003\" ASC",
+            optional("40001"),
+            optional("40002"),
+            optional("40020"),
+            optional("40021"),
+            table,
+            chat.peer_column(),
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = stmt.query([chat.peer()])?;
+
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next()? {
+            let content: Option<Vec<u8>> = row.get(3)?;
+            let body = content.map(|c| parse_elements(&c)).unwrap_or_default();
+            if body.is_empty() {
+                continue;
+            }
+
+            let from_uin: i64 = row.get::<_, Option<i64>>(1)?.unwrap_or(0);
+            let to_uin = match chat {
+                NtChat::C2c(peer) if from_uin == peer => self_uin,
+                NtChat::C2c(peer) | NtChat::Group(peer) => peer,
+            };
+            messages.push(Message {
+                version: SCHEMA_VERSION,
+                content_head: ContentHead {
+                    msg_uid: row.get::<_, Option<i64>>(4)?.map(|id| id.to_string()).unwrap_or_default(),
+                    random: row.get::<_, Option<i64>>(5)?.unwrap_or(0),
+                    client_seq: 0,
+                    msg_time: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                    msg_seq: row.get::<_, Option<i64>>(0)?.unwrap_or(0),
+                },
+                routing_head: RoutingHead {
+                    from_uin,
+                    to_uin,
+                    from_uid: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+                    to_uid: if matches!(chat, NtChat::C2c(peer) if from_uin != peer) {
+                        row.get::<_, Option<String>>(7)?.unwrap_or_default()
+                    } else {
+                        String::new()
+                    },
+                },
+                body,
+            });
+        }
+
+        fill_reply_sources(&mut messages);
+        Ok(messages)
+    }
+
+    fn columns(&self, table: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .map_err(|e| anyhow!("无法读取表 {}: {}", table, e))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        if columns.is_empty() {
+            bail!("数据库中没有表 {}", table);
+        }
+        Ok(columns)
+    }
+}
+
+impl Drop for NtqqDatabase {
+    fn drop(&mut self) {
+        if let Some(temp) = &self.temp_file {
+            fs::remove_file(temp).ok();
+        }
+    }
+}
+
+/// 注册表中NTQQ元素的键为 `ntqq_<element_type>`，回复元素为 [`ELEM_SRC_MSG`]
+pub const ELEM_NT_PREFIX: &str = "ntqq_";
+/// 类型未注册的元素使用的键，保留解码结果
+pub const ELEM_NT_OTHER: &str = "ntqq_other";
+
+/// NTQQ元素的解析器注册表
+///
+/// 元素以 `{"element_type": 类型, "bytes": 元素protobuf的base64}` 的形式交给解析器。
+pub fn nt_registry() -> Arc<ParserRegistry> {
+    static REGISTRY: OnceLock<Arc<ParserRegistry>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(|| {
+            let mut registry = ParserRegistry::new();
+            registry.register(&nt_key(NT_TEXT), Arc::new(NtTextElem));
+            registry.register(&nt_key(NT_PIC), Arc::new(NtMediaElem));
+            registry.register(&nt_key(NT_PTT), Arc::new(NtMediaElem));
+            registry.register(&nt_key(NT_VIDEO), Arc::new(NtMediaElem));
+            registry.register(&nt_key(NT_FACE), Arc::new(NtFaceElem));
+            registry.register(ELEM_SRC_MSG, Arc::new(NtReplyElem));
+            registry.register(ELEM_NT_OTHER, Arc::new(NtOtherElem));
+            Arc::new(registry)
+        })
+        .clone()
+}
+
+fn nt_key(element_type: u64) -> String {
+    format!("{}{}", ELEM_NT_PREFIX, element_type)
+}
+
+/// 解析消息内容（列 40800）
+///
+/// 每个元素按类型交给 [`nt_registry`] 中的解析器，回复的内容与漫游消息一致，由 [`ElemParser`] 放在回复元素中。
+pub fn parse_elements(content: &[u8]) -> Vec<Element> {
+    let registry = nt_registry();
+    let elems = MessageView::new(content)
+        .get_all(NT_ELEMENTS_FIELD)
+        .filter_map(|value| {
+            let FieldValue::Bytes(data) = value else {
+                return None;
+            };
+            let element_type = SchemaView::new(data, &NT_ELEMENT).uint("element_type")?;
+            let key = if element_type == NT_REPLY { ELEM_SRC_MSG.to_string() } else { nt_key(element_type) };
+            let key = if registry.contains(&key) { key } else { ELEM_NT_OTHER.to_string() };
+            Some(json!({ key: { "element_type": element_type, "bytes": general_purpose::STANDARD.encode(data) } }))
+        })
+        .collect();
+    ElemParser::with_registry(elems, registry).build()
+}
+
+/// 解析器收到的元素protobuf
+fn element_bytes(data: &Value) -> Option<Vec<u8>> {
+    general_purpose::STANDARD.decode(data.get("bytes")?.as_str()?).ok()
+}
+
+fn view_string(view: &SchemaView, name: &str) -> String {
+    view.string(name).unwrap_or("").to_string()
+}
+
+/// 文本和@
+struct NtTextElem;
+
+impl ParserInterface for NtTextElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let bytes = element_bytes(data)?;
+        let view = SchemaView::new(&bytes, &NT_ELEMENT);
+        let text = view_string(&view, "text");
+        let element = match view.uint("at_type").unwrap_or(0) {
+            0 if text.is_empty() => return None,
+            0 => Element::Text(TextElement { text }),
+            at_type => Element::Mention(MentionElement {
+                uin: view.uint("at_uin").unwrap_or(0) as i64,
+                uid: view_string(&view, "at_uid"),
+                name: text.strip_prefix('@').unwrap_or(&text).to_string(),
+                all: at_type == AT_ALL,
+            }),
+        };
+        Some(element)
+    }
+}
+
+/// 图片、语音和视频，本地数据库中没有下载链接
+struct NtMediaElem;
+
+impl ParserInterface for NtMediaElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let bytes = element_bytes(data)?;
+        let view = SchemaView::new(&bytes, &NT_ELEMENT);
+        let duration = view.uint("duration").unwrap_or(0) as u32;
+        let file = FileInfo {
+            size: view.uint("file_size").unwrap_or(0),
+            md5: view.bytes("md5").map(hex).unwrap_or_default(),
+            file_name: view_string(&view, "file_name"),
+            width: view.uint("width").unwrap_or(0) as u32,
+            height: view.uint("height").unwrap_or(0) as u32,
+            duration,
+            ..Default::default()
+        };
+        let file_uuid = view_string(&view, "file_uuid");
+
+        let element = match view.uint("element_type")? {
+            NT_PIC => Element::Image(ImageElement { file, file_uuid, ..Default::default() }),
+            NT_PTT => Element::Voice(VoiceElement { duration, file, file_uuid, ..Default::default() }),
+            NT_VIDEO => Element::Video(VideoElement { duration, file, file_uuid, ..Default::default() }),
+            _ => return None,
+        };
+        Some(element)
+    }
+}
+
+/// 表情，缺少名称时按id查找
+struct NtFaceElem;
+
+impl ParserInterface for NtFaceElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let bytes = element_bytes(data)?;
+        let view = SchemaView::new(&bytes, &NT_ELEMENT);
+        let id = view.uint("face_index").unwrap_or(0) as u32;
+        let name = view_string(&view, "face_text");
+        let name = name.trim_start_matches('/');
+        Some(Element::Face(FaceElement {
+            id,
+            name: if name.is_empty() { face_name(id) } else { name }.to_string(),
+        }))
+    }
+}
+
+/// 回复，被回复的内容之后由 [`fill_reply_sources`] 补充
+struct NtReplyElem;
+
+impl ParserInterface for NtReplyElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let bytes = element_bytes(data)?;
+        let view = SchemaView::new(&bytes, &NT_ELEMENT);
+        Some(Element::Reply(ReplyElement {
+            seq: view.uint("reply_msg_seq").unwrap_or(0) as i64,
+            from_uid: view_string(&view, "reply_sender_uid"),
+            ..Default::default()
+        }))
+    }
+}
+
+/// 其他类型保留解码结果，便于之后补充解析
+struct NtOtherElem;
+
+impl ParserInterface for NtOtherElem {
+    fn parse(&self, data: &Value, _full_elem: Option<&Value>) -> Option<Element> {
+        let element_type = data.get("element_type")?.as_u64()?;
+        let fields = Protobuf::deserialize(&element_bytes(data)?).ok()?;
+        let data = fields
+            .into_iter()
+            .map(|(number, value)| (number.to_string(), value))
+            .collect::<serde_json::Map<String, Value>>();
+        Some(Element::Custom(CustomElement {
+            kind: nt_key(element_type),
+            data: Value::Object(data),
+        }))
+    }
+}
+
+/// 被回复的消息内容从同一批消息中按msg_seq查找
+fn fill_reply_sources(messages: &mut [Message]) {
+    let by_seq: HashMap<i64, Vec<Element>> = messages
+        .iter()
+        .map(|m| (m.content_head.msg_seq, m.body.clone()))
+        .collect();
+    for message in messages.iter_mut() {
+        for elem in &mut message.body {
+            if let Element::Reply(reply) = elem {
+                if let Some(source) = by_seq.get(&reply.seq) {
+                    reply.source = source.clone();
+                }
+            }
+        }
+    }
+}
+
+/// 复制到临时目录中新建的文件，只有本用户可读写
+fn copy_to_temp(source: &mut fs::File) -> Result<PathBuf> {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    for _ in 0..16 {
+        let temp = std::env::temp_dir().join(format!(
+            "qqhistory-nt-{}-{:08x}-{}.db",
+            std::process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        match options.open(&temp) {
+            Ok(mut file) => {
+                let copied = io::copy(source, &mut file).and_then(|_| file.sync_all());
+                if let Err(e) = copied {
+                    fs::remove_file(&temp).ok();
+                    return Err(e).with_context(|| format!("无法写入临时文件 {}", temp.display()));
+                }
+                return Ok(temp);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("无法创建临时文件 {}", temp.display())),
+        }
+    }
+    bail!("无法在 {} 中创建临时文件", std::env::temp_dir().display())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_elements() {
+        let mut content = HashMap::new();
+        content.insert(
+            NT_ELEMENTS_FIELD,
+            json!([
+                {"45002": 7, "47402": 99},
+                {"45002": 1, "45101": "你好"},
+                {"45002": 1, "45101": "@张三", "45102": 2, "45103": 10001},
+                {"45002": 6, "47601": 14},
+            ]),
+        );
+        let content = Protobuf::serialize(&content).unwrap();
+
+        let elems = parse_elements(&content);
+        let Element::Reply(reply) = &elems[0] else {
+            panic!("expected reply, got {:?}", elems);
+        };
+        assert_eq!(reply.seq, 99);
+        assert_eq!(
+            reply.content,
+            vec![
+                Element::Text(TextElement { text: "你好".to_string() }),
+                Element::Mention(MentionElement {
+                    uin: 10001,
+                    uid: String::new(),
+                    name: "张三".to_string(),
+                    all: false,
+                }),
+                Element::Face(FaceElement { id: 14, name: "微笑".to_string() }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_with_header() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-ntopen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let plain = dir.join("plain.db");
+        Connection::open(&plain).unwrap().execute_batch("CREATE TABLE t (a INTEGER)").unwrap();
+        let mut data = vec![0u8; 1024];
+        data.extend(fs::read(&plain).unwrap());
+        let nt = dir.join("nt_msg.db");
+        fs::write(&nt, data).unwrap();
+
+        let db = NtqqDatabase::open(&nt).unwrap();
+        let temp = db.temp_file.clone().unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&temp).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(db.columns("t").unwrap(), vec!["a".to_string()]);
+        drop(db);
+        assert!(!temp.exists());
+
+        fs::write(&nt, b"not a database").unwrap();
+        assert!(NtqqDatabase::open(&nt).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use crate::import::ParsedMessage;
+use crate::message::value_to_i64;
+use crate::onebot::{MessageFormat, Segment};
+
+/// 读取OneBot消息的JSON Lines文件
+///
+/// 每行需要 `time`、`user_id` 和 `message`，`message` 可以是v11/v12消息段数组或CQ码字符串，
+/// 未指定 `format` 时逐行判断。回复段的id按msg_seq处理，与 `export onebot` 的输出一致。
+/// 媒体只保留http(s)链接，本地路径不会被导入。
+pub fn parse_file(path: &Path, format: Option<MessageFormat>) -> Result<Vec<ParsedMessage>> {
+    let content = fs::read_to_string(path).with_context(|| format!("无法读取 {}", path.display()))?;
+    let mut messages = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).with_context(|| format!("第 {} 行不是有效的JSON", i + 1))?;
+        messages.push(parse_message(&value, format).with_context(|| format!("第 {} 行格式错误", i + 1))?);
+    }
+    Ok(messages)
+}
+
+/// 解析单条消息
+pub fn parse_message(value: &Value, format: Option<MessageFormat>) -> Result<ParsedMessage> {
+    let msg_time = value.get("time").map(value_to_i64).context("缺少time")?;
+    let sender_uin = value
+        .get("user_id")
+        .or_else(|| value.pointer("/sender/user_id"))
+        .map(value_to_i64)
+        .filter(|uin| *uin != 0);
+    let sender_name = value
+        .pointer("/sender/nickname")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string();
+
+    let message = value.get("message").context("缺少message")?;
+    let format = format
+        .or_else(|| MessageFormat::detect(message))
+        .context("message应为消息段数组或CQ码字符串")?;
+    let segments = format.parse(message);
+    let body = Segment::to_elements(&segments, &mut |id| id.trim().parse().ok());
+
+    Ok(ParsedMessage { sender_name, sender_uin, msg_time, body })
+}
@@ -0,0 +1,584 @@
+//! QQ PC版“导出消息记录”生成的 .txt / .mht 文件
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{NaiveDate, NaiveTime};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::export::{media_file_name, ExportTimezone, LOCAL_MEDIA_PREFIX};
+use crate::import::ParsedMessage;
+use crate::message::{Element, FileInfo, ImageElement, TextElement};
+
+/// 解析结果
+#[derive(Debug, Default)]
+pub struct PcExport {
+    /// 文件头中的“消息对象”
+    pub peer_name: Option<String>,
+    pub messages: Vec<ParsedMessage>,
+    /// 提取到磁盘的图片数
+    pub images: usize,
+}
+
+/// 按扩展名解析 .txt 或 .mht/.mhtml 文件，MHT中的图片保存到 `media_root/<conversation>`
+pub fn parse_file(path: &Path, timezone: ExportTimezone, media_root: &Path, conversation: &str) -> Result<PcExport> {
+    let content = read_text_file(path)?;
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "txt" => parse_txt(&content, timezone),
+        "mht" | "mhtml" => parse_mht(&content, timezone, media_root, conversation),
+        _ => bail!("不支持的文件类型: {}，应为 .txt 或 .mht", path.display()),
+    }
+}
+
+/// 读取文本文件，支持UTF-8（可带BOM）和带BOM的UTF-16LE
+pub fn read_text_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("无法读取 {}", path.display()))?;
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Ok(String::from_utf8_lossy(rest).into_owned());
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        return Ok(String::from_utf16_lossy(&units));
+    }
+    String::from_utf8(bytes).map_err(|_| {
+        anyhow!("{} 不是UTF-8或UTF-16编码，GBK等编码请先转换为UTF-8", path.display())
+    })
+}
+
+/// 解析 .txt 格式
+///
+/// ```text
+/// 消息对象:张三
+/// ================================================================
+///
+/// 2016-03-05 12:34:56 张三
+/// 你好[图片]
+/// ```
+pub fn parse_txt(content: &str, timezone: ExportTimezone) -> Result<PcExport> {
+    let mut export = PcExport::default();
+    let mut current: Option<(ParsedMessage, Vec<&str>)> = None;
+
+    let finish = |current: Option<(ParsedMessage, Vec<&str>)>, export: &mut PcExport| {
+        if let Some((mut message, mut lines)) = current {
+            while lines.last().is_some_and(|l| l.trim().is_empty()) {
+                lines.pop();
+            }
+            message.body = text_to_elements(&lines.join("\n"));
+            if !message.body.is_empty() {
+                export.messages.push(message);
+            }
+        }
+    };
+
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        if current.is_none() {
+            if let Some(name) = line.strip_prefix("消息对象:") {
+                export.peer_name = Some(name.trim().to_string());
+                continue;
+            }
+        }
+        if let Some(message) = parse_txt_header(line, timezone) {
+            finish(current.take(), &mut export);
+            current = Some((message, Vec::new()));
+        } else if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    finish(current, &mut export);
+
+    Ok(export)
+}
+
+/// 解析 `2016-03-05 12:34:56 发送者` 形式的消息头
+fn parse_txt_header(line: &str, timezone: ExportTimezone) -> Option<ParsedMessage> {
+    let mut parts = line.splitn(3, ' ');
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let time = NaiveTime::parse_from_str(parts.next()?, "%H:%M:%S").ok()?;
+    let sender = parts.next()?.trim();
+    if sender.is_empty() {
+        return None;
+    }
+
+    let msg_time = timezone
+        .parse_time(&format!("{} {}", date, time.format("%H:%M:%S")), false)
+        .ok()?;
+    let (sender_name, sender_uin) = split_sender(sender);
+    Some(ParsedMessage {
+        sender_name,
+        sender_uin,
+        msg_time,
+        body: Vec::new(),
+    })
+}
+
+/// 文本中的 `[图片]` 转为图片元素（txt格式不含图片文件）
+fn text_to_elements(text: &str) -> Vec<Element> {
+    let mut elems = Vec::new();
+    for (i, part) in text.split("[图片]").enumerate() {
+        if i > 0 {
+            elems.push(Element::Image(ImageElement::default()));
+        }
+        if !part.is_empty() {
+            elems.push(Element::Text(TextElement { text: part.to_string() }));
+        }
+    }
+    elems
+}
+
+/// 发送者中的QQ号，如 `张三(10001)`、`张三<10001@qq.com>`
+fn split_sender(sender: &str) -> (String, Option<i64>) {
+    for (open, close) in [('(', ')'), ('<', '>')] {
+        if let Some(inner) = sender.strip_suffix(close) {
+            if let Some(pos) = inner.rfind(open) {
+                let id = &inner[pos + open.len_utf8()..];
+                let id = id.strip_suffix("@qq.com").unwrap_or(id);
+                if let Ok(uin) = id.parse::<i64>() {
+                    return (inner[..pos].trim().to_string(), Some(uin));
+                }
+            }
+        }
+    }
+    (sender.to_string(), None)
+}
+
+/// 已保存的内嵌图片
+struct SavedImage {
+    file_name: String,
+    file_uuid: String,
+    url: String,
+    size: u64,
+}
+
+/// 解析 .mht 格式：MIME multipart/related，第一部分为HTML，其余为内嵌图片
+///
+/// 图片保存到 `media_root/<conversation>`，元素的链接为相对 `media_root` 的 `local:` 链接。
+pub fn parse_mht(content: &str, timezone: ExportTimezone, media_root: &Path, conversation: &str) -> Result<PcExport> {
+    let boundary = mime_boundary(content).context("未找到MIME boundary，不是有效的MHT文件")?;
+    let delimiter = format!("--{}", boundary);
+
+    let mut html = None;
+    let mut images = HashMap::new();
+    for part in content.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+        let Some((headers, body)) = split_headers(part) else {
+            continue;
+        };
+        let header = |name: &str| {
+            headers.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+            })
+        };
+        let content_type = header("Content-Type").unwrap_or_default().to_ascii_lowercase();
+        let encoding = header("Content-Transfer-Encoding").unwrap_or_default().to_ascii_lowercase();
+        let data = decode_transfer(body, &encoding)?;
+
+        if content_type.starts_with("text/html") && html.is_none() {
+            html = Some(String::from_utf8_lossy(&data).into_owned());
+        } else if content_type.starts_with("image/") {
+            let Some(location) = header("Content-Location") else {
+                continue;
+            };
+            if let Some(image) = save_image(&location, &content_type, &data, media_root, conversation)? {
+                images.insert(location, image);
+            }
+        }
+    }
+
+    let html = html.context("MHT文件中没有HTML内容")?;
+    let mut export = parse_mht_html(&html, timezone, &images)?;
+    export.images = images.len();
+    Ok(export)
+}
+
+/// 顶层头部中的boundary
+fn mime_boundary(content: &str) -> Option<String> {
+    let pos = find_ci(content, "boundary=", 0)?;
+    let rest = &content[pos + "boundary=".len()..];
+    let value = match rest.strip_prefix('"') {
+        Some(quoted) => &quoted[..quoted.find('"')?],
+        None => rest.split(|c: char| c.is_whitespace() || c == ';').next()?,
+    };
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// 拆分MIME部分的头部和正文
+fn split_headers(part: &str) -> Option<(&str, &str)> {
+    let part = part.trim_start_matches(['\r', '\n']);
+    if let Some(pos) = part.find("\r\n\r\n") {
+        return Some((&part[..pos], &part[pos + 4..]));
+    }
+    part.find("\n\n").map(|pos| (&part[..pos], &part[pos + 2..]))
+}
+
+/// 按 Content-Transfer-Encoding 解码
+fn decode_transfer(body: &str, encoding: &str) -> Result<Vec<u8>> {
+    match encoding {
+        "base64" => {
+            let compact: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+            Ok(general_purpose::STANDARD.decode(compact)?)
+        }
+        "quoted-printable" => Ok(decode_quoted_printable(body)),
+        _ => Ok(body.as_bytes().to_vec()),
+    }
+}
+
+fn decode_quoted_printable(body: &str) -> Vec<u8> {
+    let bytes = body.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'=' {
+            // 软换行
+            if bytes[i + 1..].starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if bytes[i + 1..].starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// 保存内嵌图片，文件名与导出时的媒体文件名规则一致
+fn save_image(
+    location: &str,
+    content_type: &str,
+    data: &[u8],
+    media_root: &Path,
+    conversation: &str,
+) -> Result<Option<SavedImage>> {
+    let ext = match content_type.split(';').next().unwrap_or("").trim() {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/webp" => "webp",
+        _ => "jpg",
+    };
+    let file_uuid = Path::new(location)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(location)
+        .trim_matches(['{', '}'])
+        .to_ascii_lowercase();
+    let Some(file_name) = media_file_name("", &file_uuid, "", ext) else {
+        return Ok(None);
+    };
+
+    let media_dir = media_root.join(conversation);
+    fs::create_dir_all(&media_dir)?;
+    fs::write(media_dir.join(&file_name), data)?;
+
+    Ok(Some(SavedImage {
+        url: format!("{}{}/{}", LOCAL_MEDIA_PREFIX, conversation, file_name),
+        file_name,
+        file_uuid,
+        size: data.len() as u64,
+    }))
+}
+
+/// 解析MHT中的HTML表格
+///
+/// 日期行为 `日期: 2016-03-05`，消息行的头部为
+/// `<div style=float:left;margin-right:6px;>发送者</div>12:34:56</div>`，其后为消息内容。
+fn parse_mht_html(html: &str, timezone: ExportTimezone, images: &HashMap<String, SavedImage>) -> Result<PcExport> {
+    const SENDER_MARKER: &str = "float:left;margin-right:6px;>";
+
+    let mut export = PcExport::default();
+    let mut date: Option<NaiveDate> = None;
+
+    let mut pos = 0;
+    let mut rows = Vec::new();
+    while let Some(start) = find_ci(html, "<tr", pos) {
+        let end = find_ci(html, "<tr", start + 3).unwrap_or(html.len());
+        rows.push(&html[start..end]);
+        pos = end;
+    }
+
+    for row in rows {
+        let Some(marker) = row.find(SENDER_MARKER) else {
+            let text = decode_entities(&strip_tags(row));
+            let text = text.trim();
+            if let Some(value) = text.strip_prefix("日期:") {
+                date = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok();
+            } else if let Some(name) = text.strip_prefix("消息对象:") {
+                export.peer_name = Some(name.trim().to_string());
+            }
+            continue;
+        };
+
+        let after = &row[marker + SENDER_MARKER.len()..];
+        let Some(sender_end) = find_ci(after, "</div>", 0) else {
+            continue;
+        };
+        let sender = decode_entities(&strip_tags(&after[..sender_end]));
+        let after = &after[sender_end + "</div>".len()..];
+        let Some(time_end) = find_ci(after, "</div>", 0) else {
+            continue;
+        };
+        let time = decode_entities(&strip_tags(&after[..time_end]));
+        let time = time.trim();
+        let content = &after[time_end + "</div>".len()..];
+
+        // 消息时间只有时分秒时使用最近的日期行
+        let datetime = if time.contains(' ') {
+            time.to_string()
+        } else {
+            let date = date.context("消息出现在日期行之前")?;
+            format!("{} {}", date, time)
+        };
+        let Ok(msg_time) = timezone.parse_time(&datetime, false) else {
+            continue;
+        };
+
+        let body = html_to_elements(content, images);
+        if body.is_empty() {
+            continue;
+        }
+        let (sender_name, sender_uin) = split_sender(sender.trim());
+        export.messages.push(ParsedMessage {
+            sender_name,
+            sender_uin,
+            msg_time,
+            body,
+        });
+    }
+
+    Ok(export)
+}
+
+/// 将消息内容的HTML转换为元素：文本、换行和内嵌图片
+fn html_to_elements(html: &str, images: &HashMap<String, SavedImage>) -> Vec<Element> {
+    let mut elems = Vec::new();
+    let mut text = String::new();
+    let flush = |text: &mut String, elems: &mut Vec<Element>| {
+        if !text.is_empty() {
+            elems.push(Element::Text(TextElement { text: std::mem::take(text) }));
+        }
+    };
+
+    let mut rest = html;
+    while let Some(lt) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..lt]));
+        let Some(gt) = rest[lt..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[lt + 1..lt + gt];
+        rest = &rest[lt + gt + 1..];
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "br" => text.push('\n'),
+            "img" => {
+                flush(&mut text, &mut elems);
+                let image = tag_attr(tag, "src")
+                    .and_then(|src| images.get(&src))
+                    .map(|saved| ImageElement {
+                        url: saved.url.clone(),
+                        file: FileInfo {
+                            size: saved.size,
+                            file_name: saved.file_name.clone(),
+                            ..Default::default()
+                        },
+                        file_uuid: saved.file_uuid.clone(),
+                        ..Default::default()
+                    })
+                    .unwrap_or_default();
+                elems.push(Element::Image(image));
+            }
+            _ => {}
+        }
+    }
+    text.push_str(&decode_entities(rest));
+    flush(&mut text, &mut elems);
+
+    // 去掉首尾空白
+    if let Some(Element::Text(t)) = elems.first_mut() {
+        t.text = t.text.trim_start().to_string();
+    }
+    if let Some(Element::Text(t)) = elems.last_mut() {
+        t.text = t.text.trim_end().to_string();
+    }
+    elems.retain(|e| !matches!(e, Element::Text(t) if t.text.is_empty()));
+    elems
+}
+
+/// 读取标签属性值
+fn tag_attr(tag: &str, name: &str) -> Option<String> {
+    let pos = find_ci(tag, &format!("{}=", name), 0)?;
+    let rest = &tag[pos + name.len() + 1..];
+    let value = match rest.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let inner = &rest[1..];
+            &inner[..inner.find(quote)?]
+        }
+        _ => rest.split(|c: char| c.is_whitespace() || c == '>').next()?,
+    };
+    Some(decode_entities(value))
+}
+
+/// 去掉所有标签
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// 解码HTML实体
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let decoded = entity.and_then(|entity| match entity {
+            "nbsp" => Some(' '),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let num = entity.strip_prefix('#')?;
+                let code = match num.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => num.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (decoded, entity) {
+            (Some(c), Some(entity)) => {
+                out.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 忽略ASCII大小写查找，`needle` 须为ASCII
+fn find_ci(haystack: &str, needle: &str, from: usize) -> Option<usize> {
+    let hay = haystack.as_bytes();
+    let needle = needle.as_bytes();
+    if needle.is_empty() || hay.len() < needle.len() {
+        return None;
+    }
+    (from..=hay.len() - needle.len()).find(|&i| hay[i..i + needle.len()].eq_ignore_ascii_case(needle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc() -> ExportTimezone {
+        "utc".parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_txt() {
+        let content = "消息记录（此消息记录为文本格式，不支持重新导入）\n\n\
+            ================================================================\n\
+            消息对象:张三\n\
+            ================================================================\n\n\
+            2016-03-05 9:04:56 张三\n你好\n\n第二行[图片]\n\n\
+            2016-03-05 09:05:10 李四(10002)\nhi\n\n";
+        let export = parse_txt(content, utc()).unwrap();
+        assert_eq!(export.peer_name.as_deref(), Some("张三"));
+        assert_eq!(export.messages.len(), 2);
+
+        let first = &export.messages[0];
+        assert_eq!(first.sender_name, "张三");
+        assert_eq!(first.msg_time, 1457168696);
+        assert_eq!(
+            first.body,
+            vec![
+                Element::Text(TextElement { text: "你好\n\n第二行".to_string() }),
+                Element::Image(ImageElement::default()),
+            ]
+        );
+        assert_eq!(export.messages[1].sender_uin, Some(10002));
+    }
+
+    #[test]
+    fn test_parse_mht() {
+        let html = "<html><body><table>\
+            <tr><td><div style=padding-left:10px;>消息对象:张三</div></td></tr>\
+            <tr><td style=color:#3568BB;>日期: 2016-03-05</td></tr>\
+            <tr><td><div style=color:#42B475;padding-left:10px;><div style=float:left;margin-right:6px;>张三</div>9:04:56</div>\
+            <div style=padding-left:20px;><font>a &lt; b<br>c</font><IMG src=\"{ABC-123}.dat\"></div></td></tr>\
+            </table></body></html>";
+        let image = general_purpose::STANDARD.encode(b"\x89PNG");
+        let content = format!(
+            "From: <Saved by QQ>\r\nContent-Type: multipart/related;\r\n\tboundary=\"----=_NextPart_01\"\r\n\r\n\
+             ------=_NextPart_01\r\nContent-Type:text/html\r\nContent-Transfer-Encoding:7bit\r\n\r\n{}\r\n\
+             ------=_NextPart_01\r\nContent-Type:image/png\r\nContent-Transfer-Encoding:base64\r\nContent-Location:{{ABC-123}}.dat\r\n\r\n{}\r\n\
+             ------=_NextPart_01--\r\n",
+            html, image
+        );
+
+        let dir = std::env::temp_dir().join(format!("qqhistory-mht-{}", std::process::id()));
+        let export = parse_mht(&content, utc(), &dir, "10001").unwrap();
+        assert_eq!(export.peer_name.as_deref(), Some("张三"));
+        assert_eq!(export.images, 1);
+        assert_eq!(export.messages.len(), 1);
+
+        let message = &export.messages[0];
+        assert_eq!(message.msg_time, 1457168696);
+        assert_eq!(message.body[0], Element::Text(TextElement { text: "a < b\nc".to_string() }));
+        match &message.body[1] {
+            Element::Image(image) => {
+                assert_eq!(image.file_uuid, "abc-123");
+                assert_eq!(image.file.file_name, "abc-123.png");
+                assert_eq!(image.url, "local:10001/abc-123.png");
+                assert_eq!(fs::read(dir.join("10001/abc-123.png")).unwrap(), b"\x89PNG");
+            }
+            other => panic!("unexpected element {:?}", other),
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+}
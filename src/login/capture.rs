@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::Value;
+use std::io::Read;
+use std::str::FromStr;
+use crate::cookie::Cookie;
+use crate::login::tnetstring::{self, TnetValue};
+
+/// 换取票据的接口
+pub const EXCHANGE_HOST: &str = "ntlogin.qq.com";
+pub const EXCHANGE_PATH: &str = "/wxmini/login/exchange";
+
+/// 抓包文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// 浏览器或Charles、Fiddler导出的HAR
+    Har,
+    /// mitmproxy/mitmdump 保存的流量文件
+    Mitmproxy,
+    /// 原始HTTP响应，或只有响应体的JSON
+    Http,
+}
+
+impl FromStr for CaptureFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "har" => Ok(CaptureFormat::Har),
+            "mitmproxy" | "flow" => Ok(CaptureFormat::Mitmproxy),
+            "http" | "raw" => Ok(CaptureFormat::Http),
+            _ => Err(format!("未知的抓包格式: {}，可选 har、mitmproxy、http", s)),
+        }
+    }
+}
+
+impl CaptureFormat {
+    /// 根据文件内容判断格式
+    pub fn detect(data: &[u8]) -> CaptureFormat {
+        let head = data.trim_ascii_start();
+        if head.first() == Some(&b'{') {
+            let is_har = serde_json::from_slice::<Value>(head)
+                .map(|v| v.pointer("/log/entries").is_some())
+                .unwrap_or(false);
+            if is_har {
+                return CaptureFormat::Har;
+            }
+        }
+        let colon = head.iter().take(12).position(|&b| b == b':');
+        if matches!(colon, Some(n) if n > 0 && head[..n].iter().all(u8::is_ascii_digit)) {
+            return CaptureFormat::Mitmproxy;
+        }
+        CaptureFormat::Http
+    }
+}
+
+/// 抓包中的一个响应
+struct Response {
+    url: Option<String>,
+    body: Vec<u8>,
+}
+
+/// 从抓包文件中找出 exchange 接口的响应并解析为登录信息
+///
+/// 有多个响应时使用最后一个成功的。
+pub fn find_exchange(data: &[u8], format: CaptureFormat) -> Result<Cookie> {
+    let responses = match format {
+        CaptureFormat::Har => from_har(data)?,
+        CaptureFormat::Mitmproxy => from_mitmproxy(data)?,
+        CaptureFormat::Http => vec![from_http(data)?],
+    };
+    if responses.is_empty() {
+        bail!("抓包中没有 {}{} 的响应", EXCHANGE_HOST, EXCHANGE_PATH);
+    }
+
+    let mut last_error = None;
+    for response in responses.iter().rev() {
+        match parse_exchange(&response.body) {
+            Ok(parsed) => return Ok(parsed),
+            Err(e) => {
+                last_error = Some(match &response.url {
+                    Some(url) => e.context(format!("{} 的响应无效", url)),
+                    None => e,
+                });
+            }
+        }
+    }
+    Err(last_error.expect("responses非空"))
+}
+
+/// 校验 exchange 响应体
+pub fn parse_exchange(body: &[u8]) -> Result<Cookie> {
+    let value: Value = serde_json::from_slice(body).context("响应体不是JSON")?;
+    let cookie: Cookie = serde_json::from_value(value).context("响应体不是有效的登录信息")?;
+    if cookie.result != 0 {
+        bail!("登录失败: {} ({})", cookie.msg, cookie.result);
+    }
+    cookie.to_login_info()?;
+    Ok(cookie)
+}
+
+fn is_exchange_url(url: &str) -> bool {
+    url.contains(EXCHANGE_HOST) && url.contains(EXCHANGE_PATH)
+}
+
+fn from_har(data: &[u8]) -> Result<Vec<Response>> {
+    let har: Value = serde_json::from_slice(data).context("HAR文件不是有效的JSON")?;
+    let entries = har
+        .pointer("/log/entries")
+        .and_then(Value::as_array)
+        .context("HAR文件缺少 log.entries")?;
+
+    let mut responses = Vec::new();
+    for entry in entries {
+        let url = entry.pointer("/request/url").and_then(Value::as_str).unwrap_or("");
+        if !is_exchange_url(url) {
+            continue;
+        }
+        let content = entry.pointer("/response/content");
+        let text = content.and_then(|c| c.get("text")).and_then(Value::as_str).unwrap_or("");
+        let body = match content.and_then(|c| c.get("encoding")).and_then(Value::as_str) {
+            Some("base64") => general_purpose::STANDARD.decode(text).context("HAR响应体base64解码失败")?,
+            _ => text.as_bytes().to_vec(),
+        };
+        responses.push(Response { url: Some(url.to_string()), body });
+    }
+    Ok(responses)
+}
+
+fn from_mitmproxy(data: &[u8]) -> Result<Vec<Response>> {
+    let flows = tnetstring::parse_all(data).context("不是有效的mitmproxy流量文件")?;
+
+    let mut responses = Vec::new();
+    for flow in &flows {
+        if flow.get("type").and_then(TnetValue::as_str).is_some_and(|t| t != "http") {
+            continue;
+        }
+        let (Some(request), Some(response)) = (flow.get("request"), flow.get("response")) else {
+            continue;
+        };
+        let host = request
+            .get("host")
+            .or_else(|| request.get("authority"))
+            .and_then(TnetValue::as_str)
+            .unwrap_or(EXCHANGE_HOST);
+        let path = request.get("path").and_then(TnetValue::as_str).unwrap_or("");
+        let url = format!("https://{}{}", host, path);
+        if !is_exchange_url(&url) || response.get("status_code").and_then(TnetValue::as_i64).is_some_and(|c| c != 200) {
+            continue;
+        }
+
+        let headers: Vec<(String, String)> = response
+            .get("headers")
+            .and_then(TnetValue::as_list)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|h| {
+                let pair = h.as_list()?;
+                Some((pair.first()?.as_str()?.to_string(), pair.get(1)?.as_str()?.to_string()))
+            })
+            .collect();
+        let content = response.get("content").and_then(TnetValue::as_bytes).unwrap_or_default();
+        let body = decode_body(content, &headers)?;
+        responses.push(Response { url: Some(url), body });
+    }
+    Ok(responses)
+}
+
+/// 原始HTTP响应，前面可以带有请求；没有状态行时整个文件视为响应体
+fn from_http(data: &[u8]) -> Result<Response> {
+    let Some(start) = find_status_line(data) else {
+        return Ok(Response { url: None, body: data.to_vec() });
+    };
+    let response = &data[start..];
+    let (head, body) = match find(response, b"\r\n\r\n") {
+        Some(i) => (&response[..i], &response[i + 4..]),
+        None => match find(response, b"\n\n") {
+            Some(i) => (&response[..i], &response[i + 2..]),
+            None => bail!("HTTP响应缺少空行"),
+        },
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+    let status = lines.next().unwrap_or_default();
+    let code = status.split_whitespace().nth(1).unwrap_or_default();
+    if code != "200" {
+        bail!("HTTP状态不是200: {}", status.trim());
+    }
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let body = if header(&headers, "transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+
+    // 请求行中的路径
+    let url = std::str::from_utf8(&data[..start])
+        .ok()
+        .and_then(|req| req.lines().next())
+        .and_then(|line| line.split_whitespace().nth(1))
+        .map(str::to_string);
+    if let Some(url) = url.as_deref().filter(|u| !u.contains(EXCHANGE_PATH)) {
+        bail!("请求不是 {}: {}", EXCHANGE_PATH, url);
+    }
+    Ok(Response { url, body: decode_body(&body, &headers)? })
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 按 Content-Encoding 解压响应体
+fn decode_body(body: &[u8], headers: &[(String, String)]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match header(headers, "content-encoding").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("identity") => return Ok(body.to_vec()),
+        Some("gzip") => GzDecoder::new(body).read_to_end(&mut out).context("gzip解压失败")?,
+        Some("deflate") => ZlibDecoder::new(body).read_to_end(&mut out).context("deflate解压失败")?,
+        Some(other) => bail!("不支持的Content-Encoding: {}，请在抓包工具中保存解码后的响应", other),
+    };
+    Ok(out)
+}
+
+/// 合并 chunked 编码的分块
+fn dechunk(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = find(data, b"\n").context("chunked分块不完整")?;
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size_str = size_line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size_str, 16).context("chunked分块大小无效")?;
+        data = &data[line_end + 1..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if data.len() < size {
+            bail!("chunked分块不完整");
+        }
+        out.extend_from_slice(&data[..size]);
+        data = data[size..].strip_prefix(b"\r").unwrap_or(&data[size..]);
+        data = data.strip_prefix(b"\n").unwrap_or(data);
+    }
+}
+
+/// 响应状态行的位置，请求行末尾的 HTTP/1.1 不算
+fn find_status_line(data: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    for line in data.split(|&b| b == b'\n') {
+        if line.starts_with(b"HTTP/") {
+            return Some(offset);
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = r#"{"result":0,"msg":"","req_id":"r","account":"10001","nickname":"n","avatar_url":"",
+        "tickets":[{"name":"pskey","domain":"myqq.qq.com","ticket":"abc","expire_at":4102444800}]}"#;
+
+    #[test]
+    fn test_find_exchange() {
+        let har = serde_json::json!({"log": {"entries": [
+            {"request": {"url": "https://ntlogin.qq.com/wxmini/login/exchange"},
+             "response": {"content": {"text": general_purpose::STANDARD.encode(BODY), "encoding": "base64"}}},
+            {"request": {"url": "https://example.com/"}, "response": {"content": {"text": "x"}}},
+        ]}})
+        .to_string();
+        assert_eq!(CaptureFormat::detect(har.as_bytes()), CaptureFormat::Har);
+        let cookie = find_exchange(har.as_bytes(), CaptureFormat::Har).unwrap();
+        assert_eq!(cookie.account, "10001");
+
+        let raw = format!(
+            "POST /wxmini/login/exchange HTTP/1.1\r\nHost: ntlogin.qq.com\r\n\r\n\
+             HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            BODY.len(),
+            BODY
+        );
+        assert_eq!(CaptureFormat::detect(raw.as_bytes()), CaptureFormat::Http);
+        assert!(find_exchange(raw.as_bytes(), CaptureFormat::Http).is_ok());
+
+        let request = tnet_dict(&[("host", "14:ntlogin.qq.com,"), ("path", "22:/wxmini/login/exchange,")]);
+        let response = tnet_dict(&[("content", &format!("{}:{},", BODY.len(), BODY))]);
+        let flow = tnet_dict(&[("type", "4:http;"), ("request", &request), ("response", &response)]);
+        assert_eq!(CaptureFormat::detect(flow.as_bytes()), CaptureFormat::Mitmproxy);
+        assert!(find_exchange(flow.as_bytes(), CaptureFormat::Mitmproxy).is_ok());
+    }
+
+    fn tnet_dict(items: &[(&str, &str)]) -> String {
+        let payload: String = items
+            .iter()
+            .map(|(k, v)| format!("{}:{};{}", k.len(), k, v))
+            .collect();
+        format!("{}:{}}}", payload.len(), payload)
+    }
+}
@@ -0,0 +1,332 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use crate::helper::Helper;
+use crate::profile::Profile;
+
+/// 提供口令的环境变量，用于定时任务等无法交互输入的场景
+pub const PASSPHRASE_ENV: &str = "QQHISTORY_PASSPHRASE";
+
+/// 系统密钥环中的服务名
+const KEYRING_SERVICE: &str = "qqhistory";
+
+/// 本次运行中已输入的口令，刷新票据后重新加密时不再询问
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// 加密方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMethod {
+    /// 由口令经argon2id派生密钥
+    Passphrase,
+    /// 随机密钥保存在系统密钥环，不可用或不能长期保存（Linux内核密钥环）时保存为单独的密钥文件
+    Keyring,
+}
+
+impl FromStr for KeyMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "passphrase" => Ok(KeyMethod::Passphrase),
+            "keyring" => Ok(KeyMethod::Keyring),
+            _ => Err(format!("未知的加密方式: {}，可选 passphrase、keyring", s)),
+        }
+    }
+}
+
+/// 密钥来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum KeyRef {
+    Passphrase { salt: String, m_cost: u32, t_cost: u32, p_cost: u32 },
+    Keyring { id: String },
+    File { id: String },
+}
+
+/// 加密后的文件内容：`{"encrypted": {...}}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    version: u32,
+    key: KeyRef,
+    /// base64的12字节nonce
+    nonce: String,
+    /// base64的AES-256-GCM密文
+    data: String,
+}
+
+impl Envelope {
+    /// 文件内容为加密格式时取出信封
+    pub fn from_value(value: &Value) -> Option<Envelope> {
+        value
+            .get("encrypted")
+            .filter(|_| value.get("tickets").is_none())
+            .and_then(|e| serde_json::from_value(e.clone()).ok())
+    }
+
+    /// 读取文件，不是加密格式或无法读取时为None
+    pub fn read(path: &Path) -> Option<Envelope> {
+        let content = fs::read_to_string(path).ok()?;
+        Self::from_value(&serde_json::from_str(&content).ok()?)
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::json!({ "encrypted": self })
+    }
+
+    /// 密钥来源的说明
+    pub fn describe(&self) -> &'static str {
+        match self.key {
+            KeyRef::Passphrase { .. } => "口令",
+            KeyRef::Keyring { .. } => "系统密钥环",
+            KeyRef::File { .. } => "密钥文件",
+        }
+    }
+
+    /// 用新的随机密钥或口令加密
+    pub fn seal(plain: &[u8], method: KeyMethod) -> Result<Envelope> {
+        let (key_ref, key) = match method {
+            KeyMethod::Passphrase => {
+                let passphrase = passphrase(true)?;
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                let params = Params::default();
+                let key_ref = KeyRef::Passphrase {
+                    salt: general_purpose::STANDARD.encode(salt),
+                    m_cost: params.m_cost(),
+                    t_cost: params.t_cost(),
+                    p_cost: params.p_cost(),
+                };
+                let key = derive_key(&passphrase, &key_ref)?;
+                (key_ref, key)
+            }
+            KeyMethod::Keyring => {
+                let mut key = [0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                (store_random_key(&key)?, key)
+            }
+        };
+        encrypt_with(plain, key_ref, &key)
+    }
+
+    /// 用同一密钥重新加密，nonce重新生成；先解密原内容确认密钥正确
+    pub fn reseal(&self, plain: &[u8]) -> Result<Envelope> {
+        self.open()?;
+        let key = self.key()?;
+        encrypt_with(plain, self.key.clone(), &key)
+    }
+
+    /// 解密
+    pub fn open(&self) -> Result<Vec<u8>> {
+        let key = self.key()?;
+        let result = decrypt_with(self, &key);
+        if result.is_err() && matches!(self.key, KeyRef::Passphrase { .. }) {
+            // 口令错误时清掉缓存，下次重新询问
+            *PASSPHRASE.lock().unwrap() = None;
+        }
+        result
+    }
+
+    fn key(&self) -> Result<[u8; 32]> {
+        match &self.key {
+            KeyRef::Passphrase { .. } => derive_key(&passphrase(false)?, &self.key),
+            KeyRef::Keyring { id } => {
+                let encoded = keyring_entry(id)?
+                    .get_password()
+                    .map_err(|e| anyhow!("无法从系统密钥环读取密钥 {}: {}", id, e))?;
+                decode_key(&encoded)
+            }
+            KeyRef::File { id } => {
+                let path = key_file(id)?;
+                let encoded = fs::read_to_string(&path)
+                    .with_context(|| format!("无法读取密钥文件: {}", path.display()))?;
+                decode_key(encoded.trim())
+            }
+        }
+    }
+}
+
+fn encrypt_with(plain: &[u8], key_ref: KeyRef, key: &[u8; 32]) -> Result<Envelope> {
+    let cipher = Aes256Gcm::new(key.into());
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    let data = cipher
+        .encrypt(Nonce::from_slice(&nonce), plain)
+        .map_err(|_| anyhow!("加密失败"))?;
+    Ok(Envelope {
+        version: 1,
+        key: key_ref,
+        nonce: general_purpose::STANDARD.encode(nonce),
+        data: general_purpose::STANDARD.encode(data),
+    })
+}
+
+fn decrypt_with(envelope: &Envelope, key: &[u8; 32]) -> Result<Vec<u8>> {
+    if envelope.version != 1 {
+        bail!("不支持的加密版本: {}", envelope.version);
+    }
+    let nonce = general_purpose::STANDARD.decode(&envelope.nonce).context("nonce格式错误")?;
+    if nonce.len() != 12 {
+        bail!("nonce长度错误");
+    }
+    let data = general_purpose::STANDARD.decode(&envelope.data).context("密文格式错误")?;
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+        .map_err(|_| anyhow!("解密失败，{}不正确或文件已损坏", envelope.describe()))
+}
+
+fn derive_key(passphrase: &str, key_ref: &KeyRef) -> Result<[u8; 32]> {
+    let KeyRef::Passphrase { salt, m_cost, t_cost, p_cost } = key_ref else {
+        bail!("不是口令加密");
+    };
+    let salt = general_purpose::STANDARD.decode(salt).context("salt格式错误")?;
+    let params = Params::new(*m_cost, *t_cost, *p_cost, Some(32)).map_err(|e| anyhow!("argon2参数错误: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| anyhow!("派生密钥失败: {}", e))?;
+    Ok(key)
+}
+
+/// 读取口令：本次运行已输入的、环境变量 [`PASSPHRASE_ENV`]，否则在终端询问，`confirm` 时输入两次
+fn passphrase(confirm: bool) -> Result<String> {
+    let mut cached = PASSPHRASE.lock().unwrap();
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase = secret_from_env_or_prompt(PASSPHRASE_ENV, "cookie.json 口令", confirm)?;
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+/// 读取环境变量 `env_name`，未设置时在终端询问，`confirm` 时输入两次
+pub fn secret_from_env_or_prompt(env_name: &str, prompt: &str, confirm: bool) -> Result<String> {
+    if let Some(secret) = env::var(env_name).ok().filter(|p| !p.is_empty()) {
+        return Ok(secret);
+    }
+    let secret = rpassword::prompt_password(format!("{}: ", prompt))
+        .with_context(|| format!("无法读取{}，可以设置环境变量 {}", prompt, env_name))?;
+    if secret.is_empty() {
+        bail!("{}不能为空", prompt);
+    }
+    if confirm && rpassword::prompt_password(format!("再次输入{}: ", prompt))? != secret {
+        bail!("两次输入的{}不一致", prompt);
+    }
+    Ok(secret)
+}
+
+/// 保存随机密钥：优先能长期保存的系统密钥环，不可用或重启后会清空时写入权限为0600的密钥文件
+fn store_random_key(key: &[u8; 32]) -> Result<KeyRef> {
+    let mut id = [0u8; 8];
+    OsRng.fill_bytes(&mut id);
+    let id: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+    let encoded = general_purpose::STANDARD.encode(key);
+
+    let stored = persistent_keyring().and_then(|_| keyring_entry(&id)).and_then(|entry| {
+        entry.set_password(&encoded).map_err(|e| anyhow!("{}", e))?;
+        // 确认能读回，部分环境写入成功但读取失败
+        entry.get_password().map_err(|e| anyhow!("{}", e))
+    });
+    match stored {
+        Ok(_) => Ok(KeyRef::Keyring { id }),
+        Err(e) => {
+            let path = key_file(&id)?;
+            Helper::echo(&format!("系统密钥环不可用（{}），密钥保存到: {}", e, path.display()), "yellow");
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            write_private(&path, encoded.as_bytes())?;
+            Ok(KeyRef::File { id })
+        }
+    }
+}
+
+/// 系统密钥环能否长期保存：Linux内核密钥环（keyutils）在重启或长期闲置后清空，密钥只存在那里会导致无法解密
+fn persistent_keyring() -> Result<()> {
+    use keyring::credential::CredentialPersistence;
+    match keyring::default::default_credential_builder().persistence() {
+        CredentialPersistence::UntilDelete => Ok(()),
+        _ => bail!("系统密钥环在重启后不保留凭据"),
+    }
+}
+
+fn keyring_entry(id: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, id).map_err(|e| anyhow!("无法访问系统密钥环: {}", e))
+}
+
+/// 密钥文件放在数据目录下，与配置目录分开
+fn key_file(id: &str) -> Result<PathBuf> {
+    Ok(Profile::data_dir()?.join("keys").join(format!("{}.key", id)))
+}
+
+fn decode_key(encoded: &str) -> Result<[u8; 32]> {
+    general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .context("密钥格式错误")
+}
+
+/// 写入只有本用户可读写的文件
+pub fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).with_context(|| format!("无法写入: {}", path.display()))?;
+    std::io::Write::write_all(&mut file, content)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 先写同目录的临时文件再重命名，文件权限为0600，读取的程序不会读到一半的内容
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    write_private(&tmp, content)?;
+    fs::rename(&tmp, path).with_context(|| format!("无法写入: {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passphrase_roundtrip() {
+        let key_ref = KeyRef::Passphrase {
+            salt: general_purpose::STANDARD.encode([7u8; 16]),
+            m_cost: 1024,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        let key = derive_key("secret", &key_ref).unwrap();
+        let envelope = encrypt_with(b"{\"tickets\":[]}", key_ref.clone(), &key).unwrap();
+
+        let value = envelope.to_value();
+        let parsed = Envelope::from_value(&value).unwrap();
+        assert_eq!(decrypt_with(&parsed, &key).unwrap(), b"{\"tickets\":[]}");
+
+        let wrong = derive_key("other", &key_ref).unwrap();
+        assert!(decrypt_with(&parsed, &wrong).is_err());
+        assert!(Envelope::from_value(&serde_json::json!({"tickets": []})).is_none());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_kernel_keyring_not_persistent() {
+        // 只启用了 linux-native（keyutils），重启后凭据丢失，不能作为唯一的密钥存放处
+        assert!(persistent_keyring().is_err());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod capture;
+pub mod crypto;
+pub mod refresh;
+mod tnetstring;
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde_json::json;
+use std::path::Path;
+use crate::api::USER_AGENT;
+use crate::cookie::Cookie;
+use crate::login::capture;
+
+/// 用refresh_token换取新票据的接口，与 exchange 同属小程序登录服务
+///
+/// 目前没有抓到小程序的刷新请求，地址和请求体是按 exchange 接口和票据中的字段推测的；
+/// 抓到实际请求后可用 [`REFRESH_URL_ENV`] 指定地址。
+pub const REFRESH_URL: &str = "https://ntlogin.qq.com/wxmini/login/refresh";
+
+/// 覆盖 [`REFRESH_URL`] 的环境变量
+pub const REFRESH_URL_ENV: &str = "QQHISTORY_REFRESH_URL";
+
+/// 刷新票据使用的地址，设置了 [`REFRESH_URL_ENV`] 时优先使用
+pub fn refresh_url() -> String {
+    std::env::var(REFRESH_URL_ENV)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| REFRESH_URL.to_string())
+}
+
+/// 用refresh_token换取新的票据，返回合并后的登录信息
+///
+/// 响应与 exchange 的格式相同，只包含刷新后的票据，未返回的票据保持不变。
+pub async fn refresh(client: &Client, url: &str, cookie: &Cookie) -> Result<Cookie> {
+    let ticket = cookie
+        .refresh_ticket()
+        .context("cookie.json 中没有 refresh_token，请重新登录")?;
+    let body = json!({
+        "account": cookie.account,
+        "appid": ticket.appid,
+        "openid": ticket.openid,
+        "refresh_token": ticket.refresh_token,
+    });
+
+    let response = client
+        .post(url)
+        .header("User-Agent", USER_AGENT)
+        .json(&body)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("刷新票据失败: HTTP {}", status);
+    }
+    let fresh = capture::parse_exchange(&response.bytes().await?).context("刷新票据失败")?;
+    if fresh.account != cookie.account {
+        bail!("刷新票据失败: 返回的账号 {} 与 {} 不一致", fresh.account, cookie.account);
+    }
+
+    let mut merged = cookie.clone();
+    merged.merge_refreshed(fresh);
+    Ok(merged)
+}
+
+/// 刷新 `path` 中的票据并原子地写回，返回刷新后的登录信息
+pub async fn refresh_file(client: &Client, url: &str, path: &Path) -> Result<Cookie> {
+    let cookie = Cookie::load_from_file(path)?;
+    let refreshed = refresh(client, url, &cookie).await?;
+    refreshed.save_to_file(path)?;
+    Ok(refreshed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    /// 本地保存的cookie.json：myqq.qq.com 域的pskey带有refresh_token
+    const COOKIE: &str = r#"{"result":0,"msg":"","req_id":"r0","account":"10001","nickname":"n",
+        "avatar_url":"a","unknown":1,"tickets":[
+        {"name":"pskey","domain":"myqq.qq.com","ticket":"old","expire_at":1,
+         "appid":"wx1234","openid":"o-10001","refresh_token":"rt"},
+        {"name":"skey","ticket":"s","expire_at":1}]}"#;
+
+    /// 刷新接口的响应，与 exchange 同格式，只含刷新后的票据
+    const REFRESHED: &str = r#"{"result":0,"msg":"","req_id":"r1","account":"10001","nickname":"",
+        "avatar_url":"","tickets":[
+        {"name":"pskey","domain":"myqq.qq.com","ticket":"new","expire_at":4102444800}]}"#;
+
+    /// 启动本地的模拟刷新接口，按顺序返回 `responses`，并记录收到的请求体
+    async fn mock_refresh(responses: Vec<(StatusCode, &'static str)>) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = Arc::new(Mutex::new(responses.into_iter()));
+        let recorded = requests.clone();
+        let app = Router::new().route(
+            "/wxmini/login/refresh",
+            post(move |Json(body): Json<Value>| async move {
+                recorded.lock().unwrap().push(body);
+                responses.lock().unwrap().next().unwrap_or((StatusCode::NOT_FOUND, ""))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/wxmini/login/refresh", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let cookie: Cookie = serde_json::from_str(COOKIE).unwrap();
+        let mismatch = r#"{"result":0,"msg":"","req_id":"","account":"10002","nickname":"","avatar_url":"",
+            "tickets":[{"name":"pskey","domain":"myqq.qq.com","ticket":"x","expire_at":4102444800}]}"#;
+        let (url, requests) = mock_refresh(vec![
+            (StatusCode::OK, REFRESHED),
+            (StatusCode::OK, mismatch),
+            (StatusCode::FORBIDDEN, "{}"),
+        ])
+        .await;
+        let client = Client::new();
+
+        let merged = refresh(&client, &url, &cookie).await.unwrap();
+        let ticket = merged.refresh_ticket().unwrap();
+        assert_eq!(ticket.ticket, "new");
+        assert_eq!(ticket.expire_at, Some(4102444800));
+        assert_eq!(ticket.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(merged.nickname, "n");
+        assert_eq!(
+            requests.lock().unwrap()[0],
+            json!({"account": "10001", "appid": "wx1234", "openid": "o-10001", "refresh_token": "rt"})
+        );
+
+        let err = refresh(&client, &url, &cookie).await.unwrap_err();
+        assert!(err.to_string().contains("10002"), "{}", err);
+        let err = refresh(&client, &url, &cookie).await.unwrap_err();
+        assert!(err.to_string().contains("403"), "{}", err);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        // 没有refresh_token时不发送请求
+        let mut plain = cookie.clone();
+        plain.tickets.iter_mut().for_each(|t| t.refresh_token = None);
+        assert!(refresh(&client, &url, &plain).await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_file() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-refresh-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cookie.json");
+        std::fs::write(&path, COOKIE).unwrap();
+        let (url, _) = mock_refresh(vec![(StatusCode::OK, REFRESHED), (StatusCode::BAD_GATEWAY, "")]).await;
+
+        refresh_file(&Client::new(), &url, &path).await.unwrap();
+        let saved = Cookie::load_from_file(&path).unwrap();
+        assert_eq!(saved.refresh_ticket().unwrap().ticket, "new");
+        assert_eq!(saved.refresh_ticket().unwrap().appid.as_deref(), Some("wx1234"));
+        assert_eq!(saved.tickets.len(), 2);
+        assert_eq!(saved.extra["unknown"], 1);
+        assert!(!saved.is_expired());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // 刷新失败时不改动原文件
+        let before = std::fs::read(&path).unwrap();
+        assert!(refresh_file(&Client::new(), &url, &path).await.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{bail, Context, Result};
+
+/// tnetstring值，mitmproxy的流量文件由连续的tnetstring字典组成
+#[derive(Debug, Clone, PartialEq)]
+pub enum TnetValue {
+    Bytes(Vec<u8>),
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+    List(Vec<TnetValue>),
+    Dict(Vec<(TnetValue, TnetValue)>),
+}
+
+impl TnetValue {
+    /// 字典中键为 `key` 的值
+    pub fn get(&self, key: &str) -> Option<&TnetValue> {
+        match self {
+            TnetValue::Dict(items) => items
+                .iter()
+                .find(|(k, _)| k.as_bytes() == Some(key.as_bytes()))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// 字节串或字符串的内容
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            TnetValue::Bytes(b) => Some(b),
+            TnetValue::String(s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            TnetValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[TnetValue]> {
+        match self {
+            TnetValue::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// 解析连续的tnetstring值
+pub fn parse_all(mut data: &[u8]) -> Result<Vec<TnetValue>> {
+    let mut values = Vec::new();
+    loop {
+        data = data.trim_ascii_start();
+        if data.is_empty() {
+            return Ok(values);
+        }
+        let (value, rest) = parse(data)?;
+        values.push(value);
+        data = rest;
+    }
+}
+
+/// 解析一个tnetstring值，返回值和剩余数据
+pub fn parse(data: &[u8]) -> Result<(TnetValue, &[u8])> {
+    let colon = data
+        .iter()
+        .take(12)
+        .position(|&b| b == b':')
+        .context("tnetstring缺少长度")?;
+    let len: usize = std::str::from_utf8(&data[..colon])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .context("tnetstring长度无效")?;
+    let start = colon + 1;
+    if data.len() < start + len + 1 {
+        bail!("tnetstring数据不完整");
+    }
+    let payload = &data[start..start + len];
+    let tag = data[start + len];
+    let rest = &data[start + len + 1..];
+
+    let text = || std::str::from_utf8(payload).context("tnetstring不是有效的UTF-8");
+    let value = match tag {
+        b',' => TnetValue::Bytes(payload.to_vec()),
+        b';' => TnetValue::String(text()?.to_string()),
+        b'#' => TnetValue::Int(text()?.parse().context("tnetstring整数无效")?),
+        b'^' => TnetValue::Float(text()?.parse().context("tnetstring浮点数无效")?),
+        b'!' => TnetValue::Bool(payload == b"true"),
+        b'~' => TnetValue::Null,
+        b']' => TnetValue::List(parse_all(payload)?),
+        b'}' => {
+            let items = parse_all(payload)?;
+            if items.len() % 2 != 0 {
+                bail!("tnetstring字典的键值数量不匹配");
+            }
+            let mut pairs = Vec::with_capacity(items.len() / 2);
+            let mut iter = items.into_iter();
+            while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+                pairs.push((k, v));
+            }
+            TnetValue::Dict(pairs)
+        }
+        other => bail!("未知的tnetstring类型: {}", other as char),
+    };
+    Ok((value, rest))
+}
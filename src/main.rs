@@ -1,13 +1,22 @@
 mod helper;
 mod cookie;
+mod profile;
 mod api;
 mod protobuf;
 mod database;
 mod elem;
-
-use anyhow::{Context, Result};
-use clap::Parser as ClapParser;
-use serde_json::Value;
+mod message;
+mod export;
+mod import;
+mod onebot;
+mod login;
+mod serve;
+mod stats;
+mod sync;
+mod commands;
+
+use anyhow::Result;
+use clap::{Parser as ClapParser, Subcommand};
 use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
@@ -16,7 +25,16 @@ use crate::helper::Helper;
 use crate::cookie::Cookie;
 use crate::api::Api;
 use crate::database::Database;
-use crate::elem::parser::ElemParser;
+use crate::profile::Profile;
+use crate::commands::db::DbCommand;
+use crate::commands::export::ExportCommand;
+use crate::commands::import::ImportCommand;
+use crate::commands::login::LoginCommand;
+use crate::commands::pb::PbCommand;
+use crate::commands::profiles::ProfilesCommand;
+use crate::commands::serve::ServeArgs;
+use crate::commands::stats::StatsArgs;
+use crate::commands::watch::WatchArgs;
 
 /// QQ历史消息拉取工具
 #[derive(ClapParser, Debug)]
@@ -25,24 +43,83 @@ struct Args {
     /// QQ号
     #[arg(short = 'u', long = "uin")]
     uin: Option<String>,
+
+    /// 账号配置名称，默认读取环境变量 QQHISTORY_PROFILE，都未指定时使用当前目录
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// 子命令，不指定时拉取 --uin 的历史消息
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Protobuf调试工具
+    #[command(subcommand)]
+    Pb(PbCommand),
+    /// 导出聊天记录
+    #[command(subcommand)]
+    Export(ExportCommand),
+    /// 导入其他来源的聊天记录
+    #[command(subcommand)]
+    Import(ImportCommand),
+    /// 启动本地HTTP服务，在浏览器中查看消息记录
+    Serve(ServeArgs),
+    /// 统计聊天记录
+    Stats(StatsArgs),
+    /// 管理账号配置
+    #[command(subcommand)]
+    Profiles(ProfilesCommand),
+    /// 管理登录信息
+    #[command(subcommand)]
+    Login(LoginCommand),
+    /// 加密、解密消息数据库（需要启用 sqlcipher 特性）
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// 持续运行，定时拉取指定会话的新消息
+    Watch(WatchArgs),
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    // 只有拉取消息、导入登录信息或聊天记录、watch 会创建不存在的配置
+    let create = matches!(
+        args.command,
+        None | Some(Command::Login(LoginCommand::Import(_))) | Some(Command::Import(_)) | Some(Command::Watch(_))
+    );
+    let profile = Profile::init(args.profile.as_deref(), create)?;
+
+    match args.command {
+        Some(Command::Pb(command)) => return commands::pb::run(command),
+        Some(Command::Export(command)) => return commands::export::run(command).await,
+        Some(Command::Import(command)) => return commands::import::run(command),
+        Some(Command::Serve(args)) => return commands::serve::run(args).await,
+        Some(Command::Stats(args)) => return commands::stats::run(args),
+        Some(Command::Profiles(command)) => return commands::profiles::run(command),
+        Some(Command::Login(command)) => return commands::login::run(command).await,
+        Some(Command::Db(command)) => return commands::db::run(command),
+        Some(Command::Watch(args)) => return commands::watch::run(args).await,
+        None => {}
+    }
 
     // 加载cookie
-    let cookie_path = "cookie.json";
-    if !std::path::Path::new(cookie_path).exists() {
-        Helper::echo("未找到有效的登录信息", "red");
+    let cookie_path = profile.cookie_path();
+    if !cookie_path.exists() {
+        Helper::echo(&format!("未找到有效的登录信息: {}", cookie_path.display()), "red");
         return Ok(());
     }
 
-    let cookie = Cookie::load_from_file(cookie_path)?;
+    let mut cookie = Cookie::load_from_file(&cookie_path)?;
 
     if cookie.is_expired() {
-        Helper::echo("登录信息已过期，请重新登录", "red");
-        return Ok(());
+        if cookie.refresh_ticket().is_none() {
+            Helper::echo("登录信息已过期，请重新登录", "red");
+            return Ok(());
+        }
+        Helper::echo("登录信息已过期，正在刷新票据...", "yellow");
+        cookie = login::refresh::refresh_file(&reqwest::Client::new(), &login::refresh::refresh_url(), &cookie_path).await?;
     }
 
     let login_info = cookie.to_login_info()?;
@@ -58,14 +135,14 @@ async fn main() -> Result<()> {
     );
 
     // 创建API客户端
-    let api = Api::new(login_info)?;
+    let api = Api::new(login_info)?.with_refresh(cookie_path);
 
     // 获取好友列表
     Helper::echo("正在获取好友列表...", "cyan");
     let _friend_list = api.get_friend_list(500).await?;
 
     // 处理uid映射
-    if !std::path::Path::new("uids.json").exists() {
+    if !profile.uids_path().exists() {
         Helper::echo("正在获取UID映射（第1次）...", "cyan");
         let offline_msg = api.sso_get_offline_msg(14, "").await?;
         let uids = Api::save_uid(&offline_msg)?;
@@ -80,19 +157,19 @@ async fn main() -> Result<()> {
     }
 
     // 获取uid参数
-    let uid = get_uid(&args.uin)?;
+    let uid = get_uid(profile, &args.uin)?;
 
     // 创建db目录
-    let db_dir = "db";
-    if !std::path::Path::new(db_dir).exists() {
-        fs::create_dir_all(db_dir)?;
-        Helper::echo(&format!("创建数据库目录: {}", db_dir), "cyan");
+    let db_dir = profile.db_dir();
+    if !db_dir.exists() {
+        fs::create_dir_all(&db_dir)?;
+        Helper::echo(&format!("创建数据库目录: {}", db_dir.display()), "cyan");
     }
 
     // 使用uid作为数据库文件名
-    let db_file = format!("{}/{}.db", db_dir, uid);
-    let db = Database::new(&db_file)?;
-    Helper::echo(&format!("使用数据库文件: {}", db_file), "cyan");
+    let db_file = profile.db_file(&uid);
+    let db = Database::new(&db_file.to_string_lossy())?;
+    Helper::echo(&format!("使用数据库文件: {}", db_file.display()), "cyan");
 
     // 循环拉取历史消息
     let max_rounds = 1000;
@@ -131,66 +208,7 @@ async fn main() -> Result<()> {
         // 解析消息
         let mut messages = Vec::new();
         for msg in msgs {
-            let content_head = msg.get("content_head").context("缺少content_head")?;
-            let body = msg.get("body").context("缺少body")?;
-            let elems = body
-                .get("rich_text")
-                .and_then(|rt| rt.get("elems"))
-                .and_then(|e| e.as_array())
-                .map(|arr| arr.clone())
-                .unwrap_or_default();
-
-            let parser = ElemParser::new(elems);
-            let arrays = parser.build();
-
-            let routing_head = msg.get("routing_head").context("缺少routing_head")?;
-
-            // 处理可能是字符串的数字字段
-            let parse_to_i64 = |v: &Value| -> i64 {
-                match v {
-                    Value::Number(n) => n.as_i64().unwrap_or(0),
-                    Value::String(s) => s.parse::<i64>().unwrap_or(0),
-                    _ => 0,
-                }
-            };
-
-            let msg_seq = content_head.get("nt_msg_seq")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-            let client_seq = content_head.get("msg_seq")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-            let random_val = content_head.get("random")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-            let msg_time = content_head.get("msg_time")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-            let from_uin = routing_head.get("from_uin")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-            let to_uin = routing_head.get("to_uin")
-                .map(|v| parse_to_i64(v))
-                .unwrap_or(0);
-
-            let message = serde_json::json!({
-                "content_head": {
-                    "msg_uid": content_head.get("msg_uid").and_then(|v| v.as_str()).unwrap_or(""),
-                    "random": random_val,
-                    "client_seq": client_seq,
-                    "msg_time": msg_time,
-                    "msg_seq": msg_seq,
-                },
-                "routing_head": {
-                    "from_uin": from_uin,
-                    "to_uin": to_uin,
-                    "from_uid": routing_head.get("from_uid").and_then(|v| v.as_str()).unwrap_or(""),
-                    "to_uid": routing_head.get("to_uid").and_then(|v| v.as_str()).unwrap_or(""),
-                },
-                "body": arrays
-            });
-
-            messages.push(message);
+            messages.push(sync::parse_message(msg)?);
         }
 
         // 保存到数据库
@@ -229,11 +247,12 @@ async fn main() -> Result<()> {
 }
 
 /// 获取UID
-fn get_uid(cli_uin: &Option<String>) -> Result<String> {
+fn get_uid(profile: &Profile, cli_uin: &Option<String>) -> Result<String> {
     if let Some(uin) = cli_uin {
         // 从uids.json查找
-        if std::path::Path::new("uids.json").exists() {
-            let content = fs::read_to_string("uids.json")?;
+        let uids_path = profile.uids_path();
+        if uids_path.exists() {
+            let content = fs::read_to_string(&uids_path)?;
             let map: HashMap<String, String> = serde_json::from_str(&content)?;
 
             if let Some(uid) = map.get(uin) {
@@ -244,7 +263,7 @@ fn get_uid(cli_uin: &Option<String>) -> Result<String> {
                 return prompt_uid();
             }
         } else {
-            Helper::echo("当前目录没有 uids.json，无法根据 uin 匹配。", "yellow");
+            Helper::echo(&format!("{} 不存在，无法根据 uin 匹配。", uids_path.display()), "yellow");
             return prompt_uid();
         }
     }
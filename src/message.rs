@@ -0,0 +1,471 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// 消息JSON格式版本号
+///
+/// 任何会破坏下游解析的字段变更（重命名、删除、改变类型）都必须递增此版本号，
+/// 新增可选字段不需要递增。
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 解析可能是字符串或数字的整数字段
+pub fn value_to_i64(v: &Value) -> i64 {
+    match v {
+        Value::Number(n) => n.as_i64().unwrap_or(0),
+        Value::String(s) => s.parse::<i64>().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// 一条完整的消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// 消息JSON格式版本号，见 [`SCHEMA_VERSION`]
+    #[serde(default = "default_version")]
+    pub version: u32,
+    pub content_head: ContentHead,
+    pub routing_head: RoutingHead,
+    pub body: Vec<Element>,
+}
+
+fn default_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// 消息头
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContentHead {
+    pub msg_uid: String,
+    pub random: i64,
+    pub client_seq: i64,
+    pub msg_time: i64,
+    pub msg_seq: i64,
+}
+
+/// 路由信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingHead {
+    pub from_uin: i64,
+    pub to_uin: i64,
+    pub from_uid: String,
+    pub to_uid: String,
+}
+
+/// 消息元素
+///
+/// 序列化时以 `type` 字段区分类型，其余字段与对应结构体平铺在同一层级。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Text(TextElement),
+    Face(FaceElement),
+    Mention(MentionElement),
+    Image(ImageElement),
+    Voice(VoiceElement),
+    Video(VideoElement),
+    Reply(ReplyElement),
+    Custom(CustomElement),
+}
+
+/// 文本
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextElement {
+    pub text: String,
+}
+
+/// 表情
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaceElement {
+    /// 表情id
+    pub id: u32,
+    /// 表情名称（不含 `/`），未知时为空字符串
+    pub name: String,
+}
+
+/// @提及
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MentionElement {
+    /// 被提及者的QQ号，@全体成员或未知时为0
+    pub uin: i64,
+    pub uid: String,
+    /// 显示的名称（不含 `@`）
+    pub name: String,
+    /// 是否为@全体成员
+    pub all: bool,
+}
+
+/// 富媒体文件信息，所有字段始终存在，缺失时为0或空字符串
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileInfo {
+    pub size: u64,
+    pub md5: String,
+    pub sha1: String,
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+    /// 时长（秒），仅语音/视频有效
+    pub duration: u32,
+    /// 视频格式，仅视频有效
+    pub format: u32,
+}
+
+/// 图片下载信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageDownload {
+    pub domain: String,
+    pub url_path: String,
+    pub rkey: String,
+    pub original_parameter: String,
+    pub big_parameter: String,
+    pub thumb_parameter: String,
+}
+
+/// 图片
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageElement {
+    pub url: String,
+    pub file: FileInfo,
+    pub file_uuid: String,
+    /// 上传时间（Unix时间戳，秒），未知时为0
+    pub upload_time: i64,
+    pub download: ImageDownload,
+    /// common_elem 中 `bytes_pb_elem` 的base64，其他来源为空字符串
+    ///
+    /// 不写入消息JSON，单独保存在数据库的 `raw` 列中，供 `pb decode --id` 调试。
+    #[serde(skip)]
+    pub raw: String,
+}
+
+/// 语音
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoiceElement {
+    pub url: String,
+    pub duration: u32,
+    pub file: FileInfo,
+    pub file_uuid: String,
+    pub upload_time: i64,
+    /// common_elem 中 `bytes_pb_elem` 的base64，其他来源为空字符串
+    ///
+    /// 不写入消息JSON，单独保存在数据库的 `raw` 列中，供 `pb decode --id` 调试。
+    #[serde(skip)]
+    pub raw: String,
+}
+
+/// 视频缩略图
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoThumb {
+    pub url: String,
+    pub file: FileInfo,
+    pub file_uuid: String,
+    pub upload_time: i64,
+}
+
+/// 视频
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoElement {
+    pub url: String,
+    pub duration: u32,
+    pub file: FileInfo,
+    pub file_uuid: String,
+    pub upload_time: i64,
+    /// 缩略图，缺失时为null
+    pub thumb: Option<VideoThumb>,
+    /// common_elem 中 `bytes_pb_elem` 的base64，其他来源为空字符串
+    ///
+    /// 不写入消息JSON，单独保存在数据库的 `raw` 列中，供 `pb decode --id` 调试。
+    #[serde(skip)]
+    pub raw: String,
+}
+
+/// 回复
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplyElement {
+    /// 被回复消息的msg_seq
+    pub seq: i64,
+    pub from_uid: String,
+    pub to_uid: String,
+    /// 被回复的消息内容
+    pub source: Vec<Element>,
+    /// 本条回复的内容
+    pub content: Vec<Element>,
+}
+
+/// 自定义元素，供注册表中的扩展解析器使用
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomElement {
+    /// 自定义类型名，建议带上团队或格式前缀以免冲突
+    pub kind: String,
+    pub data: Value,
+}
+
+impl Message {
+    /// 消息的纯文本形式
+    pub fn plain_text(&self) -> String {
+        self.body.iter().map(Element::plain_text).collect()
+    }
+
+    /// 从漫游消息接口返回的单条消息构建
+    pub fn from_roam(msg: &Value, body: Vec<Element>) -> Result<Self> {
+        let content_head = msg.get("content_head").context("缺少content_head")?;
+        let routing_head = msg.get("routing_head").context("缺少routing_head")?;
+
+        let get_i64 = |v: &Value, key: &str| v.get(key).map(value_to_i64).unwrap_or(0);
+        let get_str = |v: &Value, key: &str| {
+            v.get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+
+        Ok(Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead {
+                msg_uid: get_str(content_head, "msg_uid"),
+                random: get_i64(content_head, "random"),
+                client_seq: get_i64(content_head, "msg_seq"),
+                msg_time: get_i64(content_head, "msg_time"),
+                msg_seq: get_i64(content_head, "nt_msg_seq"),
+            },
+            routing_head: RoutingHead {
+                from_uin: get_i64(routing_head, "from_uin"),
+                to_uin: get_i64(routing_head, "to_uin"),
+                from_uid: get_str(routing_head, "from_uid"),
+                to_uid: get_str(routing_head, "to_uid"),
+            },
+            body,
+        })
+    }
+}
+
+impl Element {
+    /// 富媒体元素的原始protobuf（base64），其他元素为None
+    fn raw(&self) -> Option<&str> {
+        match self {
+            Element::Image(image) => Some(&image.raw),
+            Element::Voice(voice) => Some(&voice.raw),
+            Element::Video(video) => Some(&video.raw),
+            _ => None,
+        }
+    }
+
+    /// 元素列表中非空的原始protobuf，键为消息JSON中的路径，如 `body.0.raw`、`body.1.content.0.raw`
+    pub fn collect_raw(elems: &[Element], path: &str, out: &mut BTreeMap<String, String>) {
+        for (i, elem) in elems.iter().enumerate() {
+            let path = format!("{}.{}", path, i);
+            match elem {
+                Element::Reply(reply) => {
+                    Self::collect_raw(&reply.source, &format!("{}.source", path), out);
+                    Self::collect_raw(&reply.content, &format!("{}.content", path), out);
+                }
+                _ => {
+                    if let Some(raw) = elem.raw().filter(|r| !r.is_empty()) {
+                        out.insert(format!("{}.raw", path), raw.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    /// 元素类型名，与序列化时的 `type` 字段一致
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Element::Text(_) => "text",
+            Element::Face(_) => "face",
+            Element::Mention(_) => "mention",
+            Element::Image(_) => "image",
+            Element::Voice(_) => "voice",
+            Element::Video(_) => "video",
+            Element::Reply(_) => "reply",
+            Element::Custom(_) => "custom",
+        }
+    }
+
+    /// 纯文本形式，富媒体以 `[图片]` 等占位符表示
+    pub fn plain_text(&self) -> String {
+        match self {
+            Element::Text(t) => t.text.clone(),
+            Element::Face(f) if f.name.is_empty() => format!("[表情{}]", f.id),
+            Element::Face(f) => format!("[/{}]", f.name),
+            Element::Mention(m) if m.all => "@全体成员".to_string(),
+            Element::Mention(m) if m.name.is_empty() => format!("@{}", m.uin),
+            Element::Mention(m) => format!("@{}", m.name),
+            Element::Image(_) => "[图片]".to_string(),
+            Element::Voice(v) => format!("[语音 {}s]", v.duration),
+            Element::Video(v) => format!("[视频 {}s]", v.duration),
+            Element::Reply(r) => r.content.iter().map(Element::plain_text).collect(),
+            Element::Custom(c) => format!("[{}]", c.kind),
+        }
+    }
+
+    /// 从旧版（版本0）的body元素转换
+    ///
+    /// 版本0的数据由早期版本直接拼装JSON写入数据库，字段形状不固定，
+    /// 无法识别的元素返回None。
+    pub fn from_legacy(v: &Value) -> Option<Self> {
+        let str_of = |v: Option<&Value>| v.and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let u64_of = |v: Option<&Value>| v.map(value_to_i64).unwrap_or(0).max(0) as u64;
+        let file_of = |v: Option<&Value>| FileInfo {
+            size: u64_of(v.and_then(|v| v.get("size"))),
+            md5: str_of(v.and_then(|v| v.get("md5"))),
+            sha1: str_of(v.and_then(|v| v.get("sha1"))),
+            file_name: str_of(v.and_then(|v| v.get("file_name"))),
+            width: u64_of(v.and_then(|v| v.get("width"))) as u32,
+            height: u64_of(v.and_then(|v| v.get("height"))) as u32,
+            duration: u64_of(v.and_then(|v| v.get("duration"))) as u32,
+            format: u64_of(v.and_then(|v| v.get("format"))) as u32,
+        };
+        let elems_of = |v: Option<&Value>| {
+            v.and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(Element::from_legacy).collect())
+                .unwrap_or_default()
+        };
+
+        match v.get("type")?.as_str()? {
+            "text" => Some(Element::Text(TextElement {
+                text: str_of(v.get("content")),
+            })),
+            "image" => {
+                let image = v.get("image")?;
+                let richmedia = image.get("richmedia");
+                let download = richmedia.and_then(|r| r.get("download_info"));
+                let ext = download.and_then(|d| d.get("pic_url_ext_info"));
+                Some(Element::Image(ImageElement {
+                    url: str_of(image.get("url")),
+                    file: file_of(richmedia.and_then(|r| r.get("file_info"))),
+                    file_uuid: str_of(richmedia.and_then(|r| r.get("file_uuid"))),
+                    upload_time: image.get("upload_time").map(value_to_i64).unwrap_or(0),
+                    raw: String::new(),
+                    download: ImageDownload {
+                        domain: str_of(download.and_then(|d| d.get("domain"))),
+                        url_path: str_of(download.and_then(|d| d.get("url_path"))),
+                        rkey: str_of(download.and_then(|d| d.get("rkey"))),
+                        original_parameter: str_of(ext.and_then(|e| e.get("original_parameter"))),
+                        big_parameter: str_of(ext.and_then(|e| e.get("big_parameter"))),
+                        thumb_parameter: str_of(ext.and_then(|e| e.get("thumb_parameter"))),
+                    },
+                }))
+            }
+            "voice" => {
+                let voice = v.get("voice")?;
+                let richmedia = voice.get("richmedia");
+                Some(Element::Voice(VoiceElement {
+                    url: str_of(voice.get("url")),
+                    duration: u64_of(voice.get("duration")) as u32,
+                    file: file_of(richmedia.and_then(|r| r.get("file_info"))),
+                    file_uuid: str_of(richmedia.and_then(|r| r.get("file_uuid"))),
+                    upload_time: voice.get("upload_time").map(value_to_i64).unwrap_or(0),
+                    raw: String::new(),
+                }))
+            }
+            "video" => {
+                let video = v.get("video")?;
+                let richmedia = video.get("richmedia");
+                let info = richmedia.and_then(|r| r.get("video"));
+                let thumb = richmedia
+                    .and_then(|r| r.get("thumb"))
+                    .filter(|t| !t.is_null())
+                    .map(|t| VideoThumb {
+                        url: str_of(t.get("url")),
+                        file: file_of(t.get("file_info")),
+                        file_uuid: str_of(t.get("file_uuid")),
+                        upload_time: t.get("upload_time").map(value_to_i64).unwrap_or(0),
+                    });
+                Some(Element::Video(VideoElement {
+                    url: str_of(video.get("url")),
+                    duration: u64_of(video.get("duration")) as u32,
+                    file: file_of(info.and_then(|i| i.get("file_info"))),
+                    file_uuid: str_of(info.and_then(|i| i.get("file_uuid"))),
+                    upload_time: info
+                        .and_then(|i| i.get("upload_time"))
+                        .map(value_to_i64)
+                        .unwrap_or(0),
+                    thumb,
+                    raw: String::new(),
+                }))
+            }
+            "reply" => {
+                let reply = v.get("reply")?;
+                Some(Element::Reply(ReplyElement {
+                    seq: reply.get("seq").map(value_to_i64).unwrap_or(0),
+                    from_uid: str_of(reply.get("from_uid")),
+                    to_uid: str_of(reply.get("to_uid")),
+                    source: elems_of(reply.get("reply_to")),
+                    content: elems_of(reply.get("reply_msg")),
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    /// 从数据库中保存的body解析元素列表
+    ///
+    /// 版本0中无法识别的元素会被跳过，但body本身不是合法的JSON数组或元素格式错误时返回错误，不会当作空消息。
+    pub fn parse_body(body: &str, version: u32) -> Result<Vec<Element>> {
+        if version == 0 {
+            let legacy: Vec<Value> = serde_json::from_str(body).context("body不是JSON数组")?;
+            Ok(legacy.iter().filter_map(Element::from_legacy).collect())
+        } else {
+            serde_json::from_str(body).with_context(|| format!("无法按版本 {} 解析body", version))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_element_json_shape() {
+        let elem = Element::Text(TextElement { text: "你好".to_string() });
+        assert_eq!(serde_json::to_value(&elem).unwrap(), json!({"type": "text", "text": "你好"}));
+
+        let elem = Element::Voice(VoiceElement::default());
+        let value = serde_json::to_value(&elem).unwrap();
+        assert_eq!(value["type"], "voice");
+        assert_eq!(value["type"], elem.kind());
+        assert_eq!(value["upload_time"], 0);
+        assert_eq!(value["file"]["md5"], "");
+    }
+
+    #[test]
+    fn test_from_legacy() {
+        let legacy = json!([
+            {"type": "text", "content": "hi"},
+            {"type": "reply", "reply": {
+                "seq": 42, "to_uid": "u_a", "from_uid": "u_b",
+                "reply_to": [{"type": "text", "content": "src"}],
+                "reply_msg": [{"type": "text", "content": "re"}]
+            }},
+            {"type": "image", "image": {"url": "https://x", "upload_time": "", "richmedia": {}}}
+        ]);
+        assert!(Element::parse_body("{", 0).is_err());
+        assert!(Element::parse_body(r#"[{"type":"text","text":1}]"#, 1).is_err());
+        let elems = Element::parse_body(&legacy.to_string(), 0).unwrap();
+        assert_eq!(elems.len(), 3);
+        assert_eq!(elems[0], Element::Text(TextElement { text: "hi".to_string() }));
+        match &elems[1] {
+            Element::Reply(r) => {
+                assert_eq!(r.seq, 42);
+                assert_eq!(r.source, vec![Element::Text(TextElement { text: "src".to_string() })]);
+                assert_eq!(r.content, vec![Element::Text(TextElement { text: "re".to_string() })]);
+            }
+            other => panic!("unexpected element: {:?}", other),
+        }
+        match &elems[2] {
+            Element::Image(i) => assert_eq!(i.upload_time, 0),
+            other => panic!("unexpected element: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::onebot::Segment;
+
+/// 消息段数组的CQ码字符串
+pub fn to_cq_string(segments: &[Segment]) -> String {
+    segments.iter().map(to_cq).collect()
+}
+
+/// 单个消息段的CQ码，文本段只转义特殊字符
+pub fn to_cq(segment: &Segment) -> String {
+    let (kind, params): (&str, Vec<(&str, &str)>) = match segment {
+        Segment::Text { text } => return escape_cq(text, false),
+        Segment::Face { id } => ("face", vec![("id", id)]),
+        Segment::At { qq } => ("at", vec![("qq", qq)]),
+        Segment::Image { file, url } => ("image", vec![("file", file), ("url", url)]),
+        Segment::Record { file, url } => ("record", vec![("file", file), ("url", url)]),
+        Segment::Video { file, url } => ("video", vec![("file", file), ("url", url)]),
+        Segment::Reply { id } => ("reply", vec![("id", id)]),
+        Segment::Forward { id } => ("forward", vec![("id", id)]),
+        Segment::Json { data } => ("json", vec![("data", data)]),
+    };
+    let mut cq = format!("[CQ:{}", kind);
+    for (key, value) in params {
+        cq.push_str(&format!(",{}={}", key, escape_cq(value, true)));
+    }
+    cq.push(']');
+    cq
+}
+
+/// 解析CQ码字符串
+///
+/// 不支持的CQ码和格式错误的 `[CQ:` 按原文保留为文本。
+pub fn parse_cq(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[CQ:") {
+        plain.push_str(&rest[..start]);
+        let code = &rest[start..];
+        match code.find(']').and_then(|end| Some((parse_code(&code[4..end])?, end))) {
+            Some((segment, end)) => {
+                if !plain.is_empty() {
+                    segments.push(Segment::Text { text: unescape_cq(&plain) });
+                    plain.clear();
+                }
+                segments.push(segment);
+                rest = &code[end + 1..];
+            }
+            None => {
+                plain.push_str("[CQ:");
+                rest = &code[4..];
+            }
+        }
+    }
+    plain.push_str(rest);
+    if !plain.is_empty() {
+        segments.push(Segment::Text { text: unescape_cq(&plain) });
+    }
+    segments
+}
+
+/// 解析 `类型,键=值,...`（不含 `[CQ:` 和 `]`）
+fn parse_code(code: &str) -> Option<Segment> {
+    let mut parts = code.split(',');
+    let kind = parts.next()?;
+    let mut params = Vec::new();
+    for part in parts {
+        let (key, value) = part.split_once('=')?;
+        params.push((key, unescape_cq(value)));
+    }
+    let get = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default()
+    };
+    Some(match kind {
+        "face" => Segment::Face { id: get("id") },
+        "at" => Segment::At { qq: get("qq") },
+        "image" => Segment::Image { file: get("file"), url: get("url") },
+        "record" => Segment::Record { file: get("file"), url: get("url") },
+        "video" => Segment::Video { file: get("file"), url: get("url") },
+        "reply" => Segment::Reply { id: get("id") },
+        "forward" => Segment::Forward { id: get("id") },
+        "json" => Segment::Json { data: get("data") },
+        _ => return None,
+    })
+}
+
+/// 转义CQ码特殊字符，参数值中还需转义逗号
+pub fn escape_cq(text: &str, in_param: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '[' => out.push_str("&#91;"),
+            ']' => out.push_str("&#93;"),
+            ',' if in_param => out.push_str("&#44;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 还原 [`escape_cq`] 的转义
+pub fn unescape_cq(text: &str) -> String {
+    text.replace("&#44;", ",")
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cq_roundtrip() {
+        let segments = vec![
+            Segment::Reply { id: "42".to_string() },
+            Segment::At { qq: "10001".to_string() },
+            Segment::Text { text: " [1,2] &amp;".to_string() },
+            Segment::Json { data: r#"{"app":"com.tencent.miniapp","a":[1,2]}"#.to_string() },
+        ];
+        let cq = to_cq_string(&segments);
+        assert_eq!(
+            cq,
+            "[CQ:reply,id=42][CQ:at,qq=10001] &#91;1,2&#93; &amp;amp;\
+             [CQ:json,data={\"app\":\"com.tencent.miniapp\"&#44;\"a\":&#91;1&#44;2&#93;}]"
+        );
+        assert_eq!(parse_cq(&cq), segments);
+
+        // 不支持的CQ码保留为文本
+        assert_eq!(
+            parse_cq("a[CQ:shake]b[CQ:face,id=14"),
+            vec![Segment::Text { text: "a[CQ:shake]b[CQ:face,id=14".to_string() }]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+pub mod cq;
+pub mod v12;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use crate::elem::face_elem::face_name;
+use crate::export::media_file_name;
+use crate::message::{
+    CustomElement, Element, FaceElement, FileInfo, ImageElement, MentionElement, ReplyElement, TextElement,
+    VideoElement, VoiceElement,
+};
+
+pub use cq::{parse_cq, to_cq_string};
+
+/// 合并转发的自定义元素类型，`data` 为 `{"res_id": "..."}`
+pub const CUSTOM_FORWARD: &str = "forward";
+/// JSON卡片消息的自定义元素类型，`data` 为 `{"data": "<JSON字符串>"}`
+pub const CUSTOM_JSON: &str = "json";
+/// XML卡片消息的自定义元素类型，`data` 为 `{"data": "<XML字符串>"}`，没有对应的消息段，以纯文本表示
+pub const CUSTOM_XML: &str = "xml";
+
+/// 消息的表示形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    /// OneBot v11 消息段数组
+    V11,
+    /// OneBot v12 消息段数组
+    V12,
+    /// CQ码字符串
+    Cq,
+}
+
+impl FromStr for MessageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "v11" => Ok(MessageFormat::V11),
+            "v12" => Ok(MessageFormat::V12),
+            "cq" => Ok(MessageFormat::Cq),
+            _ => bail!("无效的消息格式: {}，应为 v11、v12 或 cq", s),
+        }
+    }
+}
+
+impl MessageFormat {
+    /// 按格式输出消息段
+    pub fn render(&self, segments: &[Segment]) -> Value {
+        match self {
+            MessageFormat::V11 => json!(segments),
+            MessageFormat::V12 => Value::Array(v12::to_v12(segments)),
+            MessageFormat::Cq => Value::String(to_cq_string(segments)),
+        }
+    }
+
+    /// 按内容判断格式：字符串为CQ码，数组中出现v12特有的类型或 `file_id` 时为v12，否则为v11
+    pub fn detect(message: &Value) -> Option<MessageFormat> {
+        match message {
+            Value::String(_) => Some(MessageFormat::Cq),
+            Value::Array(items) => {
+                let is_v12 = items.iter().any(|item| {
+                    let kind = item.get("type").and_then(Value::as_str).unwrap_or("");
+                    matches!(kind, "mention" | "mention_all" | "voice" | "audio")
+                        || kind.starts_with("qq.")
+                        || item.pointer("/data/file_id").is_some()
+                });
+                Some(if is_v12 { MessageFormat::V12 } else { MessageFormat::V11 })
+            }
+            _ => None,
+        }
+    }
+
+    /// 解析为消息段，v11中不支持的类型被忽略
+    pub fn parse(&self, message: &Value) -> Vec<Segment> {
+        match (self, message) {
+            (MessageFormat::Cq, Value::String(text)) => parse_cq(text),
+            (MessageFormat::V11, Value::Array(items)) => items.iter().filter_map(Segment::from_value).collect(),
+            (MessageFormat::V12, Value::Array(items)) => v12::from_v12(items),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// OneBot v11 消息段，`data` 中的值按标准均为字符串
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Segment {
+    Text { text: String },
+    Face { id: String },
+    /// `qq` 为QQ号或 `all`
+    At { qq: String },
+    Image { file: String, url: String },
+    Record { file: String, url: String },
+    Video { file: String, url: String },
+    /// `id` 为被回复消息的message_id
+    Reply { id: String },
+    /// `id` 为合并转发的res_id
+    Forward { id: String },
+    Json { data: String },
+}
+
+impl Segment {
+    /// 将消息元素转换为消息段
+    ///
+    /// `reply_id` 将被回复消息的msg_seq转换为message_id，找不到时回复段被省略，只保留回复内容。
+    /// 没有对应消息段的元素以及缺少文件名的媒体以纯文本表示。
+    pub fn from_elements<F>(elems: &[Element], reply_id: &mut F) -> Vec<Segment>
+    where
+        F: FnMut(i64) -> Option<i64>,
+    {
+        let mut segments = Vec::new();
+        for elem in elems {
+            match elem {
+                Element::Text(t) => segments.push(Segment::Text { text: t.text.clone() }),
+                Element::Face(f) => segments.push(Segment::Face { id: f.id.to_string() }),
+                Element::Mention(m) if m.all => segments.push(Segment::At { qq: "all".to_string() }),
+                Element::Mention(m) if m.uin != 0 => segments.push(Segment::At { qq: m.uin.to_string() }),
+                Element::Image(image) => {
+                    match media_file_name(&image.file.md5, &image.file_uuid, &image.file.file_name, "jpg") {
+                        Some(file) => segments.push(Segment::Image { file, url: image.url.clone() }),
+                        None => segments.push(Segment::Text { text: elem.plain_text() }),
+                    }
+                }
+                Element::Voice(voice) => {
+                    match media_file_name(&voice.file.md5, &voice.file_uuid, &voice.file.file_name, "amr") {
+                        Some(file) => segments.push(Segment::Record { file, url: voice.url.clone() }),
+                        None => segments.push(Segment::Text { text: elem.plain_text() }),
+                    }
+                }
+                Element::Video(video) => {
+                    match media_file_name(&video.file.md5, &video.file_uuid, &video.file.file_name, "mp4") {
+                        Some(file) => segments.push(Segment::Video { file, url: video.url.clone() }),
+                        None => segments.push(Segment::Text { text: elem.plain_text() }),
+                    }
+                }
+                Element::Reply(reply) => {
+                    if let Some(id) = reply_id(reply.seq) {
+                        segments.push(Segment::Reply { id: id.to_string() });
+                    }
+                    segments.extend(Segment::from_elements(&reply.content, reply_id));
+                }
+                Element::Custom(custom) if custom.kind == CUSTOM_FORWARD => {
+                    let id = custom.data.get("res_id").and_then(Value::as_str).unwrap_or("");
+                    segments.push(Segment::Forward { id: id.to_string() });
+                }
+                Element::Custom(custom) if custom.kind == CUSTOM_JSON => {
+                    let data = custom.data.get("data").and_then(Value::as_str).unwrap_or("");
+                    segments.push(Segment::Json { data: data.to_string() });
+                }
+                _ => segments.push(Segment::Text { text: elem.plain_text() }),
+            }
+        }
+
+        // 合并相邻的文本段
+        let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+        for segment in segments {
+            match (merged.last_mut(), segment) {
+                (_, Segment::Text { text }) if text.is_empty() => {}
+                (Some(Segment::Text { text: last }), Segment::Text { text }) => last.push_str(&text),
+                (_, segment) => merged.push(segment),
+            }
+        }
+        merged
+    }
+
+    /// 将消息段转换回消息元素
+    ///
+    /// `reply_seq` 将回复段的message_id转换为被回复消息的msg_seq，找不到时忽略回复段；
+    /// 回复段之后的消息段成为回复元素的内容。媒体只保留文件名和http(s)链接，
+    /// `file://` 等本地链接会被丢弃，避免导出时读取任意本地文件。
+    pub fn to_elements<F>(segments: &[Segment], reply_seq: &mut F) -> Vec<Element>
+    where
+        F: FnMut(&str) -> Option<i64>,
+    {
+        let mut elems = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let elem = match segment {
+                Segment::Text { text } => Element::Text(TextElement { text: text.clone() }),
+                Segment::Face { id } => {
+                    let id = id.parse().unwrap_or(0);
+                    Element::Face(FaceElement { id, name: face_name(id).to_string() })
+                }
+                Segment::At { qq } if qq == "all" => {
+                    Element::Mention(MentionElement { all: true, ..Default::default() })
+                }
+                Segment::At { qq } => Element::Mention(MentionElement {
+                    uin: qq.parse().unwrap_or(0),
+                    ..Default::default()
+                }),
+                Segment::Image { file, url } => Element::Image(ImageElement {
+                    url: remote_url(file, url),
+                    file: file_info(file),
+                    ..Default::default()
+                }),
+                Segment::Record { file, url } => Element::Voice(VoiceElement {
+                    url: remote_url(file, url),
+                    file: file_info(file),
+                    ..Default::default()
+                }),
+                Segment::Video { file, url } => Element::Video(VideoElement {
+                    url: remote_url(file, url),
+                    file: file_info(file),
+                    ..Default::default()
+                }),
+                Segment::Reply { id } => match reply_seq(id) {
+                    Some(seq) => {
+                        let content = Segment::to_elements(&segments[i + 1..], reply_seq);
+                        elems.push(Element::Reply(ReplyElement { seq, content, ..Default::default() }));
+                        break;
+                    }
+                    None => continue,
+                },
+                Segment::Forward { id } => Element::Custom(CustomElement {
+                    kind: CUSTOM_FORWARD.to_string(),
+                    data: json!({ "res_id": id }),
+                }),
+                Segment::Json { data } => Element::Custom(CustomElement {
+                    kind: CUSTOM_JSON.to_string(),
+                    data: json!({ "data": data }),
+                }),
+            };
+            elems.push(elem);
+        }
+        elems
+    }
+
+    /// 解析OneBot v11的消息段对象，`data` 中的值可以是字符串或数字；不支持的类型返回None
+    pub fn from_value(value: &Value) -> Option<Segment> {
+        let data = value.get("data").unwrap_or(&Value::Null);
+        let get = |key: &str| match data.get(key) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Number(n)) => n.to_string(),
+            Some(Value::Bool(b)) => b.to_string(),
+            _ => String::new(),
+        };
+        Some(match value.get("type")?.as_str()? {
+            "text" => Segment::Text { text: get("text") },
+            "face" => Segment::Face { id: get("id") },
+            "at" => Segment::At { qq: get("qq") },
+            "image" => Segment::Image { file: get("file"), url: get("url") },
+            "record" => Segment::Record { file: get("file"), url: get("url") },
+            "video" => Segment::Video { file: get("file"), url: get("url") },
+            "reply" => Segment::Reply { id: get("id") },
+            "forward" => Segment::Forward { id: get("id") },
+            "json" => Segment::Json { data: get("data") },
+            _ => return None,
+        })
+    }
+}
+
+/// 媒体的远程链接，`url` 不是http(s)链接时尝试 `file`，都不是时为空字符串
+fn remote_url(file: &str, url: &str) -> String {
+    let is_remote = |s: &str| {
+        let s = s.trim().to_ascii_lowercase();
+        s.starts_with("http://") || s.starts_with("https://")
+    };
+    [url, file].into_iter().find(|s| is_remote(s)).unwrap_or("").trim().to_string()
+}
+
+/// 由媒体文件名恢复文件信息，`<md5>.<扩展名>` 形式的文件名同时填入md5
+fn file_info(file: &str) -> FileInfo {
+    let stem = file.split('.').next().unwrap_or("");
+    let md5 = if stem.len() == 32 && stem.chars().all(|c| c.is_ascii_hexdigit()) {
+        stem.to_ascii_lowercase()
+    } else {
+        String::new()
+    };
+    FileInfo { md5, file_name: file.to_string(), ..Default::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_roundtrip() {
+        let elems = vec![
+            Element::Reply(ReplyElement {
+                seq: 7,
+                content: vec![
+                    Element::Mention(MentionElement { uin: 10001, name: "张三".to_string(), ..Default::default() }),
+                    Element::Text(TextElement { text: " [1,2]".to_string() }),
+                    Element::Face(FaceElement { id: 14, name: "微笑".to_string() }),
+                ],
+                ..Default::default()
+            }),
+        ];
+        let segments = Segment::from_elements(&elems, &mut |seq| (seq == 7).then_some(42));
+
+        let value = serde_json::to_value(&segments).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"type": "reply", "data": {"id": "42"}},
+                {"type": "at", "data": {"qq": "10001"}},
+                {"type": "text", "data": {"text": " [1,2]"}},
+                {"type": "face", "data": {"id": "14"}},
+            ])
+        );
+        let parsed: Vec<Segment> = value.as_array().unwrap().iter().filter_map(Segment::from_value).collect();
+        assert_eq!(parsed, segments);
+
+        let back = Segment::to_elements(&parsed, &mut |id| (id == "42").then_some(7));
+        let Element::Reply(reply) = &back[0] else { panic!("应为回复元素") };
+        assert_eq!(reply.seq, 7);
+        assert_eq!(reply.content.len(), 3);
+        assert_eq!(reply.content[2], Element::Face(FaceElement { id: 14, name: "微笑".to_string() }));
+    }
+
+    #[test]
+    fn test_remote_url() {
+        assert_eq!(remote_url("a.jpg", "https://example.com/a.jpg"), "https://example.com/a.jpg");
+        assert_eq!(remote_url("http://example.com/a.jpg", ""), "http://example.com/a.jpg");
+        assert_eq!(remote_url("a.jpg", "file:///etc/passwd"), "");
+        assert_eq!(remote_url("/etc/passwd", "local:../../etc/passwd"), "");
+    }
+}
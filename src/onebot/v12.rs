@@ -0,0 +1,52 @@
+use serde_json::{json, Value};
+use crate::onebot::Segment;
+
+/// 转换为OneBot v12消息段
+///
+/// v12没有的类型使用 `qq.` 前缀的扩展段（`qq.face`、`qq.forward`、`qq.json`），
+/// 媒体的 `file_id` 为文件名，下载链接放在扩展字段 `qq.url` 中。
+pub fn to_v12(segments: &[Segment]) -> Vec<Value> {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text { text } => json!({"type": "text", "data": {"text": text}}),
+            Segment::Face { id } => json!({"type": "qq.face", "data": {"id": id}}),
+            Segment::At { qq } if qq == "all" => json!({"type": "mention_all", "data": {}}),
+            Segment::At { qq } => json!({"type": "mention", "data": {"user_id": qq}}),
+            Segment::Image { file, url } => json!({"type": "image", "data": {"file_id": file, "qq.url": url}}),
+            Segment::Record { file, url } => json!({"type": "voice", "data": {"file_id": file, "qq.url": url}}),
+            Segment::Video { file, url } => json!({"type": "video", "data": {"file_id": file, "qq.url": url}}),
+            Segment::Reply { id } => json!({"type": "reply", "data": {"message_id": id}}),
+            Segment::Forward { id } => json!({"type": "qq.forward", "data": {"id": id}}),
+            Segment::Json { data } => json!({"type": "qq.json", "data": {"data": data}}),
+        })
+        .collect()
+}
+
+/// 解析OneBot v12消息段，忽略不支持的类型
+pub fn from_v12(values: &[Value]) -> Vec<Segment> {
+    values
+        .iter()
+        .filter_map(|value| {
+            let data = value.get("data").unwrap_or(&Value::Null);
+            let get = |key: &str| match data.get(key) {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Number(n)) => n.to_string(),
+                _ => String::new(),
+            };
+            Some(match value.get("type")?.as_str()? {
+                "text" => Segment::Text { text: get("text") },
+                "qq.face" => Segment::Face { id: get("id") },
+                "mention_all" => Segment::At { qq: "all".to_string() },
+                "mention" => Segment::At { qq: get("user_id") },
+                "image" => Segment::Image { file: get("file_id"), url: get("qq.url") },
+                "voice" | "audio" => Segment::Record { file: get("file_id"), url: get("qq.url") },
+                "video" => Segment::Video { file: get("file_id"), url: get("qq.url") },
+                "reply" => Segment::Reply { id: get("message_id") },
+                "qq.forward" => Segment::Forward { id: get("id") },
+                "qq.json" => Segment::Json { data: get("data") },
+                _ => return None,
+            })
+        })
+        .collect()
+}
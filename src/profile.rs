@@ -0,0 +1,248 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// 指定默认账号配置的环境变量
+pub const PROFILE_ENV: &str = "QQHISTORY_PROFILE";
+
+static CURRENT: OnceLock<Profile> = OnceLock::new();
+
+/// 账号配置
+///
+/// 每个配置有独立的 cookie.json、uids.json、db 目录和 config.json，
+/// 保存在 `$XDG_DATA_HOME/qqhistory/profiles/<名称>`（默认 `~/.local/share`，Windows为 `%APPDATA%`）。
+/// 不指定配置时使用当前目录，与旧版本的布局一致。
+#[derive(Debug, Clone)]
+pub struct Profile {
+    name: Option<String>,
+    root: PathBuf,
+}
+
+/// 配置文件 config.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// 导出时的发送者名称，QQ号到名称，`--name` 优先
+    pub names: BTreeMap<String, String>,
+    /// `watch` 的默认设置
+    pub watch: WatchConfig,
+}
+
+/// config.json 中的 `watch`，命令行参数优先
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// 私聊对象的QQ号或uid
+    pub peers: Vec<String>,
+    /// 群号
+    pub groups: Vec<i64>,
+    /// 轮询间隔（秒）
+    pub interval: Option<u64>,
+    /// 同时用漫游消息接口拉取私聊
+    pub roam: bool,
+}
+
+impl Profile {
+    /// 当前目录的配置
+    pub fn cwd() -> Self {
+        Profile { name: None, root: PathBuf::new() }
+    }
+
+    /// 以 `root` 为目录的配置，用于测试
+    #[cfg(test)]
+    pub(crate) fn at(root: PathBuf) -> Self {
+        Profile { name: None, root }
+    }
+
+    /// 名称为 `name` 的已有配置，目录不存在时报错
+    pub fn named(name: &str) -> Result<Self> {
+        Self::open(&Self::profiles_dir()?, name, false)
+    }
+
+    /// 名称为 `name` 的配置，目录不存在时创建
+    pub fn create(name: &str) -> Result<Self> {
+        Self::open(&Self::profiles_dir()?, name, true)
+    }
+
+    fn open(dir: &Path, name: &str, create: bool) -> Result<Self> {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            bail!("无效的配置名称: {}，只能包含字母、数字、_ 和 -", name);
+        }
+        let root = dir.join(name);
+        if create {
+            fs::create_dir_all(&root).with_context(|| format!("无法创建配置目录: {}", root.display()))?;
+        } else if !root.is_dir() {
+            bail!("配置不存在: {}（{}），使用 login import 或拉取消息时创建", name, root.display());
+        }
+        Ok(Profile { name: Some(name.to_string()), root })
+    }
+
+    /// 设置本次运行使用的配置，`name` 为None时读取环境变量 [`PROFILE_ENV`]
+    ///
+    /// `create` 为true时创建不存在的配置目录，只用于会写入配置的命令。
+    pub fn init(name: Option<&str>, create: bool) -> Result<&'static Profile> {
+        let name = name
+            .map(str::to_string)
+            .or_else(|| env::var(PROFILE_ENV).ok().filter(|s| !s.is_empty()));
+        let profile = match name {
+            Some(name) if create => Self::create(&name)?,
+            Some(name) => Self::named(&name)?,
+            None => Self::cwd(),
+        };
+        Ok(CURRENT.get_or_init(|| profile))
+    }
+
+    /// 本次运行使用的配置，未初始化时为当前目录
+    pub fn current() -> &'static Profile {
+        CURRENT.get_or_init(Self::cwd)
+    }
+
+    /// 数据目录：`$XDG_DATA_HOME/qqhistory`
+    pub fn data_dir() -> Result<PathBuf> {
+        if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+            return Ok(PathBuf::from(dir).join("qqhistory"));
+        }
+        if cfg!(windows) {
+            if let Some(dir) = env::var_os("APPDATA") {
+                return Ok(PathBuf::from(dir).join("qqhistory"));
+            }
+        }
+        let home = env::var_os("HOME").context("无法确定数据目录，请设置 XDG_DATA_HOME 或 HOME")?;
+        Ok(PathBuf::from(home).join(".local").join("share").join("qqhistory"))
+    }
+
+    pub fn profiles_dir() -> Result<PathBuf> {
+        Ok(Self::data_dir()?.join("profiles"))
+    }
+
+    /// 所有已创建的配置，按名称排序
+    pub fn list() -> Result<Vec<Profile>> {
+        let dir = Self::profiles_dir()?;
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut profiles = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    profiles.push(Profile { name: Some(name.to_string()), root: entry.path() });
+                }
+            }
+        }
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(profiles)
+    }
+
+    /// 配置名称，当前目录时为None
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn cookie_path(&self) -> PathBuf {
+        self.root.join("cookie.json")
+    }
+
+    pub fn uids_path(&self) -> PathBuf {
+        self.root.join("uids.json")
+    }
+
+    pub fn db_dir(&self) -> PathBuf {
+        self.root.join("db")
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.root.join("config.json")
+    }
+
+    /// 导出的默认目录，当前目录时为 export，否则在配置目录下
+    pub fn export_dir(&self) -> PathBuf {
+        self.root.join("export")
+    }
+
+    /// 会话的数据库文件
+    pub fn db_file(&self, uid: &str) -> PathBuf {
+        self.db_dir().join(format!("{}.db", uid))
+    }
+
+    /// 解析 `--db` 参数：已存在或包含目录的路径原样使用，否则视为 db 目录下的会话，`.db` 后缀可省略
+    pub fn resolve_db(&self, db: &str) -> PathBuf {
+        let path = Path::new(db);
+        if path.exists() || path.components().count() > 1 {
+            return path.to_path_buf();
+        }
+        match db.strip_suffix(".db") {
+            Some(stem) => self.db_file(stem),
+            None => self.db_file(db),
+        }
+    }
+
+    /// uids.json 中uin到uid的映射，文件不存在或格式错误时为空
+    pub fn load_uids(&self) -> HashMap<String, String> {
+        fs::read_to_string(self.uids_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_uids(&self, uids: &HashMap<String, String>) -> Result<()> {
+        fs::write(self.uids_path(), serde_json::to_string_pretty(uids)?)?;
+        Ok(())
+    }
+
+    /// 读取 config.json，不存在时为默认配置
+    pub fn load_config(&self) -> Result<ProfileConfig> {
+        let path = self.config_path();
+        if !path.exists() {
+            return Ok(ProfileConfig::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).with_context(|| format!("解析 {} 失败", path.display()))
+    }
+}
+
+impl ProfileConfig {
+    /// `UIN=名称` 形式的名称列表，用于与 `--name` 合并
+    pub fn name_overrides(&self) -> Vec<String> {
+        self.names.iter().map(|(uin, name)| format!("{}={}", uin, name)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_db() {
+        let profile = Profile { name: Some("work".to_string()), root: PathBuf::from("/data/work") };
+        assert_eq!(profile.resolve_db("u_abc"), PathBuf::from("/data/work/db/u_abc.db"));
+        assert_eq!(profile.resolve_db("u_abc.db"), PathBuf::from("/data/work/db/u_abc.db"));
+        assert_eq!(profile.resolve_db("db/u_abc.db"), PathBuf::from("db/u_abc.db"));
+        assert!(Profile::named("../x").is_err());
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-profile-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let err = Profile::open(&dir, "alice", false).unwrap_err();
+        assert!(err.to_string().starts_with("配置不存在: alice"));
+        assert!(!dir.exists());
+
+        let profile = Profile::open(&dir, "alice", true).unwrap();
+        assert_eq!(profile.root(), dir.join("alice"));
+        assert!(profile.root().is_dir());
+        assert_eq!(Profile::open(&dir, "alice", false).unwrap().name(), Some("alice"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
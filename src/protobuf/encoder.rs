@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use base64::{Engine as _, engine::general_purpose};
+use crate::protobuf::schema::{Field, FieldKind, Label, Schema};
+use crate::protobuf::{Protobuf, WireType};
+
+impl Protobuf {
+    /// 将字段号映射序列化为Protobuf二进制数据
+    ///
+    /// 没有schema时按JSON类型推断编码：整数为varint，浮点数为double，
+    /// 字符串为UTF-8，对象为以字段号为键的嵌套消息，数组为重复字段，null被忽略。
+    /// 启发式解码无法区分字符串、嵌套消息和packed字段，[`Protobuf::deserialize`] 的结果重新编码后
+    /// 不一定得到原来的字节；需要往返一致时使用 [`Protobuf::serialize_with`]。
+    pub fn serialize(fields: &HashMap<u32, Value>) -> Result<Vec<u8>> {
+        let mut numbers: Vec<&u32> = fields.keys().collect();
+        numbers.sort();
+
+        let mut out = Vec::new();
+        for number in numbers {
+            Self::encode_inferred(&mut out, *number, &fields[number])?;
+        }
+        Ok(out)
+    }
+
+    /// 按schema将JSON对象序列化为Protobuf二进制数据，对schema中声明的字段与 [`Protobuf::deserialize_with`] 互逆
+    ///
+    /// 对象的键为schema中的字段名；未声明的字段可以用字段号作为键，按类型推断编码。
+    pub fn serialize_with(value: &Value, schema: &Schema) -> Result<Vec<u8>> {
+        let obj = value
+            .as_object()
+            .with_context(|| format!("{} 需要JSON对象", schema.name))?;
+
+        let mut entries: Vec<(u32, Option<&Field>, &Value)> = Vec::new();
+        for (key, value) in obj {
+            if let Some(field) = schema.fields.iter().find(|f| f.name == key) {
+                entries.push((field.number, Some(field), value));
+            } else if let Ok(number) = key.parse::<u32>() {
+                entries.push((number, schema.field(number), value));
+            } else {
+                return Err(anyhow!("{} 中没有字段 {}", schema.name, key));
+            }
+        }
+        entries.sort_by_key(|(number, _, _)| *number);
+
+        let mut out = Vec::new();
+        for (number, field, value) in entries {
+            match field {
+                Some(field) => Self::encode_field(&mut out, field, value)
+                    .with_context(|| format!("编码字段 {}.{} 失败", schema.name, field.name))?,
+                None => Self::encode_inferred(&mut out, number, value)?,
+            }
+        }
+        Ok(out)
+    }
+
+    /// 按字段描述编码
+    fn encode_field(out: &mut Vec<u8>, field: &Field, value: &Value) -> Result<()> {
+        if value.is_null() {
+            return Ok(());
+        }
+        if !field.is_repeated() {
+            return Self::encode_typed(out, field.number, field.kind, value);
+        }
+
+        let items = value.as_array().context("重复字段需要JSON数组")?;
+        if field.label == Label::Packed && field.kind.is_scalar_numeric() {
+            if items.is_empty() {
+                return Ok(());
+            }
+            let mut packed = Vec::new();
+            for item in items {
+                Self::encode_scalar(&mut packed, field.kind, item)?;
+            }
+            Self::encode_bytes(out, field.number, &packed);
+        } else {
+            for item in items {
+                Self::encode_typed(out, field.number, field.kind, item)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 编码带tag的单个值
+    fn encode_typed(out: &mut Vec<u8>, number: u32, kind: FieldKind, value: &Value) -> Result<()> {
+        match kind {
+            FieldKind::String => {
+                let s = value.as_str().context("需要字符串")?;
+                Self::encode_bytes(out, number, s.as_bytes());
+            }
+            FieldKind::Bytes => {
+                let s = value.as_str().context("需要base64字符串")?;
+                let bytes = general_purpose::STANDARD.decode(s).context("无效的base64")?;
+                Self::encode_bytes(out, number, &bytes);
+            }
+            FieldKind::Message(schema) => {
+                let nested = Self::serialize_with(value, schema)?;
+                Self::encode_bytes(out, number, &nested);
+            }
+            FieldKind::Group(schema) => {
+                Self::encode_tag(out, number, WireType::StartGroup);
+                out.extend(Self::serialize_with(value, schema)?);
+                Self::encode_tag(out, number, WireType::EndGroup);
+            }
+            _ => {
+                Self::encode_tag(out, number, Self::wire_type_of(kind));
+                Self::encode_scalar(out, kind, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 编码不带tag的数值
+    fn encode_scalar(out: &mut Vec<u8>, kind: FieldKind, value: &Value) -> Result<()> {
+        match kind {
+            FieldKind::Int | FieldKind::SignedInt => Self::encode_varint(out, Self::value_to_u64(value)?),
+            FieldKind::SInt => Self::encode_varint(out, Self::zigzag_encode(Self::value_to_i64(value)?)),
+            FieldKind::Bool => {
+                let b = match value {
+                    Value::Bool(b) => *b,
+                    _ => Self::value_to_u64(value)? != 0,
+                };
+                Self::encode_varint(out, b as u64);
+            }
+            FieldKind::Fixed32 => out.extend_from_slice(&(Self::value_to_u64(value)? as u32).to_le_bytes()),
+            FieldKind::Fixed64 => out.extend_from_slice(&Self::value_to_u64(value)?.to_le_bytes()),
+            FieldKind::SFixed32 => out.extend_from_slice(&(Self::value_to_i64(value)? as i32).to_le_bytes()),
+            FieldKind::SFixed64 => out.extend_from_slice(&Self::value_to_i64(value)?.to_le_bytes()),
+            FieldKind::Float => {
+                let f = value.as_f64().context("需要浮点数")?;
+                out.extend_from_slice(&(f as f32).to_le_bytes());
+            }
+            FieldKind::Double => {
+                let f = value.as_f64().context("需要浮点数")?;
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+            FieldKind::String | FieldKind::Bytes | FieldKind::Message(_) | FieldKind::Group(_) => {
+                return Err(anyhow!("{:?} 不是数值类型", kind));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按JSON类型推断编码
+    fn encode_inferred(out: &mut Vec<u8>, number: u32, value: &Value) -> Result<()> {
+        match value {
+            Value::Null => {}
+            Value::Bool(b) => {
+                Self::encode_tag(out, number, WireType::Varint);
+                Self::encode_varint(out, *b as u64);
+            }
+            Value::Number(n) if n.is_f64() => {
+                Self::encode_tag(out, number, WireType::Bit64);
+                out.extend_from_slice(&n.as_f64().unwrap_or(0.0).to_le_bytes());
+            }
+            Value::Number(_) => {
+                Self::encode_tag(out, number, WireType::Varint);
+                Self::encode_varint(out, Self::value_to_u64(value)?);
+            }
+            Value::String(s) => Self::encode_bytes(out, number, s.as_bytes()),
+            Value::Array(items) => {
+                for item in items {
+                    Self::encode_inferred(out, number, item)?;
+                }
+            }
+            Value::Object(obj) => {
+                let nested = Self::serialize(&Self::object_to_fields(obj)?)?;
+                Self::encode_bytes(out, number, &nested);
+            }
+        }
+        Ok(())
+    }
+
+    /// 将以字段号为键的JSON对象转换为字段号映射
+    fn object_to_fields(obj: &Map<String, Value>) -> Result<HashMap<u32, Value>> {
+        obj.iter()
+            .map(|(key, value)| {
+                let number = key
+                    .parse::<u32>()
+                    .with_context(|| format!("无效的字段号: {}", key))?;
+                Ok((number, value.clone()))
+            })
+            .collect()
+    }
+
+    /// 读取整数，负数按补码转换
+    fn value_to_u64(value: &Value) -> Result<u64> {
+        match value {
+            Value::Number(n) => n
+                .as_u64()
+                .or_else(|| n.as_i64().map(|v| v as u64))
+                .with_context(|| format!("需要整数: {}", n)),
+            Value::String(s) => s
+                .parse::<u64>()
+                .or_else(|_| s.parse::<i64>().map(|v| v as u64))
+                .with_context(|| format!("需要整数: {}", s)),
+            Value::Bool(b) => Ok(*b as u64),
+            _ => Err(anyhow!("需要整数: {}", value)),
+        }
+    }
+
+    /// 读取有符号整数
+    fn value_to_i64(value: &Value) -> Result<i64> {
+        match value {
+            Value::Number(n) => n
+                .as_i64()
+                .or_else(|| n.as_u64().map(|v| v as i64))
+                .with_context(|| format!("需要整数: {}", n)),
+            Value::String(s) => s
+                .parse::<i64>()
+                .or_else(|_| s.parse::<u64>().map(|v| v as i64))
+                .with_context(|| format!("需要整数: {}", s)),
+            Value::Bool(b) => Ok(*b as i64),
+            _ => Err(anyhow!("需要整数: {}", value)),
+        }
+    }
+
+    /// 编码长度限定值
+    fn encode_bytes(out: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+        Self::encode_tag(out, number, WireType::LengthDelimited);
+        Self::encode_varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    }
+
+    /// 编码标签（tag）
+    fn encode_tag(out: &mut Vec<u8>, number: u32, wire_type: WireType) {
+        Self::encode_varint(out, ((number as u64) << 3) | wire_type as u64);
+    }
+
+    /// 编码变长整数（varint）
+    pub(crate) fn encode_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_varint() {
+        let mut out = Vec::new();
+        Protobuf::encode_varint(&mut out, 150);
+        assert_eq!(out, vec![0x96, 0x01]);
+        let (value, _) = Protobuf::decode_varint(&out, 0).unwrap();
+        assert_eq!(value, 150);
+    }
+
+    #[test]
+    fn test_round_trip_inferred() {
+        let fields: HashMap<u32, Value> = [
+            (1, json!(150)),
+            (2, json!("hello world")),
+            (3, json!({"1": 7, "2": "nested string"})),
+            (4, json!([1, 2, 3])),
+        ]
+        .into_iter()
+        .collect();
+
+        let bytes = Protobuf::serialize(&fields).unwrap();
+        let decoded = Protobuf::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, fields);
+    }
+
+    #[test]
+    fn test_round_trip_schema() {
+        static INNER: Schema = Schema {
+            name: "Inner",
+            fields: &[
+                Field::optional(1, "name", FieldKind::String),
+                Field::optional(2, "raw", FieldKind::Bytes),
+            ],
+        };
+        static OUTER: Schema = Schema {
+            name: "Outer",
+            fields: &[
+                Field::optional(1, "id", FieldKind::Int),
+                Field::optional(2, "ok", FieldKind::Bool),
+                Field::repeated(3, "items", FieldKind::Message(&INNER)),
+                Field::packed(4, "nums", FieldKind::Int),
+                Field::optional(5, "ratio", FieldKind::Double),
+                Field::optional(6, "crc", FieldKind::Fixed32),
+                Field::optional(7, "delta", FieldKind::SInt),
+                Field::optional(8, "offset", FieldKind::SFixed64),
+                Field::optional(10, "big", FieldKind::Fixed64),
+                Field::optional(11, "legacy", FieldKind::Group(&INNER)),
+            ],
+        };
+
+        let value = json!({
+            "id": 42,
+            "ok": true,
+            "items": [{"name": "a", "raw": "AAEC"}, {"name": "b"}],
+            "nums": [1, 300, 70000],
+            "ratio": 0.5,
+            "crc": 3735928559u32,
+            "delta": -150,
+            "offset": "-9223372036854775808",
+            "big": "18446744073709551615",
+            "legacy": {"name": "group"},
+            "9": 9,
+        });
+        let bytes = Protobuf::serialize_with(&value, &OUTER).unwrap();
+        assert_eq!(Protobuf::deserialize_with(&bytes, &OUTER).unwrap(), value);
+    }
+}
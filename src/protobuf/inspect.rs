@@ -0,0 +1,283 @@
+use anyhow::Result;
+use serde::Serialize;
+use crate::protobuf::{Protobuf, WireType};
+
+/// 字段的逐字节解码结果，用于逆向分析
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub field_number: u32,
+    pub wire_type: WireType,
+    /// tag在输入中的字节偏移
+    pub offset: usize,
+    /// 值在输入中的起止偏移（不含tag和长度前缀）
+    pub start: usize,
+    pub end: usize,
+    /// 所有可能的解释
+    pub interpretations: Vec<Interpretation>,
+    /// 能完整解码为嵌套消息或group时的子字段
+    pub children: Option<Vec<Node>>,
+}
+
+/// 值的一种可能解释
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "as", content = "value", rename_all = "snake_case")]
+pub enum Interpretation {
+    Uint(u64),
+    Int(i64),
+    Sint(i64),
+    Bool(bool),
+    Fixed32(u32),
+    Sfixed32(i32),
+    Float(f32),
+    Fixed64(u64),
+    Sfixed64(i64),
+    Double(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    /// 按packed varint解码
+    Packed(Vec<u64>),
+}
+
+/// 两个负载之间的差异
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+}
+
+impl Protobuf {
+    /// 解码为带偏移和所有可能解释的字段树
+    pub fn inspect(data: &[u8]) -> Result<Vec<Node>> {
+        Self::check_size(data)?;
+        let (nodes, _) = Self::inspect_fields(data, 0, 0, None, 0)?;
+        Ok(nodes)
+    }
+
+    /// 解码字段，`base` 为 `data` 在原始输入中的偏移
+    fn inspect_fields(
+        data: &[u8],
+        base: usize,
+        mut offset: usize,
+        group: Option<u32>,
+        depth: usize,
+    ) -> Result<(Vec<Node>, usize)> {
+        Self::check_depth(depth)?;
+        let mut nodes = Vec::new();
+
+        while offset < data.len() {
+            let tag_offset = offset;
+            let (field_number, wire_type, new_offset) = Self::decode_tag(data, offset)?;
+            offset = new_offset;
+
+            if wire_type == WireType::EndGroup {
+                return Self::end_group(field_number, group).map(|_| (nodes, offset));
+            }
+
+            let mut node = Node {
+                field_number,
+                wire_type,
+                offset: base + tag_offset,
+                start: base + offset,
+                end: base + offset,
+                interpretations: Vec::new(),
+                children: None,
+            };
+
+            match wire_type {
+                WireType::Varint => {
+                    let (value, new_offset) = Self::decode_varint(data, offset)?;
+                    offset = new_offset;
+                    node.interpretations = Self::varint_interpretations(value);
+                }
+                WireType::Bit64 => {
+                    let (bytes, new_offset) = Self::read_fixed::<8>(data, offset)?;
+                    offset = new_offset;
+                    node.interpretations = vec![
+                        Interpretation::Fixed64(u64::from_le_bytes(bytes)),
+                        Interpretation::Sfixed64(i64::from_le_bytes(bytes)),
+                        Interpretation::Double(f64::from_le_bytes(bytes)),
+                    ];
+                }
+                WireType::Bit32 => {
+                    let (bytes, new_offset) = Self::read_fixed::<4>(data, offset)?;
+                    offset = new_offset;
+                    node.interpretations = vec![
+                        Interpretation::Fixed32(u32::from_le_bytes(bytes)),
+                        Interpretation::Sfixed32(i32::from_le_bytes(bytes)),
+                        Interpretation::Float(f32::from_le_bytes(bytes)),
+                    ];
+                }
+                WireType::LengthDelimited => {
+                    let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+                    node.start = base + new_offset - bytes.len();
+                    offset = new_offset;
+
+                    node.children = Self::inspect_fields(bytes, node.start, 0, None, depth + 1)
+                        .ok()
+                        .map(|(children, _)| children);
+                    if let Ok(s) = std::str::from_utf8(bytes) {
+                        node.interpretations.push(Interpretation::String(s.to_string()));
+                    }
+                    if let Some(packed) = Self::packed_varints(bytes) {
+                        node.interpretations.push(Interpretation::Packed(packed));
+                    }
+                    node.interpretations.push(Interpretation::Bytes(bytes.to_vec()));
+                }
+                WireType::StartGroup => {
+                    let (children, new_offset) = Self::inspect_fields(data, base, offset, Some(field_number), depth + 1)?;
+                    offset = new_offset;
+                    node.children = Some(children);
+                }
+                WireType::EndGroup => unreachable!(),
+            }
+
+            node.end = base + offset;
+            nodes.push(node);
+        }
+
+        Self::end_of_data(group).map(|_| (nodes, offset))
+    }
+
+    /// varint的所有解释
+    fn varint_interpretations(value: u64) -> Vec<Interpretation> {
+        let mut result = vec![
+            Interpretation::Uint(value),
+            Interpretation::Int(value as i64),
+            Interpretation::Sint(Self::zigzag_decode(value)),
+        ];
+        if value <= 1 {
+            result.push(Interpretation::Bool(value == 1));
+        }
+        result
+    }
+
+    /// 尝试按packed varint解码，至少两个值才视为packed
+    fn packed_varints(bytes: &[u8]) -> Option<Vec<u64>> {
+        let mut values = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let (value, new_pos) = Self::decode_varint(bytes, pos).ok()?;
+            values.push(value);
+            pos = new_pos;
+        }
+        (values.len() >= 2).then_some(values)
+    }
+
+    /// 比较两个负载的字段树
+    ///
+    /// 字段按路径对齐，路径形如 `1[0].2[1]`，中括号内为同一字段重复出现的序号。
+    pub fn diff(a: &[Node], b: &[Node]) -> Vec<Difference> {
+        let left = Self::flatten(a);
+        let right = Self::flatten(b);
+
+        let mut result = Vec::new();
+        for (path, value) in &left {
+            match right.iter().find(|(p, _)| p == path) {
+                None => result.push(Difference::Removed(path.clone(), value.clone())),
+                Some((_, other)) if other != value => {
+                    result.push(Difference::Changed(path.clone(), value.clone(), other.clone()))
+                }
+                _ => {}
+            }
+        }
+        for (path, value) in &right {
+            if !left.iter().any(|(p, _)| p == path) {
+                result.push(Difference::Added(path.clone(), value.clone()));
+            }
+        }
+        result
+    }
+
+    /// 将字段树展开为 (路径, 叶子值摘要) 列表，能解码为嵌套消息的字段继续展开
+    fn flatten(nodes: &[Node]) -> Vec<(String, String)> {
+        let mut result = Vec::new();
+        Self::flatten_into(nodes, "", &mut result);
+        result
+    }
+
+    fn flatten_into(nodes: &[Node], prefix: &str, out: &mut Vec<(String, String)>) {
+        let mut counts = std::collections::HashMap::new();
+        for node in nodes {
+            let index = counts.entry(node.field_number).or_insert(0);
+            let path = format!("{}{}[{}]", prefix, node.field_number, index);
+            *index += 1;
+
+            match &node.children {
+                Some(children) if !children.is_empty() => {
+                    Self::flatten_into(children, &format!("{}.", path), out)
+                }
+                _ => out.push((path, Self::summary(node))),
+            }
+        }
+    }
+
+    /// 叶子值摘要：优先使用第一种解释
+    pub fn summary(node: &Node) -> String {
+        match node.interpretations.first() {
+            Some(Interpretation::Uint(v)) => v.to_string(),
+            Some(Interpretation::Fixed32(v)) => format!("0x{:08x}", v),
+            Some(Interpretation::Fixed64(v)) => format!("0x{:016x}", v),
+            Some(Interpretation::String(s)) => format!("{:?}", s),
+            Some(other) => Self::describe(other),
+            None => String::from("{}"),
+        }
+    }
+
+    /// 单个解释的文本形式
+    pub fn describe(interpretation: &Interpretation) -> String {
+        match interpretation {
+            Interpretation::Uint(v) => format!("uint={}", v),
+            Interpretation::Int(v) => format!("int={}", v),
+            Interpretation::Sint(v) => format!("sint={}", v),
+            Interpretation::Bool(v) => format!("bool={}", v),
+            Interpretation::Fixed32(v) => format!("fixed32={}", v),
+            Interpretation::Sfixed32(v) => format!("sfixed32={}", v),
+            Interpretation::Float(v) => format!("float={}", v),
+            Interpretation::Fixed64(v) => format!("fixed64={}", v),
+            Interpretation::Sfixed64(v) => format!("sfixed64={}", v),
+            Interpretation::Double(v) => format!("double={}", v),
+            Interpretation::String(s) => format!("string={:?}", s),
+            Interpretation::Bytes(b) => format!(
+                "bytes={}",
+                b.iter().map(|x| format!("{:02x}", x)).collect::<Vec<_>>().join(" ")
+            ),
+            Interpretation::Packed(v) => format!("packed={:?}", v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_offsets() {
+        // 1: 150, 2: {1: "a"}
+        let data = [0x08, 0x96, 0x01, 0x12, 0x03, 0x0a, 0x01, 0x61];
+        let nodes = Protobuf::inspect(&data).unwrap();
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!((nodes[0].offset, nodes[0].start, nodes[0].end), (0, 1, 3));
+        assert!(nodes[0].interpretations.contains(&Interpretation::Sint(75)));
+
+        let children = nodes[1].children.as_ref().unwrap();
+        assert_eq!((nodes[1].offset, nodes[1].start, nodes[1].end), (3, 5, 8));
+        assert_eq!((children[0].offset, children[0].start, children[0].end), (5, 7, 8));
+        assert_eq!(children[0].interpretations[0], Interpretation::String("a".to_string()));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = Protobuf::inspect(&[0x08, 0x01, 0x12, 0x03, 0x0a, 0x01, 0x61]).unwrap();
+        let b = Protobuf::inspect(&[0x08, 0x02, 0x12, 0x03, 0x0a, 0x01, 0x61, 0x18, 0x05]).unwrap();
+
+        assert_eq!(
+            Protobuf::diff(&a, &b),
+            vec![
+                Difference::Changed("1[0]".to_string(), "1".to_string(), "2".to_string()),
+                Difference::Added("3[0]".to_string(), "5".to_string()),
+            ]
+        );
+    }
+}
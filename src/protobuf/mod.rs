@@ -0,0 +1,652 @@
+pub mod encoder;
+pub mod inspect;
+pub mod schema;
+pub mod view;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use base64::{Engine as _, engine::general_purpose};
+use crate::protobuf::schema::{FieldKind, Label, Schema};
+
+/// 最大嵌套层数，超过时嵌套消息按字节处理、group解码失败
+pub const MAX_DEPTH: usize = 64;
+
+/// 最大输入长度
+pub const MAX_INPUT_SIZE: usize = 64 * 1024 * 1024;
+
+/// varint最大字节数
+const MAX_VARINT_LEN: usize = 10;
+
+/// JSON中可以无损表示的最大整数（2^53 - 1），超出的64位整数以字符串表示
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Protobuf Wire类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireType {
+    Varint = 0,
+    Bit64 = 1,
+    LengthDelimited = 2,
+    /// 已废弃的group开始标签
+    StartGroup = 3,
+    /// 已废弃的group结束标签
+    EndGroup = 4,
+    Bit32 = 5,
+}
+
+impl WireType {
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(WireType::Varint),
+            1 => Ok(WireType::Bit64),
+            2 => Ok(WireType::LengthDelimited),
+            3 => Ok(WireType::StartGroup),
+            4 => Ok(WireType::EndGroup),
+            5 => Ok(WireType::Bit32),
+            _ => Err(anyhow!("不支持的wire type: {}", value)),
+        }
+    }
+}
+
+/// Protobuf反序列化器
+pub struct Protobuf;
+
+impl Protobuf {
+    /// 反序列化Protobuf二进制数据为HashMap
+    ///
+    /// 没有schema时无法区分整数的符号：varint按无符号整数输出，
+    /// 需要时可以用 [`Protobuf::zigzag_decode`] 或 `as i64` 转换。
+    pub fn deserialize(data: &[u8]) -> Result<HashMap<u32, Value>> {
+        Self::check_size(data)?;
+        let (result, _) = Self::decode_fields(data, 0, None, 0)?;
+        Ok(result)
+    }
+
+    /// 解码字段，直到数据结束或遇到 `group` 对应的结束标签
+    fn decode_fields(
+        data: &[u8],
+        mut offset: usize,
+        group: Option<u32>,
+        depth: usize,
+    ) -> Result<(HashMap<u32, Value>, usize)> {
+        Self::check_depth(depth)?;
+        let mut result = HashMap::new();
+
+        while offset < data.len() {
+            let (field_number, wire_type, new_offset) = Self::decode_tag(data, offset)?;
+            offset = new_offset;
+
+            if wire_type == WireType::EndGroup {
+                return Self::end_group(field_number, group).map(|_| (result, offset));
+            }
+
+            let (value, new_offset) = Self::decode_value(data, offset, field_number, wire_type, depth)?;
+            offset = new_offset;
+
+            // 处理重复字段
+            match result.get_mut(&field_number) {
+                Some(existing) => Self::append_value(existing, value),
+                None => {
+                    result.insert(field_number, value);
+                }
+            }
+        }
+
+        Self::end_of_data(group).map(|_| (result, offset))
+    }
+
+    /// 按schema反序列化Protobuf二进制数据
+    ///
+    /// schema中声明的字段以字段名为键、按声明的类型解码，重复字段始终为数组；
+    /// 未声明或wire type与声明不符的字段以字段号为键回退到 [`Protobuf::deserialize`] 的启发式解码。
+    pub fn deserialize_with(data: &[u8], schema: &Schema) -> Result<Value> {
+        Self::check_size(data)?;
+        let (result, _) = Self::decode_fields_with(data, 0, schema, None, 0)?;
+        Ok(result)
+    }
+
+    /// 按schema解码字段，直到数据结束或遇到 `group` 对应的结束标签
+    fn decode_fields_with(
+        data: &[u8],
+        mut offset: usize,
+        schema: &Schema,
+        group: Option<u32>,
+        depth: usize,
+    ) -> Result<(Value, usize)> {
+        Self::check_depth(depth)?;
+        let mut result = Map::new();
+        for field in schema.fields.iter().filter(|f| f.is_repeated()) {
+            result.insert(field.name.to_string(), json!([]));
+        }
+
+        while offset < data.len() {
+            let (field_number, wire_type, new_offset) = Self::decode_tag(data, offset)?;
+            offset = new_offset;
+
+            if wire_type == WireType::EndGroup {
+                return Self::end_group(field_number, group).map(|_| (Value::Object(result), offset));
+            }
+
+            match schema.field(field_number) {
+                Some(field) if Self::wire_type_of(field.kind) == wire_type => {
+                    let (value, new_offset) = Self::decode_typed(data, offset, field_number, field.kind, depth)?;
+                    offset = new_offset;
+
+                    if let (true, Some(Value::Array(arr))) = (field.is_repeated(), result.get_mut(field.name)) {
+                        arr.push(value);
+                    } else {
+                        result.insert(field.name.to_string(), value);
+                    }
+                }
+                Some(field)
+                    if field.label == Label::Packed
+                        && wire_type == WireType::LengthDelimited
+                        && field.kind.is_scalar_numeric() =>
+                {
+                    let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+                    offset = new_offset;
+
+                    let mut values = Vec::new();
+                    let mut pos = 0;
+                    while pos < bytes.len() {
+                        let (value, new_pos) = Self::decode_typed(bytes, pos, field_number, field.kind, depth)?;
+                        values.push(value);
+                        pos = new_pos;
+                    }
+                    if let Some(Value::Array(arr)) = result.get_mut(field.name) {
+                        arr.extend(values);
+                    }
+                }
+                _ => {
+                    let (value, new_offset) = Self::decode_value(data, offset, field_number, wire_type, depth)?;
+                    offset = new_offset;
+
+                    let key = field_number.to_string();
+                    match result.get_mut(&key) {
+                        Some(existing) => Self::append_value(existing, value),
+                        None => {
+                            result.insert(key, value);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self::end_of_data(group).map(|_| (Value::Object(result), offset))
+    }
+
+    /// 检查输入长度
+    fn check_size(data: &[u8]) -> Result<()> {
+        if data.len() > MAX_INPUT_SIZE {
+            return Err(anyhow!("输入超过 {} 字节", MAX_INPUT_SIZE));
+        }
+        Ok(())
+    }
+
+    /// 检查嵌套层数
+    fn check_depth(depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(anyhow!("嵌套层数超过 {}", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    /// 检查group结束标签是否与开始标签匹配
+    fn end_group(field_number: u32, group: Option<u32>) -> Result<()> {
+        match group {
+            Some(number) if number == field_number => Ok(()),
+            _ => Err(anyhow!("意外的group结束标签: {}", field_number)),
+        }
+    }
+
+    /// 数据结束时检查是否还有未结束的group
+    fn end_of_data(group: Option<u32>) -> Result<()> {
+        match group {
+            Some(number) => Err(anyhow!("group {} 缺少结束标签", number)),
+            None => Ok(()),
+        }
+    }
+
+    /// zigzag解码（sint32/sint64）
+    pub fn zigzag_decode(value: u64) -> i64 {
+        ((value >> 1) as i64) ^ -((value & 1) as i64)
+    }
+
+    /// zigzag编码（sint32/sint64）
+    pub fn zigzag_encode(value: i64) -> u64 {
+        ((value << 1) ^ (value >> 63)) as u64
+    }
+
+    /// 无符号整数转JSON，超出 [`MAX_SAFE_INTEGER`] 时输出字符串
+    pub fn u64_to_json(value: u64) -> Value {
+        if value > MAX_SAFE_INTEGER {
+            json!(value.to_string())
+        } else {
+            json!(value)
+        }
+    }
+
+    /// 有符号整数转JSON，绝对值超出 [`MAX_SAFE_INTEGER`] 时输出字符串
+    pub fn i64_to_json(value: i64) -> Value {
+        if value.unsigned_abs() > MAX_SAFE_INTEGER {
+            json!(value.to_string())
+        } else {
+            json!(value)
+        }
+    }
+
+    /// 将重复出现的字段值合并为数组
+    fn append_value(existing: &mut Value, value: Value) {
+        if let Value::Array(arr) = existing {
+            arr.push(value);
+        } else {
+            *existing = json!([existing.take(), value]);
+        }
+    }
+
+    /// 字段类型对应的wire type
+    fn wire_type_of(kind: FieldKind) -> WireType {
+        match kind {
+            FieldKind::Int | FieldKind::SignedInt | FieldKind::SInt | FieldKind::Bool => WireType::Varint,
+            FieldKind::Fixed64 | FieldKind::SFixed64 | FieldKind::Double => WireType::Bit64,
+            FieldKind::Fixed32 | FieldKind::SFixed32 | FieldKind::Float => WireType::Bit32,
+            FieldKind::String | FieldKind::Bytes | FieldKind::Message(_) => WireType::LengthDelimited,
+            FieldKind::Group(_) => WireType::StartGroup,
+        }
+    }
+
+    /// 按声明的类型解码值
+    fn decode_typed(
+        data: &[u8],
+        offset: usize,
+        field_number: u32,
+        kind: FieldKind,
+        depth: usize,
+    ) -> Result<(Value, usize)> {
+        match kind {
+            FieldKind::Int => {
+                let (value, new_offset) = Self::decode_varint(data, offset)?;
+                Ok((Self::u64_to_json(value), new_offset))
+            }
+            FieldKind::SignedInt => {
+                let (value, new_offset) = Self::decode_varint(data, offset)?;
+                Ok((Self::i64_to_json(value as i64), new_offset))
+            }
+            FieldKind::SInt => {
+                let (value, new_offset) = Self::decode_varint(data, offset)?;
+                Ok((Self::i64_to_json(Self::zigzag_decode(value)), new_offset))
+            }
+            FieldKind::Bool => {
+                let (value, new_offset) = Self::decode_varint(data, offset)?;
+                Ok((json!(value != 0), new_offset))
+            }
+            FieldKind::Fixed32 => Self::decode_32bit(data, offset),
+            FieldKind::Fixed64 => Self::decode_64bit(data, offset),
+            FieldKind::SFixed32 => {
+                let (bytes, new_offset) = Self::read_fixed::<4>(data, offset)?;
+                Ok((json!(i32::from_le_bytes(bytes)), new_offset))
+            }
+            FieldKind::SFixed64 => {
+                let (bytes, new_offset) = Self::read_fixed::<8>(data, offset)?;
+                Ok((Self::i64_to_json(i64::from_le_bytes(bytes)), new_offset))
+            }
+            FieldKind::Float => {
+                let (bytes, new_offset) = Self::read_fixed::<4>(data, offset)?;
+                Ok((json!(f32::from_le_bytes(bytes)), new_offset))
+            }
+            FieldKind::Double => {
+                let (bytes, new_offset) = Self::read_fixed::<8>(data, offset)?;
+                Ok((json!(f64::from_le_bytes(bytes)), new_offset))
+            }
+            FieldKind::String => {
+                let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+                Ok((json!(String::from_utf8_lossy(bytes)), new_offset))
+            }
+            FieldKind::Bytes => {
+                let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+                Ok((json!(general_purpose::STANDARD.encode(bytes)), new_offset))
+            }
+            FieldKind::Message(schema) => {
+                let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+                let (value, _) = Self::decode_fields_with(bytes, 0, schema, None, depth + 1)?;
+                Ok((value, new_offset))
+            }
+            FieldKind::Group(schema) => Self::decode_fields_with(data, offset, schema, Some(field_number), depth + 1),
+        }
+    }
+
+    /// 读取定长字节
+    fn read_fixed<const N: usize>(data: &[u8], offset: usize) -> Result<([u8; N], usize)> {
+        let bytes: [u8; N] = offset
+            .checked_add(N)
+            .and_then(|end| data.get(offset..end))
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or_else(|| anyhow!("{}位值数据不足", N * 8))?;
+        Ok((bytes, offset + N))
+    }
+
+    /// 读取长度限定值的原始字节
+    fn read_length_delimited(data: &[u8], offset: usize) -> Result<(&[u8], usize)> {
+        let (length, new_offset) = Self::decode_varint(data, offset)?;
+        let end = usize::try_from(length)
+            .ok()
+            .and_then(|length| new_offset.checked_add(length))
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| anyhow!("长度限定值数据不足"))?;
+
+        Ok((&data[new_offset..end], end))
+    }
+
+    /// 解码标签（tag）
+    fn decode_tag(data: &[u8], offset: usize) -> Result<(u32, WireType, usize)> {
+        let (tag, new_offset) = Self::decode_varint(data, offset)?;
+        let field_number = (tag >> 3) as u32;
+        let wire_type = WireType::from_u8((tag & 0x07) as u8)?;
+        Ok((field_number, wire_type, new_offset))
+    }
+
+    /// 解码变长整数（varint）
+    fn decode_varint(data: &[u8], mut offset: usize) -> Result<(u64, usize)> {
+        let mut value: u64 = 0;
+
+        for i in 0..MAX_VARINT_LEN {
+            let byte = *data
+                .get(offset)
+                .ok_or_else(|| anyhow!("读取varint时数据意外结束"))?;
+            offset += 1;
+
+            // 第10个字节只剩最高1位可用
+            if i == MAX_VARINT_LEN - 1 && byte > 1 {
+                return Err(anyhow!("varint超出64位"));
+            }
+
+            value |= ((byte & 0x7F) as u64) << (7 * i);
+
+            if (byte & 0x80) == 0 {
+                return Ok((value, offset));
+            }
+        }
+
+        Err(anyhow!("varint超出64位"))
+    }
+
+    /// 解码64位值
+    fn decode_64bit(data: &[u8], offset: usize) -> Result<(Value, usize)> {
+        let (bytes, new_offset) = Self::read_fixed::<8>(data, offset)?;
+        Ok((Self::u64_to_json(u64::from_le_bytes(bytes)), new_offset))
+    }
+
+    /// 解码32位值
+    fn decode_32bit(data: &[u8], offset: usize) -> Result<(Value, usize)> {
+        let (bytes, new_offset) = Self::read_fixed::<4>(data, offset)?;
+        Ok((json!(u32::from_le_bytes(bytes)), new_offset))
+    }
+
+    /// 解码长度限定值（length-delimited）
+    fn decode_length_delimited(data: &[u8], offset: usize, depth: usize) -> Result<(Value, usize)> {
+        let (bytes, new_offset) = Self::read_length_delimited(data, offset)?;
+
+        // 尝试递归解析嵌套消息
+        match Self::decode_fields(bytes, 0, None, depth + 1) {
+            Ok((nested, _)) => {
+                // 成功解析为嵌套消息
+                let nested_json: Value = serde_json::to_value(nested)?;
+                Ok((nested_json, new_offset))
+            }
+            Err(_) => {
+                // 无法解析为嵌套消息，尝试作为字符串
+                if let Ok(s) = std::str::from_utf8(bytes) {
+                    Ok((json!(s), new_offset))
+                } else {
+                    // 作为base64编码的字节数组
+                    let encoded = general_purpose::STANDARD.encode(bytes);
+                    Ok((json!(encoded), new_offset))
+                }
+            }
+        }
+    }
+
+    /// 解码值
+    fn decode_value(
+        data: &[u8],
+        offset: usize,
+        field_number: u32,
+        wire_type: WireType,
+        depth: usize,
+    ) -> Result<(Value, usize)> {
+        match wire_type {
+            WireType::Varint => {
+                let (value, new_offset) = Self::decode_varint(data, offset)?;
+                Ok((Self::u64_to_json(value), new_offset))
+            }
+            WireType::Bit64 => Self::decode_64bit(data, offset),
+            WireType::LengthDelimited => Self::decode_length_delimited(data, offset, depth),
+            WireType::StartGroup => {
+                // group按嵌套消息输出
+                let (fields, new_offset) = Self::decode_fields(data, offset, Some(field_number), depth + 1)?;
+                Ok((serde_json::to_value(fields)?, new_offset))
+            }
+            WireType::EndGroup => Err(anyhow!("意外的group结束标签: {}", field_number)),
+            WireType::Bit32 => Self::decode_32bit(data, offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_varint() {
+        let data = vec![0x96, 0x01]; // 150
+        let (value, offset) = Protobuf::decode_varint(&data, 0).unwrap();
+        assert_eq!(value, 150);
+        assert_eq!(offset, 2);
+    }
+
+    #[test]
+    fn test_deserialize_with_schema() {
+        use crate::protobuf::schema::Field;
+
+        static INNER: Schema = Schema {
+            name: "Inner",
+            fields: &[Field::optional(1, "name", FieldKind::String)],
+        };
+        static OUTER: Schema = Schema {
+            name: "Outer",
+            fields: &[
+                Field::optional(1, "id", FieldKind::Int),
+                Field::optional(2, "label", FieldKind::String),
+                Field::repeated(3, "items", FieldKind::Message(&INNER)),
+                Field::packed(4, "nums", FieldKind::Int),
+            ],
+        };
+
+        // 字段2是两个字节的字符串，启发式解码会把它当作嵌套消息 {1: 0}
+        let data = [
+            0x08, 0x96, 0x01, // 1: 150
+            0x12, 0x02, 0x08, 0x00, // 2: "\x08\x00"
+            0x1a, 0x03, 0x0a, 0x01, 0x61, // 3: {1: "a"}
+            0x22, 0x03, 0x01, 0x02, 0x03, // 4: packed [1, 2, 3]
+            0x20, 0x04, // 4: unpacked 4
+            0x28, 0x05, // 5: 未声明
+        ];
+        let value = Protobuf::deserialize_with(&data, &OUTER).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "id": 150,
+                "label": "\u{8}\u{0}",
+                "items": [{"name": "a"}],
+                "nums": [1, 2, 3, 4],
+                "5": 5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_group_and_large_integers() {
+        // 1: group { 2: 1 }, 3: u64::MAX, 4: fixed64 2^60
+        let mut data = vec![0x0b, 0x10, 0x01, 0x0c, 0x18];
+        data.extend([0xff; 9]);
+        data.push(0x01);
+        data.push(0x21);
+        data.extend((1u64 << 60).to_le_bytes());
+
+        let decoded = Protobuf::deserialize(&data).unwrap();
+        assert_eq!(decoded[&1], json!({"2": 1}));
+        assert_eq!(decoded[&3], json!("18446744073709551615"));
+        assert_eq!(decoded[&4], json!("1152921504606846976"));
+
+        // 未闭合或不匹配的group
+        assert!(Protobuf::deserialize(&[0x0b, 0x10, 0x01]).is_err());
+        assert!(Protobuf::deserialize(&[0x0b, 0x14]).is_err());
+    }
+
+    #[test]
+    fn test_signed_interpretations() {
+        use crate::protobuf::schema::Field;
+
+        static SIGNED: Schema = Schema {
+            name: "Signed",
+            fields: &[
+                Field::optional(1, "raw", FieldKind::Int),
+                Field::optional(2, "signed", FieldKind::SignedInt),
+                Field::packed(3, "zigzag", FieldKind::SInt),
+            ],
+        };
+
+        assert_eq!(Protobuf::zigzag_decode(3), -2);
+        assert_eq!(Protobuf::zigzag_encode(-2), 3);
+
+        // 1: 1, 2: -1（10字节补码）, 3: packed [-1, 1, -64]
+        let mut data = vec![0x08, 0x01, 0x10];
+        data.extend([0xff; 9]);
+        data.push(0x01);
+        data.extend([0x1a, 0x03, 0x01, 0x02, 0x7f]);
+
+        let decoded = Protobuf::deserialize_with(&data, &SIGNED).unwrap();
+        assert_eq!(decoded, json!({"raw": 1, "signed": -1, "zigzag": [-1, 1, -64]}));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        // 11字节的varint
+        let mut data = vec![0x08];
+        data.extend([0xff; 10]);
+        data.push(0x01);
+        assert!(Protobuf::deserialize(&data).is_err());
+
+        // 第10字节超出64位
+        let mut data = vec![0x08];
+        data.extend([0xff; 9]);
+        data.push(0x02);
+        assert!(Protobuf::deserialize(&data).is_err());
+
+        // 长度接近 u64::MAX
+        let mut data = vec![0x0a];
+        data.extend([0xff; 9]);
+        data.push(0x01);
+        assert!(Protobuf::deserialize(&data).is_err());
+
+        // 深层group
+        assert!(Protobuf::deserialize(&[0x0b; 10_000]).is_err());
+        assert!(Protobuf::inspect(&[0x0b; 10_000]).is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // 200层嵌套消息：超过限制的部分按字节输出，不会栈溢出
+        let mut data = vec![0x08, 0x01];
+        for _ in 0..200 {
+            let mut outer = vec![0x0a];
+            Protobuf::encode_varint(&mut outer, data.len() as u64);
+            outer.extend(data);
+            data = outer;
+        }
+
+        let mut value = serde_json::to_value(Protobuf::deserialize(&data).unwrap()).unwrap();
+        let mut depth = 0;
+        while let Some(inner) = value.get("1").cloned() {
+            value = inner;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_DEPTH + 1);
+        assert!(value.is_string());
+    }
+
+    mod properties {
+        use super::*;
+        use crate::protobuf::schema::Field;
+        use proptest::prelude::*;
+
+        static INNER: Schema = Schema {
+            name: "Inner",
+            fields: &[Field::optional(1, "name", FieldKind::String)],
+        };
+        static ALL: Schema = Schema {
+            name: "All",
+            fields: &[
+                Field::optional(1, "uint", FieldKind::Int),
+                Field::optional(2, "int", FieldKind::SignedInt),
+                Field::optional(3, "sint", FieldKind::SInt),
+                Field::optional(4, "fixed64", FieldKind::Fixed64),
+                Field::optional(5, "sfixed32", FieldKind::SFixed32),
+                Field::optional(6, "name", FieldKind::String),
+                Field::optional(7, "raw", FieldKind::Bytes),
+                Field::packed(8, "nums", FieldKind::Int),
+                Field::repeated(9, "items", FieldKind::Message(&INNER)),
+                Field::optional(10, "group", FieldKind::Group(&INNER)),
+            ],
+        };
+
+        proptest! {
+            #[test]
+            fn prop_decoders_never_panic(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+                let _ = Protobuf::deserialize(&data);
+                let _ = Protobuf::deserialize_with(&data, &ALL);
+                let _ = Protobuf::deserialize_with(&data, &crate::elem::schemas::MSG_INFO);
+                let _ = Protobuf::inspect(&data);
+            }
+
+            #[test]
+            fn prop_varint_round_trip(value in any::<u64>()) {
+                let mut out = Vec::new();
+                Protobuf::encode_varint(&mut out, value);
+                prop_assert_eq!(Protobuf::decode_varint(&out, 0).unwrap(), (value, out.len()));
+            }
+
+            #[test]
+            fn prop_schema_round_trip(
+                uint in any::<u64>(),
+                int in any::<i64>(),
+                sint in any::<i64>(),
+                fixed64 in any::<u64>(),
+                sfixed32 in any::<i32>(),
+                name in ".{0,16}",
+                raw in proptest::collection::vec(any::<u8>(), 0..32),
+                nums in proptest::collection::vec(any::<u64>(), 0..8),
+                items in proptest::collection::vec(".{0,8}", 0..4),
+            ) {
+                let value = json!({
+                    "uint": Protobuf::u64_to_json(uint),
+                    "int": Protobuf::i64_to_json(int),
+                    "sint": Protobuf::i64_to_json(sint),
+                    "fixed64": Protobuf::u64_to_json(fixed64),
+                    "sfixed32": sfixed32,
+                    "name": name,
+                    "raw": general_purpose::STANDARD.encode(&raw),
+                    "nums": nums.iter().map(|n| Protobuf::u64_to_json(*n)).collect::<Vec<_>>(),
+                    "items": items.iter().map(|n| json!({"name": n})).collect::<Vec<_>>(),
+                    "group": {"name": name},
+                });
+
+                let bytes = Protobuf::serialize_with(&value, &ALL).unwrap();
+                prop_assert_eq!(Protobuf::deserialize_with(&bytes, &ALL).unwrap(), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+/// 字段类型
+#[derive(Debug, Clone, Copy)]
+pub enum FieldKind {
+    /// varint，无符号整数（uint32/uint64/enum）
+    Int,
+    /// varint，按补码解释的有符号整数（int32/int64）
+    SignedInt,
+    /// varint，zigzag编码的有符号整数（sint32/sint64）
+    SInt,
+    /// varint，布尔值
+    Bool,
+    /// length-delimited，UTF-8字符串
+    String,
+    /// length-delimited，原始字节，输出为base64
+    Bytes,
+    /// 32位定长整数
+    Fixed32,
+    /// 64位定长整数
+    Fixed64,
+    /// 32位定长有符号整数
+    SFixed32,
+    /// 64位定长有符号整数
+    SFixed64,
+    /// 32位浮点数
+    Float,
+    /// 64位浮点数
+    Double,
+    /// length-delimited，嵌套消息
+    Message(&'static Schema),
+    /// 已废弃的group，按嵌套消息输出
+    Group(&'static Schema),
+}
+
+impl FieldKind {
+    /// 按proto中的标量类型名解析，如 `sint64`、`fixed32`；嵌套消息和group需要另外指定schema
+    pub fn from_type_name(name: &str) -> Option<FieldKind> {
+        let kind = match name {
+            "uint32" | "uint64" | "enum" => FieldKind::Int,
+            "int32" | "int64" => FieldKind::SignedInt,
+            "sint32" | "sint64" => FieldKind::SInt,
+            "bool" => FieldKind::Bool,
+            "string" => FieldKind::String,
+            "bytes" => FieldKind::Bytes,
+            "fixed32" => FieldKind::Fixed32,
+            "fixed64" => FieldKind::Fixed64,
+            "sfixed32" => FieldKind::SFixed32,
+            "sfixed64" => FieldKind::SFixed64,
+            "float" => FieldKind::Float,
+            "double" => FieldKind::Double,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// 是否可以使用packed编码
+    pub fn is_scalar_numeric(&self) -> bool {
+        matches!(
+            self,
+            FieldKind::Int
+                | FieldKind::SignedInt
+                | FieldKind::SInt
+                | FieldKind::Bool
+                | FieldKind::Fixed32
+                | FieldKind::Fixed64
+                | FieldKind::SFixed32
+                | FieldKind::SFixed64
+                | FieldKind::Float
+                | FieldKind::Double
+        )
+    }
+}
+
+/// 字段标签
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Label {
+    /// 单值字段，重复出现时后者覆盖前者
+    Optional,
+    /// 重复字段，解码结果始终为数组
+    Repeated,
+    /// packed编码的重复字段，同时兼容未packed的编码
+    Packed,
+}
+
+/// 字段描述
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub number: u32,
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub label: Label,
+}
+
+impl Field {
+    pub const fn optional(number: u32, name: &'static str, kind: FieldKind) -> Self {
+        Field { number, name, kind, label: Label::Optional }
+    }
+
+    pub const fn repeated(number: u32, name: &'static str, kind: FieldKind) -> Self {
+        Field { number, name, kind, label: Label::Repeated }
+    }
+
+    pub const fn packed(number: u32, name: &'static str, kind: FieldKind) -> Self {
+        Field { number, name, kind, label: Label::Packed }
+    }
+
+    /// 是否为重复字段
+    pub fn is_repeated(&self) -> bool {
+        self.label != Label::Optional
+    }
+}
+
+/// 消息描述
+///
+/// 只需要列出关心的字段，未列出的字段按字段号回退到启发式解码。
+#[derive(Debug)]
+pub struct Schema {
+    pub name: &'static str,
+    pub fields: &'static [Field],
+}
+
+impl Schema {
+    /// 按字段号查找字段
+    pub fn field(&self, number: u32) -> Option<&Field> {
+        self.fields.iter().find(|f| f.number == number)
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{anyhow, Result};
+use crate::protobuf::schema::{FieldKind, Schema};
+use crate::protobuf::{Protobuf, WireType, MAX_DEPTH};
+
+/// 借用原始字节、按需解码的消息视图
+///
+/// 不分配内存、不构建JSON，访问字段时才扫描数据。重复访问同一字段会重新扫描，
+/// 适合只读取少数字段的场景；畸形数据在访问时被视为字段不存在。
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    data: &'a [u8],
+    depth: usize,
+}
+
+/// 借用的字段值
+#[derive(Debug, Clone, Copy)]
+pub enum FieldValue<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Fixed32(u32),
+    Bytes(&'a [u8]),
+    Group(MessageView<'a>),
+}
+
+/// 字段迭代器，遇到畸形数据时返回一次错误后结束
+pub struct Fields<'a> {
+    view: MessageView<'a>,
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> MessageView<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        MessageView { data, depth: 0 }
+    }
+
+    /// 按顺序遍历所有字段
+    pub fn fields(&self) -> Fields<'a> {
+        Fields { view: *self, offset: 0, failed: false }
+    }
+
+    /// 字段的最后一次出现（与protobuf单值字段的语义一致）
+    pub fn get(&self, number: u32) -> Option<FieldValue<'a>> {
+        self.fields()
+            .map_while(Result::ok)
+            .filter(|(n, _)| *n == number)
+            .last()
+            .map(|(_, v)| v)
+    }
+
+    /// 字段的所有出现
+    pub fn get_all(&self, number: u32) -> impl Iterator<Item = FieldValue<'a>> {
+        self.fields()
+            .map_while(Result::ok)
+            .filter(move |(n, _)| *n == number)
+            .map(|(_, v)| v)
+    }
+
+    pub fn uint(&self, number: u32) -> Option<u64> {
+        match self.get(number)? {
+            FieldValue::Varint(v) | FieldValue::Fixed64(v) => Some(v),
+            FieldValue::Fixed32(v) => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self, number: u32) -> Option<&'a [u8]> {
+        match self.get(number)? {
+            FieldValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, number: u32) -> Option<&'a str> {
+        std::str::from_utf8(self.bytes(number)?).ok()
+    }
+
+    pub fn message(&self, number: u32) -> Option<MessageView<'a>> {
+        self.get(number).and_then(|v| self.nested(v))
+    }
+
+    pub fn messages(&self, number: u32) -> impl Iterator<Item = MessageView<'a>> + '_ {
+        self.get_all(number).filter_map(|v| self.nested(v))
+    }
+
+    /// 将字段值作为嵌套消息，超过 [`MAX_DEPTH`] 时返回None
+    fn nested(&self, value: FieldValue<'a>) -> Option<MessageView<'a>> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        match value {
+            FieldValue::Bytes(data) => Some(MessageView { data, depth: self.depth + 1 }),
+            FieldValue::Group(view) => Some(view),
+            _ => None,
+        }
+    }
+
+    /// 从 `offset` 开始跳过group内容，返回 (内容结束位置, 结束标签之后的位置)
+    fn skip_group(&self, mut offset: usize, number: u32) -> Result<(usize, usize)> {
+        let data = self.data;
+        let mut open = vec![number];
+        loop {
+            if open.len() > MAX_DEPTH {
+                return Err(anyhow!("嵌套层数超过 {}", MAX_DEPTH));
+            }
+            let tag_offset = offset;
+            let (field_number, wire_type, new_offset) = Protobuf::decode_tag(data, offset)?;
+            offset = new_offset;
+            offset = match wire_type {
+                WireType::Varint => Protobuf::decode_varint(data, offset)?.1,
+                WireType::Bit64 => Protobuf::read_fixed::<8>(data, offset)?.1,
+                WireType::Bit32 => Protobuf::read_fixed::<4>(data, offset)?.1,
+                WireType::LengthDelimited => Protobuf::read_length_delimited(data, offset)?.1,
+                WireType::StartGroup => {
+                    open.push(field_number);
+                    offset
+                }
+                WireType::EndGroup => {
+                    if open.pop() != Some(field_number) {
+                        return Err(anyhow!("意外的group结束标签: {}", field_number));
+                    }
+                    if open.is_empty() {
+                        return Ok((tag_offset, offset));
+                    }
+                    offset
+                }
+            };
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u32, FieldValue<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.view.data;
+        if self.failed || self.offset >= data.len() {
+            return None;
+        }
+
+        let result = (|| {
+            let (number, wire_type, offset) = Protobuf::decode_tag(data, self.offset)?;
+            let (value, offset) = match wire_type {
+                WireType::Varint => {
+                    let (v, offset) = Protobuf::decode_varint(data, offset)?;
+                    (FieldValue::Varint(v), offset)
+                }
+                WireType::Bit64 => {
+                    let (b, offset) = Protobuf::read_fixed::<8>(data, offset)?;
+                    (FieldValue::Fixed64(u64::from_le_bytes(b)), offset)
+                }
+                WireType::Bit32 => {
+                    let (b, offset) = Protobuf::read_fixed::<4>(data, offset)?;
+                    (FieldValue::Fixed32(u32::from_le_bytes(b)), offset)
+                }
+                WireType::LengthDelimited => {
+                    let (b, offset) = Protobuf::read_length_delimited(data, offset)?;
+                    (FieldValue::Bytes(b), offset)
+                }
+                WireType::StartGroup => {
+                    let (end, after) = self.view.skip_group(offset, number)?;
+                    let view = MessageView { data: &data[offset..end], depth: self.view.depth + 1 };
+                    (FieldValue::Group(view), after)
+                }
+                WireType::EndGroup => return Err(anyhow!("意外的group结束标签: {}", number)),
+            };
+            self.offset = offset;
+            Ok((number, value))
+        })();
+
+        if result.is_err() {
+            self.failed = true;
+        }
+        Some(result)
+    }
+}
+
+/// 按schema中的字段名访问的消息视图
+///
+/// 字段名必须在schema中声明，嵌套消息自动使用声明的子schema。
+#[derive(Debug, Clone, Copy)]
+pub struct SchemaView<'a> {
+    view: MessageView<'a>,
+    schema: &'static Schema,
+}
+
+impl<'a> SchemaView<'a> {
+    pub fn new(data: &'a [u8], schema: &'static Schema) -> Self {
+        SchemaView { view: MessageView::new(data), schema }
+    }
+
+    /// 字段名对应的字段号和类型
+    fn field(&self, name: &str) -> (u32, FieldKind) {
+        let field = self.schema.fields.iter().find(|f| f.name == name);
+        debug_assert!(field.is_some(), "{} 中没有字段 {}", self.schema.name, name);
+        field.map(|f| (f.number, f.kind)).unwrap_or((0, FieldKind::Bytes))
+    }
+
+    pub fn uint(&self, name: &str) -> Option<u64> {
+        self.view.uint(self.field(name).0)
+    }
+
+    pub fn bytes(&self, name: &str) -> Option<&'a [u8]> {
+        self.view.bytes(self.field(name).0)
+    }
+
+    pub fn string(&self, name: &str) -> Option<&'a str> {
+        self.view.string(self.field(name).0)
+    }
+
+    pub fn message(&self, name: &str) -> Option<SchemaView<'a>> {
+        let (number, kind) = self.field(name);
+        let schema = Self::nested_schema(kind)?;
+        Some(SchemaView { view: self.view.message(number)?, schema })
+    }
+
+    pub fn messages(&self, name: &str) -> impl Iterator<Item = SchemaView<'a>> + '_ {
+        let (number, kind) = self.field(name);
+        let schema = Self::nested_schema(kind);
+        self.view
+            .messages(number)
+            .filter_map(move |view| Some(SchemaView { view, schema: schema? }))
+    }
+
+    fn nested_schema(kind: FieldKind) -> Option<&'static Schema> {
+        match kind {
+            FieldKind::Message(schema) | FieldKind::Group(schema) => Some(schema),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elem::schemas::MSG_INFO;
+    use serde_json::json;
+
+    #[test]
+    fn test_view_matches_decoder() {
+        let value = json!({
+            "bodies": [
+                {"index": {"info": {"size": 1024, "md5": "abc", "width": 640}, "file_uuid": "uuid-1"}},
+                {"index": {"info": {"size": 16}, "file_uuid": "uuid-2"}},
+            ],
+            "ext_biz_info": {"pic": {"c2c_reserve": {"rkey": "&rkey=x"}}},
+        });
+        let bytes = Protobuf::serialize_with(&value, &MSG_INFO).unwrap();
+
+        let view = SchemaView::new(&bytes, &MSG_INFO);
+        let bodies: Vec<_> = view.messages("bodies").collect();
+        assert_eq!(bodies.len(), 2);
+
+        let index = bodies[0].message("index").unwrap();
+        let info = index.message("info").unwrap();
+        assert_eq!(info.uint("size"), Some(1024));
+        assert_eq!(info.string("md5"), Some("abc"));
+        assert_eq!(info.uint("height"), None);
+        assert_eq!(index.string("file_uuid"), Some("uuid-1"));
+        assert_eq!(bodies[1].message("index").unwrap().string("file_uuid"), Some("uuid-2"));
+
+        let rkey = view
+            .message("ext_biz_info")
+            .and_then(|e| e.message("pic"))
+            .and_then(|p| p.message("c2c_reserve"))
+            .and_then(|r| r.string("rkey"));
+        assert_eq!(rkey, Some("&rkey=x"));
+    }
+
+    #[test]
+    fn test_view_groups_and_malformed() {
+        // 1: group { 2: 7 }, 3: 9
+        let data = [0x0b, 0x10, 0x07, 0x0c, 0x18, 0x09];
+        let view = MessageView::new(&data);
+        assert_eq!(view.message(1).and_then(|g| g.uint(2)), Some(7));
+        assert_eq!(view.uint(3), Some(9));
+
+        // 截断的数据：之前的字段仍可访问，之后的字段视为不存在
+        let data = [0x08, 0x01, 0x12, 0x05, 0x00];
+        let view = MessageView::new(&data);
+        assert_eq!(view.uint(1), Some(1));
+        assert_eq!(view.bytes(2), None);
+        assert!(view.fields().any(|f| f.is_err()));
+    }
+}
@@ -0,0 +1,433 @@
+pub mod onebot;
+
+use anyhow::{anyhow, Result};
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::database::{Database, StoredMessage};
+use crate::export::{media_file_name, MediaFetcher, SenderNames};
+use crate::message::Element;
+use crate::profile::Profile;
+
+/// 浏览器界面
+const INDEX_HTML: &str = include_str!("index.html");
+
+/// 每页默认条数
+const DEFAULT_LIMIT: i64 = 50;
+
+/// 每页最大条数
+const MAX_LIMIT: i64 = 500;
+
+/// 本地HTTP服务的共享状态
+///
+/// rusqlite的连接不能跨线程共享，每个请求在阻塞线程中单独打开数据库。
+pub struct ServeState {
+    dir: PathBuf,
+    names: SenderNames,
+    /// uid 到 uin 的映射，来自 uids.json
+    uins: HashMap<String, i64>,
+    media: MediaFetcher,
+}
+
+impl ServeState {
+    /// `dir` 为存放 `<uid>.db` 的目录，媒体缓存在其下的 media 目录
+    pub fn new(dir: PathBuf, names: SenderNames) -> Result<Self> {
+        let uins = Profile::current()
+            .load_uids()
+            .into_iter()
+            .filter_map(|(uin, uid)| Some((uid, uin.parse().ok()?)))
+            .collect();
+        let media = MediaFetcher::new(&dir.join("media"), true)?.with_local_root(dir.join("media"));
+        Ok(ServeState { dir, names, uins, media })
+    }
+
+    /// 会话id对应的数据库文件，id只允许字母、数字、`_` 和 `-`
+    fn db_path(&self, id: &str) -> Result<PathBuf, ApiError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(ApiError::BadRequest(format!("无效的会话id: {}", id)));
+        }
+        let path = self.dir.join(format!("{}.db", id));
+        if !path.is_file() {
+            return Err(ApiError::NotFound(format!("会话不存在: {}", id)));
+        }
+        Ok(path)
+    }
+
+    /// 目录下的所有会话id，按文件名排序
+    fn conversation_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == "db") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    ids.push(stem.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn message_view(&self, stored: StoredMessage) -> MessageView {
+        let from_uin = stored.message.routing_head.from_uin;
+        MessageView {
+            sender_name: self.names.name(from_uin),
+            is_self: self.names.is_self(&stored.message),
+            text: stored.message.plain_text(),
+            stored,
+        }
+    }
+}
+
+/// 接口错误，以 `{"error": "..."}` 的形式返回
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+        };
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// 会话概要
+#[derive(Debug, Serialize)]
+pub struct Conversation {
+    /// 数据库文件名（不含扩展名），通常为对方的uid
+    pub id: String,
+    /// 对方的QQ号，uids.json 中没有时为null
+    pub uin: Option<i64>,
+    /// 通过 --name 指定的名称，未指定时为QQ号
+    pub name: Option<String>,
+    pub message_count: i64,
+    pub first_time: Option<i64>,
+    pub last_time: Option<i64>,
+}
+
+/// 返回给前端的消息
+#[derive(Debug, Serialize)]
+pub struct MessageView {
+    #[serde(flatten)]
+    pub stored: StoredMessage,
+    pub sender_name: String,
+    pub is_self: bool,
+    /// 纯文本内容
+    pub text: String,
+}
+
+/// 分页结果，`next_cursor` 为null时没有更多消息
+#[derive(Debug, Serialize)]
+pub struct MessagePage {
+    pub messages: Vec<MessageView>,
+    pub next_cursor: Option<String>,
+}
+
+/// 消息分页参数，游标格式为 `msg_time:id`
+///
+/// 默认从最新的消息开始按时间倒序返回；指定 `after` 时按时间正序返回游标之后的消息。
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    before: Option<String>,
+    after: Option<String>,
+    limit: Option<i64>,
+}
+
+/// 搜索参数，`conversation` 为空时搜索所有会话
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    conversation: Option<String>,
+    limit: Option<i64>,
+}
+
+/// 搜索结果
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub conversation: String,
+    #[serde(flatten)]
+    pub message: MessageView,
+}
+
+/// 媒体参数，`thumb=true` 时返回视频的缩略图
+#[derive(Debug, Deserialize)]
+pub struct MediaQuery {
+    #[serde(default)]
+    thumb: bool,
+    /// 第 `index` 个元素为回复时，其内容中的第几个元素
+    content: Option<usize>,
+}
+
+/// 构建路由
+pub fn router(state: Arc<ServeState>) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/api/conversations", get(list_conversations))
+        .route("/api/conversations/:id/messages", get(list_messages))
+        .route("/api/conversations/:id/messages/:msg_id", get(get_message))
+        .route("/api/conversations/:id/messages/:msg_id/media/:index", get(get_media))
+        .route("/api/search", get(search))
+        .with_state(state)
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+/// 在阻塞线程中打开会话的数据库并执行查询
+async fn with_db<T, F>(state: &Arc<ServeState>, id: &str, f: F) -> ApiResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&ServeState, Database) -> Result<T> + Send + 'static,
+{
+    let path = state.db_path(id)?;
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || {
+        let db = Database::new(&path.to_string_lossy())?;
+        f(&state, db)
+    })
+    .await
+    .map_err(|e| anyhow!(e))?
+    .map_err(ApiError::from)
+}
+
+async fn list_conversations(State(state): State<Arc<ServeState>>) -> ApiResult<Json<Vec<Conversation>>> {
+    let state = state.clone();
+    let conversations = tokio::task::spawn_blocking(move || -> Result<Vec<Conversation>> {
+        let mut conversations = Vec::new();
+        for id in state.conversation_ids()? {
+            let db = Database::new(&state.dir.join(format!("{}.db", id)).to_string_lossy())?;
+            let first = db.get_messages_after(None, 1)?;
+            let last = db.get_messages_before(None, 1)?;
+            let uin = state.uins.get(&id).copied();
+            conversations.push(Conversation {
+                uin,
+                name: uin.map(|uin| state.names.name(uin)),
+                message_count: db.get_message_count()?,
+                first_time: first.first().map(|m| m.message.content_head.msg_time),
+                last_time: last.first().map(|m| m.message.content_head.msg_time),
+                id,
+            });
+        }
+        // 最近有消息的会话在前
+        conversations.sort_by_key(|c| std::cmp::Reverse(c.last_time));
+        Ok(conversations)
+    })
+    .await
+    .map_err(|e| anyhow!(e))??;
+    Ok(Json(conversations))
+}
+
+async fn list_messages(
+    State(state): State<Arc<ServeState>>,
+    UrlPath(id): UrlPath<String>,
+    Query(query): Query<PageQuery>,
+) -> ApiResult<Json<MessagePage>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let after = query.after.as_deref().map(parse_cursor).transpose()?;
+    let before = query.before.as_deref().map(parse_cursor).transpose()?;
+    if after.is_some() && before.is_some() {
+        return Err(ApiError::BadRequest("before 和 after 不能同时指定".to_string()));
+    }
+
+    let page = with_db(&state, &id, move |state, db| {
+        let messages = match after {
+            Some(cursor) => db.get_messages_after(Some(cursor), limit)?,
+            None => db.get_messages_before(before, limit)?,
+        };
+        let next_cursor = match messages.last() {
+            Some(last) if messages.len() as i64 == limit => Some(format_cursor(last)),
+            _ => None,
+        };
+        Ok(MessagePage {
+            messages: messages.into_iter().map(|m| state.message_view(m)).collect(),
+            next_cursor,
+        })
+    })
+    .await?;
+    Ok(Json(page))
+}
+
+async fn get_message(
+    State(state): State<Arc<ServeState>>,
+    UrlPath((id, msg_id)): UrlPath<(String, i64)>,
+) -> ApiResult<Json<MessageView>> {
+    let message = with_db(&state, &id, move |state, db| {
+        Ok(db.get_message(msg_id)?.map(|m| state.message_view(m)))
+    })
+    .await?;
+    message
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("消息不存在: {}", msg_id)))
+}
+
+async fn search(
+    State(state): State<Arc<ServeState>>,
+    Query(query): Query<SearchQuery>,
+) -> ApiResult<Json<Vec<SearchHit>>> {
+    let keyword = query.q.trim().to_string();
+    if keyword.is_empty() {
+        return Err(ApiError::BadRequest("关键词不能为空".to_string()));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+    let ids = match query.conversation.filter(|c| !c.is_empty()) {
+        Some(id) => {
+            state.db_path(&id)?;
+            vec![id]
+        }
+        None => state.conversation_ids()?,
+    };
+
+    let mut hits = Vec::new();
+    for id in ids {
+        let keyword = keyword.clone();
+        let conversation = id.clone();
+        let found = with_db(&state, &id, move |state, db| {
+            Ok(db
+                .search_messages(&keyword, None, limit)?
+                .into_iter()
+                .map(|m| SearchHit { conversation: conversation.clone(), message: state.message_view(m) })
+                .collect::<Vec<_>>())
+        })
+        .await?;
+        hits.extend(found);
+    }
+
+    // 合并各会话的结果，按时间倒序取前 limit 条
+    hits.sort_by(|a, b| {
+        let a = &a.message.stored;
+        let b = &b.message.stored;
+        (b.message.content_head.msg_time, b.id).cmp(&(a.message.content_head.msg_time, a.id))
+    });
+    hits.truncate(limit);
+    Ok(Json(hits))
+}
+
+/// 消息中第 `index` 个元素（或其回复内容中第 `content` 个元素）的媒体文件，首次访问时下载并缓存到 media/<会话id>
+async fn get_media(
+    State(state): State<Arc<ServeState>>,
+    UrlPath((id, msg_id, index)): UrlPath<(String, i64, usize)>,
+    Query(query): Query<MediaQuery>,
+) -> ApiResult<Response> {
+    let stored = with_db(&state, &id, move |_, db| db.get_message(msg_id))
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("消息不存在: {}", msg_id)))?;
+    let elem = media_element(&stored.message.body, index, query.content)?;
+
+    let (url, file, file_uuid, ext) = match elem {
+        Element::Image(image) => (&image.url, &image.file, &image.file_uuid, "jpg"),
+        Element::Video(video) if query.thumb => {
+            let thumb = video
+                .thumb
+                .as_ref()
+                .ok_or_else(|| ApiError::NotFound("视频没有缩略图".to_string()))?;
+            (&thumb.url, &thumb.file, &thumb.file_uuid, "jpg")
+        }
+        Element::Video(video) => (&video.url, &video.file, &video.file_uuid, "mp4"),
+        Element::Voice(voice) => (&voice.url, &voice.file, &voice.file_uuid, "amr"),
+        _ => return Err(ApiError::NotFound(format!("元素 {} 不是媒体", index))),
+    };
+
+    let name = media_file_name(&file.md5, file_uuid, &file.file_name, ext)
+        .ok_or_else(|| ApiError::NotFound("媒体缺少文件名".to_string()))?;
+    let saved = state
+        .media
+        .fetch(url, &format!("{}/{}", id, name))
+        .await
+        .ok_or_else(|| ApiError::NotFound("媒体下载失败或已过期".to_string()))?;
+
+    let path = state.dir.join("media").join(&saved);
+    let bytes = tokio::fs::read(&path).await.map_err(|e| anyhow!(e))?;
+    Ok(([(header::CONTENT_TYPE, content_type(&path))], bytes).into_response())
+}
+
+/// 按位置查找元素，回复之后的元素在回复的内容中
+fn media_element(body: &[Element], index: usize, content: Option<usize>) -> ApiResult<&Element> {
+    let elem = body
+        .get(index)
+        .ok_or_else(|| ApiError::NotFound(format!("元素不存在: {}", index)))?;
+    match (elem, content) {
+        (_, None) => Ok(elem),
+        (Element::Reply(reply), Some(i)) => reply
+            .content
+            .get(i)
+            .ok_or_else(|| ApiError::NotFound(format!("元素不存在: {}.{}", index, i))),
+        (_, Some(_)) => Err(ApiError::BadRequest(format!("元素 {} 不是回复", index))),
+    }
+}
+
+/// 解析 `msg_time:id` 形式的游标
+fn parse_cursor(s: &str) -> ApiResult<(i64, i64)> {
+    s.split_once(':')
+        .and_then(|(time, id)| Some((time.parse().ok()?, id.parse().ok()?)))
+        .ok_or_else(|| ApiError::BadRequest(format!("无效的游标: {}，应为 msg_time:id", s)))
+}
+
+fn format_cursor(stored: &StoredMessage) -> String {
+    format!("{}:{}", stored.message.content_head.msg_time, stored.id)
+}
+
+/// 按扩展名推断的Content-Type
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "mp4" => "video/mp4",
+        "amr" => "audio/amr",
+        "silk" | "slk" => "audio/silk",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cursor() {
+        assert_eq!(parse_cursor("1700000000:42").ok(), Some((1700000000, 42)));
+        assert!(parse_cursor("1700000000").is_err());
+        assert!(parse_cursor("abc:1").is_err());
+    }
+
+    #[test]
+    fn test_media_element() {
+        use crate::message::{ImageElement, ReplyElement, TextElement};
+
+        let image = Element::Image(ImageElement { url: "https://example.com/a".into(), ..Default::default() });
+        let body = vec![Element::Reply(ReplyElement {
+            seq: 1,
+            content: vec![Element::Text(TextElement { text: "看".into() }), image.clone()],
+            ..Default::default()
+        })];
+        assert_eq!(media_element(&body, 0, Some(1)).ok(), Some(&image));
+        assert!(media_element(&body, 0, Some(2)).is_err());
+        assert!(media_element(&body, 1, None).is_err());
+        assert!(matches!(media_element(&body, 0, None), Ok(Element::Reply(_))));
+    }
+}
@@ -0,0 +1,484 @@
+use anyhow::anyhow;
+use axum::body::Bytes;
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::database::{Database, StoredMessage};
+use crate::helper::Helper;
+use crate::onebot::{to_cq_string, Segment};
+use crate::serve::{with_db, ApiError, ServeState, MAX_LIMIT};
+
+/// 历史消息接口默认返回的条数
+const DEFAULT_COUNT: i64 = 20;
+
+/// message_id 低位为数据库行id的位数，高位为会话序号
+const ROW_BITS: u32 = 22;
+const ROW_MASK: u32 = (1 << ROW_BITS) - 1;
+/// 会话序号从1开始，0留给无法直接编码的消息
+const MAX_CONVERSATIONS: usize = (1 << (32 - ROW_BITS)) - 1;
+/// 保存会话序号的文件，位于数据库目录下
+const CONVERSATIONS_FILE: &str = "onebot_conversations.json";
+
+/// OneBot v11 HTTP API 的只读动作
+///
+/// 会话按数据库文件名区分：纯数字的为群（`<群号>.db`），其余为好友（`<uid>.db`，QQ号来自 uids.json）。
+/// message_id 由会话序号和数据库中的行id组成，可直接还原，重启后保持不变。
+pub struct OneBotState {
+    serve: Arc<ServeState>,
+    access_token: Option<String>,
+    message_ids: Mutex<MessageIds>,
+    /// 媒体文件名到 (会话id, 下载链接)
+    media: Mutex<HashMap<String, (String, String)>>,
+}
+
+/// message_id 与 (会话id, 行id) 的对应关系
+///
+/// message_id 按32位无符号数解释，高10位为会话序号，低22位为行id。会话序号按首次出现的顺序分配并保存，
+/// 行id超过22位或会话超过1023个时，在序号0的范围内按顺序分配，这部分只在本次运行中有效，用完后重新分配。
+struct MessageIds {
+    /// 保存会话序号的文件
+    path: PathBuf,
+    /// 序号为下标加一
+    conversations: Vec<String>,
+    overflow: HashMap<i32, (String, i64)>,
+    overflow_ids: HashMap<(String, i64), i32>,
+}
+
+impl MessageIds {
+    fn load(dir: &Path) -> Self {
+        let path = dir.join(CONVERSATIONS_FILE);
+        let conversations = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        MessageIds { path, conversations, overflow: HashMap::new(), overflow_ids: HashMap::new() }
+    }
+
+    /// 会话序号，新会话分配下一个序号并保存
+    fn conversation_index(&mut self, conversation: &str) -> Option<u32> {
+        if let Some(i) = self.conversations.iter().position(|c| c == conversation) {
+            return Some(i as u32 + 1);
+        }
+        if self.conversations.len() >= MAX_CONVERSATIONS {
+            return None;
+        }
+        self.conversations.push(conversation.to_string());
+        let saved = serde_json::to_string_pretty(&self.conversations)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(fs::write(&self.path, content)?));
+        if let Err(e) = saved {
+            Helper::echo(&format!("保存会话序号失败: {:#}", e), "red");
+        }
+        Some(self.conversations.len() as u32)
+    }
+
+    fn encode(&mut self, conversation: &str, id: i64) -> i32 {
+        if (1..=ROW_MASK as i64).contains(&id) {
+            if let Some(index) = self.conversation_index(conversation) {
+                return ((index << ROW_BITS) | id as u32) as i32;
+            }
+        }
+
+        let key = (conversation.to_string(), id);
+        if let Some(message_id) = self.overflow_ids.get(&key) {
+            return *message_id;
+        }
+        if self.overflow.len() >= ROW_MASK as usize {
+            self.overflow.clear();
+            self.overflow_ids.clear();
+        }
+        let message_id = self.overflow.len() as i32 + 1;
+        self.overflow.insert(message_id, key.clone());
+        self.overflow_ids.insert(key, message_id);
+        message_id
+    }
+
+    fn decode(&self, message_id: i32) -> Option<(String, i64)> {
+        let index = (message_id as u32) >> ROW_BITS;
+        let id = (message_id as u32 & ROW_MASK) as i64;
+        match index {
+            0 => self.overflow.get(&message_id).cloned(),
+            index => Some((self.conversations.get(index as usize - 1)?.clone(), id)),
+        }
+    }
+}
+
+/// 会话对方
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Peer {
+    Friend(i64),
+    Group(i64),
+    Unknown,
+}
+
+/// 动作失败，对应OneBot的retcode
+enum ActionError {
+    /// 参数缺失或无效，retcode 100
+    BadParams(String),
+    /// 执行失败，retcode 102
+    Failed(String),
+}
+
+impl From<anyhow::Error> for ActionError {
+    fn from(e: anyhow::Error) -> Self {
+        ActionError::Failed(format!("{:#}", e))
+    }
+}
+
+impl From<ApiError> for ActionError {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::BadRequest(message) | ApiError::NotFound(message) => ActionError::Failed(message),
+            ApiError::Internal(e) => e.into(),
+        }
+    }
+}
+
+type ActionResult = std::result::Result<Value, ActionError>;
+
+impl OneBotState {
+    pub fn new(serve: Arc<ServeState>, access_token: Option<String>) -> Self {
+        OneBotState {
+            message_ids: Mutex::new(MessageIds::load(&serve.dir)),
+            serve,
+            access_token,
+            media: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn peer(&self, conversation: &str) -> Peer {
+        if !conversation.is_empty() && conversation.chars().all(|c| c.is_ascii_digit()) {
+            return conversation.parse().map(Peer::Group).unwrap_or(Peer::Unknown);
+        }
+        self.serve.uins.get(conversation).copied().map(Peer::Friend).unwrap_or(Peer::Unknown)
+    }
+
+    /// 消息的message_id
+    fn message_id(&self, conversation: &str, id: i64) -> i32 {
+        self.message_ids.lock().unwrap().encode(conversation, id)
+    }
+
+    /// 转换为OneBot消息对象，格式与消息事件一致
+    fn to_onebot(&self, conversation: &str, db: &Database, stored: StoredMessage) -> Value {
+        let message = &stored.message;
+        let segments = Segment::from_elements(&message.body, &mut |seq| {
+            let replied = db.get_message_by_seq(seq).ok().flatten()?;
+            Some(self.message_id(conversation, replied.id) as i64)
+        });
+
+        let mut media = self.media.lock().unwrap();
+        for segment in &segments {
+            if let Segment::Image { file, url } | Segment::Record { file, url } | Segment::Video { file, url } = segment {
+                media.insert(file.clone(), (conversation.to_string(), url.clone()));
+            }
+        }
+        drop(media);
+
+        let from_uin = message.routing_head.from_uin;
+        let seq = message.content_head.msg_seq;
+        let mut obj = Map::new();
+        obj.insert("post_type".into(), json!("message"));
+        obj.insert("time".into(), json!(message.content_head.msg_time));
+        obj.insert("self_id".into(), json!(self.serve.names.self_uin()));
+        match self.peer(conversation) {
+            Peer::Group(group_id) => {
+                obj.insert("message_type".into(), json!("group"));
+                obj.insert("sub_type".into(), json!("normal"));
+                obj.insert("group_id".into(), json!(group_id));
+            }
+            _ => {
+                obj.insert("message_type".into(), json!("private"));
+                obj.insert("sub_type".into(), json!("friend"));
+            }
+        }
+        obj.insert("message_id".into(), json!(self.message_id(conversation, stored.id)));
+        obj.insert("real_id".into(), json!(seq));
+        obj.insert("message_seq".into(), json!(seq));
+        obj.insert("user_id".into(), json!(from_uin));
+        obj.insert(
+            "sender".into(),
+            json!({"user_id": from_uin, "nickname": self.serve.names.name(from_uin)}),
+        );
+        obj.insert("raw_message".into(), json!(to_cq_string(&segments)));
+        obj.insert("message".into(), json!(segments));
+        obj.insert("font".into(), json!(0));
+        Value::Object(obj)
+    }
+
+    /// 请求中的access_token，支持 `Authorization: Bearer` 头和 `access_token` 参数
+    fn check_token(&self, headers: &HeaderMap, query: &HashMap<String, String>) -> Option<Response> {
+        let expected = self.access_token.as_deref()?;
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("Token ")))
+            .or_else(|| query.get("access_token").map(String::as_str));
+        match provided {
+            None => Some(StatusCode::UNAUTHORIZED.into_response()),
+            Some(token) if token != expected => Some(StatusCode::FORBIDDEN.into_response()),
+            Some(_) => None,
+        }
+    }
+}
+
+/// 构建路由，动作以 `/<action>` 的形式通过GET参数或POST的JSON请求体调用
+pub fn router(state: Arc<OneBotState>) -> Router {
+    Router::new()
+        .route("/:action", get(action).post(action))
+        .with_state(state)
+}
+
+async fn action(
+    State(state): State<Arc<OneBotState>>,
+    UrlPath(action): UrlPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(response) = state.check_token(&headers, &query) {
+        return response;
+    }
+
+    let mut params: Map<String, Value> = query
+        .iter()
+        .filter(|(key, _)| key.as_str() != "access_token")
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+    if !body.is_empty() {
+        match serde_json::from_slice::<Map<String, Value>>(&body) {
+            Ok(body) => params.extend(body),
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+
+    let result = match action.as_str() {
+        "get_msg" => get_msg(&state, &params).await,
+        "get_friend_list" => get_friend_list(&state),
+        "get_group_msg_history" => get_group_msg_history(&state, &params).await,
+        "get_friend_msg_history" => get_friend_msg_history(&state, &params).await,
+        "get_image" | "get_record" => get_media(&state, &params).await,
+        _ => {
+            let body = json!({"status": "failed", "retcode": 1404, "data": null, "message": "不支持的动作"});
+            return (StatusCode::NOT_FOUND, Json(body)).into_response();
+        }
+    };
+
+    let body = match result {
+        Ok(data) => json!({"status": "ok", "retcode": 0, "data": data}),
+        Err(ActionError::BadParams(message)) => {
+            json!({"status": "failed", "retcode": 100, "data": null, "message": message})
+        }
+        Err(ActionError::Failed(message)) => {
+            json!({"status": "failed", "retcode": 102, "data": null, "message": message})
+        }
+    };
+    Json(body).into_response()
+}
+
+/// 整数参数，兼容字符串形式的数字
+fn param_i64(params: &Map<String, Value>, key: &str) -> std::result::Result<Option<i64>, ActionError> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .map(Some)
+            .ok_or_else(|| ActionError::BadParams(format!("参数 {} 不是整数", key))),
+        Some(Value::String(s)) => s
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| ActionError::BadParams(format!("参数 {} 不是整数", key))),
+        Some(_) => Err(ActionError::BadParams(format!("参数 {} 不是整数", key))),
+    }
+}
+
+fn require_i64(params: &Map<String, Value>, key: &str) -> std::result::Result<i64, ActionError> {
+    param_i64(params, key)?.ok_or_else(|| ActionError::BadParams(format!("缺少参数 {}", key)))
+}
+
+async fn get_msg(state: &Arc<OneBotState>, params: &Map<String, Value>) -> ActionResult {
+    let message_id = i32::try_from(require_i64(params, "message_id")?)
+        .map_err(|_| ActionError::BadParams("message_id 超出范围".to_string()))?;
+
+    let known = state.message_ids.lock().unwrap().decode(message_id);
+    let (conversation, id) = known.ok_or_else(|| ActionError::Failed(format!("消息不存在: {}", message_id)))?;
+
+    let ob = state.clone();
+    let conv = conversation.clone();
+    let message = with_db(&state.serve, &conversation, move |_, db| {
+        Ok(db.get_message(id)?.map(|stored| ob.to_onebot(&conv, &db, stored)))
+    })
+    .await?;
+    message.ok_or_else(|| ActionError::Failed(format!("消息不存在: {}", message_id)))
+}
+
+fn get_friend_list(state: &Arc<OneBotState>) -> ActionResult {
+    let friends: Vec<Value> = state
+        .serve
+        .conversation_ids()?
+        .iter()
+        .filter_map(|id| match state.peer(id) {
+            Peer::Friend(uin) => Some(json!({
+                "user_id": uin,
+                "nickname": state.serve.names.name(uin),
+                "remark": "",
+            })),
+            _ => None,
+        })
+        .collect();
+    Ok(json!(friends))
+}
+
+async fn get_group_msg_history(state: &Arc<OneBotState>, params: &Map<String, Value>) -> ActionResult {
+    let group_id = require_i64(params, "group_id")?;
+    history(state, group_id.to_string(), params).await
+}
+
+async fn get_friend_msg_history(state: &Arc<OneBotState>, params: &Map<String, Value>) -> ActionResult {
+    let user_id = require_i64(params, "user_id")?;
+    let conversation = state
+        .serve
+        .uins
+        .iter()
+        .find(|(_, uin)| **uin == user_id)
+        .map(|(uid, _)| uid.clone())
+        .ok_or_else(|| ActionError::Failed(format!("uids.json 中没有好友: {}", user_id)))?;
+    history(state, conversation, params).await
+}
+
+/// `message_seq` 及之前的 `count` 条消息，按时间正序；不指定 `message_seq` 时为最新的消息
+async fn history(state: &Arc<OneBotState>, conversation: String, params: &Map<String, Value>) -> ActionResult {
+    let seq = param_i64(params, "message_seq")?.filter(|seq| *seq != 0);
+    let count = param_i64(params, "count")?.unwrap_or(DEFAULT_COUNT).clamp(1, MAX_LIMIT);
+
+    let ob = state.clone();
+    let conv = conversation.clone();
+    let messages = with_db(&state.serve, &conversation, move |_, db| {
+        let cursor = match seq {
+            Some(seq) => {
+                let stored = db
+                    .get_message_by_seq(seq)?
+                    .ok_or_else(|| anyhow!("消息不存在: {}", seq))?;
+                // 游标不含自身，行id加一以包含该条消息
+                Some((stored.message.content_head.msg_time, stored.id + 1))
+            }
+            None => None,
+        };
+        let mut messages = db.get_messages_before(cursor, count)?;
+        messages.reverse();
+        Ok(messages
+            .into_iter()
+            .map(|stored| ob.to_onebot(&conv, &db, stored))
+            .collect::<Vec<_>>())
+    })
+    .await?;
+    Ok(json!({ "messages": messages }))
+}
+
+/// get_image / get_record，下载到 media/<会话id> 并返回本地路径；语音不做格式转换
+async fn get_media(state: &Arc<OneBotState>, params: &Map<String, Value>) -> ActionResult {
+    let file = params
+        .get("file")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ActionError::BadParams("缺少参数 file".to_string()))?;
+    if file.starts_with('.') || !file.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')) {
+        return Err(ActionError::BadParams(format!("无效的文件名: {}", file)));
+    }
+
+    let known = state.media.lock().unwrap().get(file).cloned();
+    let saved = match known {
+        Some((conversation, url)) => state.serve.media.fetch(&url, &format!("{}/{}", conversation, file)).await,
+        // 没有记录时查找已缓存的文件
+        None => state
+            .serve
+            .conversation_ids()?
+            .into_iter()
+            .map(|conversation| format!("{}/{}", conversation, file))
+            .find(|path| state.serve.dir.join("media").join(path).is_file()),
+    };
+    let saved = saved.ok_or_else(|| ActionError::Failed(format!("媒体不可用: {}", file)))?;
+
+    let path = fs::canonicalize(state.serve.dir.join("media").join(&saved)).map_err(|e| anyhow!(e))?;
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(json!({
+        "file": path.to_string_lossy(),
+        "filename": file,
+        "size": size,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{MediaFetcher, SenderNames};
+    use crate::message::{ContentHead, Element, Message, RoutingHead, TextElement, SCHEMA_VERSION};
+
+    fn message(seq: i64, from_uin: i64, to_uin: i64, text: &str) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_seq: seq, msg_time: 1000 + seq, ..Default::default() },
+            routing_head: RoutingHead { from_uin, to_uin, ..Default::default() },
+            body: vec![Element::Text(TextElement { text: text.to_string() })],
+        }
+    }
+
+    fn data(result: ActionResult) -> Value {
+        match result {
+            Ok(data) => data,
+            Err(ActionError::BadParams(message) | ActionError::Failed(message)) => panic!("{}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_actions() {
+        let dir = std::env::temp_dir().join(format!("qqhistory-onebot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let group = Database::new(&dir.join("20002.db").to_string_lossy()).unwrap();
+        group.save_messages(&[message(1, 10001, 20002, "a"), message(2, 10002, 20002, "b")]).unwrap();
+        let friend = Database::new(&dir.join("u_friend.db").to_string_lossy()).unwrap();
+        friend.save_messages(&[message(7, 10001, 10003, "c")]).unwrap();
+
+        let serve = ServeState {
+            media: MediaFetcher::new(&dir.join("media"), false).unwrap(),
+            dir: dir.clone(),
+            names: SenderNames::new(Some(10001), None, &[]).unwrap(),
+            uins: HashMap::from([("u_friend".to_string(), 10003)]),
+        };
+        let state = Arc::new(OneBotState::new(Arc::new(serve), None));
+
+        let params = |value: Value| value.as_object().unwrap().clone();
+        let history = data(get_group_msg_history(&state, &params(json!({"group_id": 20002}))).await);
+        let messages = history["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["message_seq"], 1);
+        assert_eq!(messages[1]["group_id"], 20002);
+
+        let history = data(get_group_msg_history(&state, &params(json!({"group_id": "20002", "message_seq": 1}))).await);
+        assert_eq!(history["messages"].as_array().unwrap().len(), 1);
+
+        let history = data(get_friend_msg_history(&state, &params(json!({"user_id": 10003}))).await);
+        let friend_id = history["messages"][0]["message_id"].as_i64().unwrap();
+        assert_eq!(history["messages"][0]["message_type"], "private");
+
+        // message_id 可直接还原，不同会话的相同行id不冲突
+        let group_id = messages[0]["message_id"].as_i64().unwrap();
+        assert_ne!(group_id, friend_id);
+        let msg = data(get_msg(&state, &params(json!({"message_id": group_id}))).await);
+        assert_eq!(msg["raw_message"], "a");
+        let msg = data(get_msg(&state, &params(json!({"message_id": friend_id}))).await);
+        assert_eq!(msg["raw_message"], "c");
+
+        // 重启后会话序号不变
+        let restarted = OneBotState::new(state.serve.clone(), None);
+        assert_eq!(restarted.message_ids.lock().unwrap().decode(friend_id as i32), Some(("u_friend".to_string(), 1)));
+        assert!(matches!(get_msg(&state, &params(json!({"message_id": 12345}))).await, Err(ActionError::Failed(_))));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,520 @@
+pub mod words;
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use crate::database::Database;
+use crate::export::{ExportTimezone, SenderNames};
+use crate::helper::Helper;
+use crate::message::{Element, Message};
+use crate::stats::words::{display_width, tokenize};
+
+/// 超过该间隔的对方消息不计入回复时长（秒）
+const REPLY_WINDOW: i64 = 12 * 3600;
+
+/// 星期的名称，与 `by_weekday` 的顺序一致
+const WEEKDAYS: [&str; 7] = ["周一", "周二", "周三", "周四", "周五", "周六", "周日"];
+
+/// 统计选项
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// 起始时间（Unix时间戳，含）
+    pub start: Option<i64>,
+    /// 结束时间（Unix时间戳，含）
+    pub end: Option<i64>,
+    /// 按日期、小时分组使用的时区
+    pub timezone: ExportTimezone,
+    /// 高频词、最长沉默等列表的条数
+    pub top: usize,
+}
+
+/// 统计报告
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub total_messages: usize,
+    pub first_time: Option<i64>,
+    pub last_time: Option<i64>,
+    /// 有消息的天数
+    pub active_days: usize,
+    pub senders: Vec<SenderStats>,
+    /// 每天的消息数，日期为 `YYYY-MM-DD`
+    pub by_day: BTreeMap<String, usize>,
+    /// 0-23时的消息数
+    pub by_hour: Vec<usize>,
+    /// 周一到周日的消息数
+    pub by_weekday: Vec<usize>,
+    /// `[星期][小时]` 的消息数，星期从周一开始
+    pub hour_of_week: Vec<Vec<usize>>,
+    /// 各元素类型的数量，回复元素中的内容按实际类型计入
+    pub element_types: BTreeMap<String, usize>,
+    /// 连续有消息的最长天数
+    pub longest_streak: Option<Streak>,
+    /// 相邻两条消息间隔最长的若干段
+    pub silent_periods: Vec<SilentPeriod>,
+    pub media: MediaStats,
+    /// 高频词，汉字按jieba内置词典分词，不含单字词
+    pub top_words: Vec<WordCount>,
+}
+
+/// 按发送者统计
+#[derive(Debug, Default, Serialize)]
+pub struct SenderStats {
+    pub uin: i64,
+    pub name: String,
+    pub messages: usize,
+    /// 文本元素的字符数
+    pub text_chars: usize,
+    /// 回复对方消息的次数（间隔在12小时内）
+    pub replies: usize,
+    /// 平均回复时长（秒）
+    pub avg_reply_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Streak {
+    pub start: String,
+    pub end: String,
+    pub days: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SilentPeriod {
+    pub from: i64,
+    pub to: i64,
+    pub seconds: i64,
+}
+
+/// 富媒体数量、大小（字节）和时长（秒）
+#[derive(Debug, Default, Serialize)]
+pub struct MediaStats {
+    pub images: usize,
+    pub image_bytes: u64,
+    pub videos: usize,
+    pub video_bytes: u64,
+    pub video_seconds: u64,
+    pub voices: usize,
+    pub voice_bytes: u64,
+    pub voice_seconds: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WordCount {
+    pub word: String,
+    pub count: usize,
+}
+
+/// 逐条累计统计数据
+pub struct StatsCollector<'a> {
+    names: &'a SenderNames,
+    options: StatsOptions,
+    report: Report,
+    senders: HashMap<i64, SenderStats>,
+    /// 每个发送者回复时长的总和
+    reply_seconds: HashMap<i64, i64>,
+    words: HashMap<String, usize>,
+    last: Option<(i64, i64)>,
+}
+
+impl<'a> StatsCollector<'a> {
+    pub fn new(names: &'a SenderNames, options: StatsOptions) -> Self {
+        let report = Report {
+            by_hour: vec![0; 24],
+            by_weekday: vec![0; 7],
+            hour_of_week: vec![vec![0; 24]; 7],
+            ..Default::default()
+        };
+        StatsCollector {
+            names,
+            options,
+            report,
+            senders: HashMap::new(),
+            reply_seconds: HashMap::new(),
+            words: HashMap::new(),
+            last: None,
+        }
+    }
+
+    /// 统计数据库中时间范围内的所有消息
+    pub fn collect(db: &Database, names: &'a SenderNames, options: StatsOptions) -> Result<Report> {
+        let (start, end) = (options.start, options.end);
+        let mut collector = StatsCollector::new(names, options);
+        db.for_each_message(start, end, |stored| {
+            collector.add(&stored.message);
+            Ok(())
+        })?;
+        Ok(collector.finish())
+    }
+
+    /// 按时间顺序加入一条消息
+    pub fn add(&mut self, message: &Message) {
+        let msg_time = message.content_head.msg_time;
+        let from_uin = message.routing_head.from_uin;
+        let report = &mut self.report;
+        report.total_messages += 1;
+        report.first_time.get_or_insert(msg_time);
+        report.last_time = Some(msg_time);
+
+        let day = self.options.timezone.format(msg_time, "%Y-%m-%d");
+        *report.by_day.entry(day).or_default() += 1;
+        let (weekday, hour) = self
+            .options
+            .timezone
+            .format(msg_time, "%u %H")
+            .split_once(' ')
+            .and_then(|(w, h)| Some((w.parse::<usize>().ok()? - 1, h.parse::<usize>().ok()?)))
+            .unwrap_or((0, 0));
+        report.by_hour[hour] += 1;
+        report.by_weekday[weekday] += 1;
+        report.hour_of_week[weekday][hour] += 1;
+
+        let sender = self.senders.entry(from_uin).or_insert_with(|| SenderStats {
+            uin: from_uin,
+            name: self.names.name(from_uin),
+            ..Default::default()
+        });
+        sender.messages += 1;
+
+        if let Some((last_time, last_uin)) = self.last {
+            let gap = msg_time - last_time;
+            if last_uin != from_uin && gap <= REPLY_WINDOW {
+                sender.replies += 1;
+                *self.reply_seconds.entry(from_uin).or_default() += gap;
+            }
+            report.silent_periods.push(SilentPeriod { from: last_time, to: msg_time, seconds: gap });
+            if report.silent_periods.len() > self.options.top * 4 {
+                Self::keep_longest(&mut report.silent_periods, self.options.top);
+            }
+        }
+        self.last = Some((msg_time, from_uin));
+
+        for elem in &message.body {
+            Self::add_element(elem, report, sender, &mut self.words);
+        }
+    }
+
+    fn add_element(elem: &Element, report: &mut Report, sender: &mut SenderStats, words: &mut HashMap<String, usize>) {
+        if let Element::Reply(reply) = elem {
+            for content in &reply.content {
+                Self::add_element(content, report, sender, words);
+            }
+            return;
+        }
+
+        *report.element_types.entry(elem.kind().to_string()).or_default() += 1;
+        let media = &mut report.media;
+        match elem {
+            Element::Text(t) => {
+                sender.text_chars += t.text.chars().filter(|c| !c.is_whitespace()).count();
+                for word in tokenize(&t.text) {
+                    *words.entry(word).or_default() += 1;
+                }
+            }
+            Element::Image(image) => {
+                media.images += 1;
+                media.image_bytes += image.file.size;
+            }
+            Element::Video(video) => {
+                media.videos += 1;
+                media.video_bytes += video.file.size;
+                media.video_seconds += video.duration as u64;
+            }
+            Element::Voice(voice) => {
+                media.voices += 1;
+                media.voice_bytes += voice.file.size;
+                media.voice_seconds += voice.duration as u64;
+            }
+            _ => {}
+        }
+    }
+
+    fn keep_longest(periods: &mut Vec<SilentPeriod>, top: usize) {
+        periods.sort_by_key(|p| std::cmp::Reverse(p.seconds));
+        periods.truncate(top);
+    }
+
+    pub fn finish(mut self) -> Report {
+        let mut report = self.report;
+        report.active_days = report.by_day.len();
+        Self::keep_longest(&mut report.silent_periods, self.options.top);
+
+        // 最长连续天数
+        let mut run: Option<(NaiveDate, NaiveDate)> = None;
+        for day in report.by_day.keys() {
+            let Ok(date) = NaiveDate::parse_from_str(day, "%Y-%m-%d") else { continue };
+            run = match run {
+                Some((start, end)) if end.succ_opt() == Some(date) => Some((start, date)),
+                _ => Some((date, date)),
+            };
+            let (start, end) = run.unwrap();
+            let days = (end - start).num_days() as usize + 1;
+            if report.longest_streak.as_ref().is_none_or(|s| days > s.days) {
+                report.longest_streak = Some(Streak {
+                    start: start.format("%Y-%m-%d").to_string(),
+                    end: end.format("%Y-%m-%d").to_string(),
+                    days,
+                });
+            }
+        }
+
+        let mut senders: Vec<SenderStats> = self.senders.into_values().collect();
+        for sender in &mut senders {
+            if sender.replies > 0 {
+                let total = self.reply_seconds.get(&sender.uin).copied().unwrap_or(0);
+                sender.avg_reply_seconds = Some(total as f64 / sender.replies as f64);
+            }
+        }
+        senders.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.uin.cmp(&b.uin)));
+        report.senders = senders;
+
+        let mut words: Vec<WordCount> = self
+            .words
+            .drain()
+            .map(|(word, count)| WordCount { word, count })
+            .collect();
+        words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        words.truncate(self.options.top);
+        report.top_words = words;
+        report
+    }
+}
+
+impl Report {
+    /// 以表格形式输出到终端
+    pub fn print(&self, timezone: ExportTimezone, top: usize) {
+        let time = |ts: i64| timezone.format(ts, "%Y-%m-%d %H:%M:%S");
+        Helper::echo("概览", "cyan");
+        print_table(
+            &["项目", "值"],
+            vec![
+                vec!["消息数".to_string(), self.total_messages.to_string()],
+                vec!["首条消息".to_string(), self.first_time.map(time).unwrap_or_default()],
+                vec!["末条消息".to_string(), self.last_time.map(time).unwrap_or_default()],
+                vec!["有消息的天数".to_string(), self.active_days.to_string()],
+                vec![
+                    "最长连续天数".to_string(),
+                    self.longest_streak
+                        .as_ref()
+                        .map(|s| format!("{} 天（{} ~ {}）", s.days, s.start, s.end))
+                        .unwrap_or_default(),
+                ],
+            ],
+        );
+
+        Helper::echo("\n发送者", "cyan");
+        print_table(
+            &["发送者", "消息数", "占比", "文字数", "回复次数", "平均回复时长"],
+            self.senders
+                .iter()
+                .map(|s| {
+                    vec![
+                        s.name.clone(),
+                        s.messages.to_string(),
+                        percent(s.messages, self.total_messages),
+                        s.text_chars.to_string(),
+                        s.replies.to_string(),
+                        s.avg_reply_seconds.map(|v| format_duration(v as i64)).unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        );
+
+        Helper::echo("\n按小时", "cyan");
+        print_histogram(
+            (0..24).map(|h| (format!("{:02}时", h), self.by_hour[h])).collect(),
+        );
+
+        Helper::echo("\n按星期", "cyan");
+        print_histogram(
+            WEEKDAYS.iter().zip(&self.by_weekday).map(|(d, c)| (d.to_string(), *c)).collect(),
+        );
+
+        Helper::echo(&format!("\n消息最多的 {} 天", top), "cyan");
+        let mut days: Vec<(&String, &usize)> = self.by_day.iter().collect();
+        days.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        print_table(
+            &["日期", "消息数"],
+            days.iter().take(top).map(|(d, c)| vec![d.to_string(), c.to_string()]).collect(),
+        );
+
+        Helper::echo("\n元素类型", "cyan");
+        print_table(
+            &["类型", "数量"],
+            self.element_types.iter().map(|(k, c)| vec![k.clone(), c.to_string()]).collect(),
+        );
+
+        Helper::echo("\n富媒体", "cyan");
+        let media = &self.media;
+        print_table(
+            &["类型", "数量", "大小", "时长"],
+            vec![
+                vec!["图片".to_string(), media.images.to_string(), format_bytes(media.image_bytes), String::new()],
+                vec![
+                    "视频".to_string(),
+                    media.videos.to_string(),
+                    format_bytes(media.video_bytes),
+                    format_duration(media.video_seconds as i64),
+                ],
+                vec![
+                    "语音".to_string(),
+                    media.voices.to_string(),
+                    format_bytes(media.voice_bytes),
+                    format_duration(media.voice_seconds as i64),
+                ],
+            ],
+        );
+
+        Helper::echo("\n最长沉默", "cyan");
+        print_table(
+            &["开始", "结束", "时长"],
+            self.silent_periods
+                .iter()
+                .map(|p| vec![time(p.from), time(p.to), format_duration(p.seconds)])
+                .collect(),
+        );
+
+        Helper::echo("\n高频词", "cyan");
+        Helper::echo("（汉字按jieba内置词典分词，单字词不计入，新词和网络用语可能切分不准）", "white");
+        print_table(
+            &["词", "次数"],
+            self.top_words.iter().map(|w| vec![w.word.clone(), w.count.to_string()]).collect(),
+        );
+    }
+}
+
+/// 按显示宽度对齐输出表格
+pub fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| display_width(h)).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(display_width(cell));
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - display_width(cell))))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    println!("{}", line(headers.to_vec()));
+    let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    println!("{}", line(dashes.iter().map(String::as_str).collect()));
+    for row in &rows {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// 带条形图的计数
+fn print_histogram(items: Vec<(String, usize)>) {
+    let max = items.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+    let rows = items
+        .into_iter()
+        .map(|(label, count)| vec![label, count.to_string(), "█".repeat((count * 40).div_ceil(max))])
+        .collect();
+    print_table(&["", "消息数", ""], rows);
+}
+
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        return String::new();
+    }
+    format!("{:.1}%", part as f64 * 100.0 / total as f64)
+}
+
+/// 如 `3天4小时`、`5分12秒`
+pub fn format_duration(seconds: i64) -> String {
+    let (days, hours, minutes, secs) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}秒", secs),
+        (0, 0, _) => format!("{}分{}秒", minutes, secs),
+        (0, _, _) => format!("{}小时{}分", hours, minutes),
+        _ => format!("{}天{}小时", days, hours),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        1048576..=1073741823 => format!("{:.1} MB", bytes as f64 / 1048576.0),
+        _ => format!("{:.2} GB", bytes as f64 / 1073741824.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{ContentHead, ImageElement, ReplyElement, RoutingHead, TextElement, SCHEMA_VERSION};
+
+    /// 2024-01-01 00:00:00 +08:00，周一
+    const MONDAY: i64 = 1704038400;
+
+    fn message(msg_time: i64, from_uin: i64, body: Vec<Element>) -> Message {
+        Message {
+            version: SCHEMA_VERSION,
+            content_head: ContentHead { msg_time, msg_seq: msg_time, ..Default::default() },
+            routing_head: RoutingHead { from_uin, to_uin: 3 - from_uin, ..Default::default() },
+            body,
+        }
+    }
+
+    #[test]
+    fn test_collect() {
+        let text = |t: &str| vec![Element::Text(TextElement { text: t.to_string() })];
+        let names = SenderNames::new(Some(1), Some("我".to_string()), &["2=对方".to_string()]).unwrap();
+        let options = StatsOptions { start: None, end: None, timezone: "+08:00".parse().unwrap(), top: 2 };
+        let mut collector = StatsCollector::new(&names, options);
+        let reply = Element::Reply(ReplyElement { content: vec![Element::Image(ImageElement::default())], ..Default::default() });
+        for msg in [
+            message(MONDAY + 9 * 3600, 1, text("早上好")),
+            message(MONDAY + 9 * 3600 + 60, 2, text("早")),
+            message(MONDAY + 9 * 3600 + 180, 1, text("吃了吗")),
+            // 周二23:30，同一发送者连续发言不算回复
+            message(MONDAY + 86400 + 84600, 1, text("晚安")),
+            // 周三00:10（UTC仍为周二）
+            message(MONDAY + 2 * 86400 + 600, 2, vec![reply]),
+            // 周五10:00，间隔超过12小时不算回复
+            message(MONDAY + 4 * 86400 + 36000, 1, text("在吗")),
+        ] {
+            collector.add(&msg);
+        }
+        let report = collector.finish();
+
+        assert_eq!(report.total_messages, 6);
+        assert_eq!(report.active_days, 4);
+        let senders: Vec<_> = report.senders.iter().map(|s| (s.name.as_str(), s.messages, s.replies, s.avg_reply_seconds)).collect();
+        assert_eq!(senders, [("我", 4, 1, Some(120.0)), ("对方", 2, 2, Some(1230.0))]);
+
+        let streak = report.longest_streak.unwrap();
+        assert_eq!((streak.start.as_str(), streak.end.as_str(), streak.days), ("2024-01-01", "2024-01-03", 3));
+
+        let silent: Vec<_> = report.silent_periods.iter().map(|p| (p.from - MONDAY, p.seconds)).collect();
+        assert_eq!(silent, [(2 * 86400 + 600, 208200), (9 * 3600 + 180, 138420)]);
+
+        let mut buckets = Vec::new();
+        for (weekday, hours) in report.hour_of_week.iter().enumerate() {
+            for (hour, &count) in hours.iter().enumerate() {
+                if count > 0 {
+                    buckets.push((weekday, hour, count));
+                }
+            }
+        }
+        assert_eq!(buckets, [(0, 9, 3), (1, 23, 1), (2, 0, 1), (4, 10, 1)]);
+        assert_eq!(report.by_weekday, [3, 1, 1, 0, 1, 0, 0]);
+        assert_eq!(report.by_day["2024-01-03"], 1);
+        assert_eq!(report.element_types["image"], 1);
+        assert!(!report.element_types.contains_key("reply"));
+        assert_eq!(report.media.images, 1);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(42), "42秒");
+        assert_eq!(format_duration(312), "5分12秒");
+        assert_eq!(format_duration(3 * 86400 + 4 * 3600 + 5), "3天4小时");
+    }
+}
@@ -0,0 +1,101 @@
+use jieba_rs::Jieba;
+use std::sync::OnceLock;
+
+/// 英文常见停用词
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "you", "are", "was", "but", "not", "with", "this", "that", "have", "has", "had",
+    "its", "it's", "can", "will", "just", "from", "what", "all", "too", "there", "they", "then", "than",
+    "http", "https", "www", "com",
+];
+
+/// 是否为中日韩文字
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'   // 平假名、片假名
+        | '\u{3400}'..='\u{4dbf}' // 扩展A
+        | '\u{4e00}'..='\u{9fff}' // 基本汉字
+        | '\u{ac00}'..='\u{d7af}' // 谚文
+        | '\u{f900}'..='\u{faff}' // 兼容汉字
+    )
+}
+
+/// 是否为汉字，交给分词词典处理
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+fn jieba() -> &'static Jieba {
+    static JIEBA: OnceLock<Jieba> = OnceLock::new();
+    JIEBA.get_or_init(Jieba::new)
+}
+
+/// 切分文本用于词频统计
+///
+/// 连续的汉字用jieba的内置词典分词，只保留两字及以上的词；其他文字（包括假名和谚文）
+/// 按字母数字连续切分并转为小写，去掉长度小于2、纯数字和停用词。
+/// 词典只收录简体中文常用词，网络用语、人名等新词的切分可能不准确，单字词不计入，日文按假名连续切分。
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut han = String::new();
+    let mut word = String::new();
+
+    let flush_han = |han: &mut String, tokens: &mut Vec<String>| {
+        if !han.is_empty() {
+            tokens.extend(
+                jieba()
+                    .cut(han, true)
+                    .into_iter()
+                    .filter(|w| w.chars().count() >= 2)
+                    .map(str::to_string),
+            );
+            han.clear();
+        }
+    };
+    let flush_word = |word: &mut String, tokens: &mut Vec<String>| {
+        if word.chars().count() >= 2
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOP_WORDS.contains(&word.as_str())
+        {
+            tokens.push(word.clone());
+        }
+        word.clear();
+    };
+
+    for c in text.chars() {
+        if is_han(c) {
+            flush_word(&mut word, &mut tokens);
+            han.push(c);
+        } else if c.is_alphanumeric() || (c == '\'' && !word.is_empty()) {
+            flush_han(&mut han, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_han(&mut han, &mut tokens);
+            flush_word(&mut word, &mut tokens);
+        }
+    }
+    flush_han(&mut han, &mut tokens);
+    flush_word(&mut word, &mut tokens);
+    tokens
+}
+
+/// 终端中的显示宽度，中日韩文字和全角字符占两列
+pub fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if is_cjk(c) || matches!(c, '\u{ff00}'..='\u{ffef}' | '\u{3000}'..='\u{303f}') { 2 } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("今天天气不错 The Rust好,2024 ok"),
+            vec!["今天天气", "不错", "rust", "ok"]
+        );
+        assert_eq!(tokenize("我们今天去吃饭"), vec!["我们", "今天", "吃饭"]);
+        assert_eq!(display_width("ab你好"), 6);
+    }
+}
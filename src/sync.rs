@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use crate::database::Database;
+use crate::elem::parser::ElemParser;
+use crate::message::{value_to_i64, Message};
+use crate::profile::Profile;
+
+/// 解析接口返回的一条消息（漫游消息和离线消息格式相同）
+pub fn parse_message(msg: &Value) -> Result<Message> {
+    let elems = msg
+        .get("body")
+        .context("缺少body")?
+        .get("rich_text")
+        .and_then(|rt| rt.get("elems"))
+        .and_then(|e| e.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let parser = ElemParser::new(elems);
+    Message::from_roam(msg, parser.build())
+}
+
+/// 离线消息中的一条消息及其所属会话
+#[derive(Debug)]
+pub struct OfflineMessage {
+    /// 会话，即数据库文件名：私聊为对方uid，群聊为群号
+    pub conversation: String,
+    /// 私聊对方的QQ号，群聊为None
+    pub peer_uin: Option<i64>,
+    pub message: Message,
+}
+
+/// 拆分 `sso_get_offline_msg` 的返回值，无法解析的消息跳过
+pub fn split_offline(data: &Value, self_uin: i64) -> Vec<OfflineMessage> {
+    let mut result = Vec::new();
+
+    for c2c in data.get("c2c_msg_list").and_then(Value::as_array).into_iter().flatten() {
+        for msg in c2c.get("msgs").and_then(Value::as_array).into_iter().flatten() {
+            let Ok(message) = parse_message(msg) else { continue };
+            let routing = &message.routing_head;
+            let (peer_uin, peer_uid) = if routing.from_uin == self_uin {
+                (routing.to_uin, routing.to_uid.clone())
+            } else {
+                (routing.from_uin, routing.from_uid.clone())
+            };
+            if peer_uid.is_empty() {
+                continue;
+            }
+            result.push(OfflineMessage { conversation: peer_uid, peer_uin: Some(peer_uin), message });
+        }
+    }
+
+    for group in data.get("group_msg_list").and_then(Value::as_array).into_iter().flatten() {
+        for msg in group.get("msg").and_then(Value::as_array).into_iter().flatten() {
+            let Some(group_code) = group_code(group, msg) else { continue };
+            let Ok(mut message) = parse_message(msg) else { continue };
+            // 群消息的to_uin为群号，与 `import ntqq` 一致
+            message.routing_head.to_uin = group_code;
+            result.push(OfflineMessage { conversation: group_code.to_string(), peer_uin: None, message });
+        }
+    }
+    result
+}
+
+/// 群号：列表项的 group_code，或 routing_head 中的群信息
+fn group_code(group: &Value, msg: &Value) -> Option<i64> {
+    [
+        group.get("group_code"),
+        msg.pointer("/routing_head/group/group_code"),
+        msg.pointer("/routing_head/group_code"),
+    ]
+    .into_iter()
+    .flatten()
+    .map(value_to_i64)
+    .find(|code| *code > 0)
+}
+
+/// 按会话打开的数据库，首次使用时创建
+pub struct Archives<'a> {
+    profile: &'a Profile,
+    dbs: HashMap<String, Database>,
+}
+
+impl<'a> Archives<'a> {
+    pub fn new(profile: &'a Profile) -> Self {
+        Archives { profile, dbs: HashMap::new() }
+    }
+
+    pub fn get(&mut self, conversation: &str) -> Result<&Database> {
+        if !self.dbs.contains_key(conversation) {
+            fs::create_dir_all(self.profile.db_dir())?;
+            let path = self.profile.db_file(conversation);
+            let db = Database::new(&path.to_string_lossy())?;
+            self.dbs.insert(conversation.to_string(), db);
+        }
+        Ok(&self.dbs[conversation])
+    }
+
+    /// 保存消息，返回新增的条数（已有的消息会被更新但不计入）
+    pub fn save(&mut self, conversation: &str, messages: &[Message]) -> Result<usize> {
+        let db = self.get(conversation)?;
+        let mut seen = HashSet::new();
+        let mut new = 0;
+        for message in messages {
+            let seq = message.content_head.msg_seq;
+            if seen.insert(seq) && !db.message_exists(seq)? {
+                new += 1;
+            }
+        }
+        db.save_messages(messages)?;
+        Ok(new)
+    }
+
+    /// 会话中最新一条消息的时间
+    pub fn latest_time(&mut self, conversation: &str) -> Result<Option<i64>> {
+        let db = self.get(conversation)?;
+        Ok(db
+            .get_messages_before(None, 1)?
+            .first()
+            .map(|m| m.message.content_head.msg_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn raw(from_uin: i64, from_uid: &str, to_uin: i64, to_uid: &str, seq: i64) -> Value {
+        json!({
+            "content_head": {"msg_time": 1700000000, "nt_msg_seq": seq},
+            "routing_head": {"from_uin": from_uin, "from_uid": from_uid, "to_uin": to_uin, "to_uid": to_uid,
+                             "group": {"group_code": "123456"}},
+            "body": {"rich_text": {"elems": [{"text": {"str": "hi"}}]}},
+        })
+    }
+
+    #[test]
+    fn test_split_offline() {
+        let data = json!({
+            "c2c_msg_list": [{"msgs": [raw(1, "u_self", 2, "u_peer", 1), raw(2, "u_peer", 1, "u_self", 2)]}],
+            "group_msg_list": [{"msg": [raw(3, "u_other", 0, "", 3)]}],
+        });
+        let messages = split_offline(&data, 1);
+        let conversations: Vec<_> = messages.iter().map(|m| (m.conversation.as_str(), m.peer_uin)).collect();
+        assert_eq!(conversations, vec![("u_peer", Some(2)), ("u_peer", Some(2)), ("123456", None)]);
+        assert_eq!(messages[2].message.routing_head.to_uin, 123456);
+    }
+}